@@ -0,0 +1,29 @@
+//! Shows the zoom presets bound to the number keys.
+
+use bevy::prelude::*;
+use bevy_m8::prelude::*;
+
+fn select_zoom_preset(
+    keys: Res<ButtonInput<KeyCode>>,
+    dimensions: Res<M8DisplayDimensions>,
+    mut zoom: ResMut<M8Zoom>,
+) {
+    let presets = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3];
+    for (key, region) in presets.into_iter().zip(M8Zoom::presets(*dimensions)) {
+        if keys.just_pressed(key) {
+            zoom.region = region;
+            zoom.enabled = true;
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Digit0) {
+        zoom.enabled = false;
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins(M8Plugin::default())
+        .add_systems(Update, select_zoom_preset)
+        .run();
+}
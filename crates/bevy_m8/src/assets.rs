@@ -1,6 +1,7 @@
 //! This is responsible for loading the necessary font files.
 
 use bevy::prelude::*;
+#[cfg(feature = "render")]
 use bevy_asset_loader::{
     asset_collection::AssetCollection,
     loading_state::{LoadingState, LoadingStateAppExt, config::ConfigureLoadingState},
@@ -8,17 +9,24 @@ use bevy_asset_loader::{
 
 use crate::M8LoadingState;
 
+/// The path of the stock M8 font atlas.
+#[cfg(any(feature = "render", feature = "serde"))]
+pub(crate) const DEFAULT_FONT_PATH: &str = "font.png";
+
 /// The assets used by the M8.
+#[cfg(feature = "render")]
 #[derive(AssetCollection, Resource)]
 pub struct M8Assets {
     #[asset(path = "font.png")]
     pub font_small: Handle<Image>,
 }
 
-/// This plugin provides asset loading capabilities.
+/// This plugin provides asset loading capabilities. Without the `render`
+/// feature there is nothing to load, and the app starts out running.
 pub struct M8AssetsPlugin;
 
 impl Plugin for M8AssetsPlugin {
+    #[cfg(feature = "render")]
     fn build(&self, app: &mut App) {
         app.init_state::<M8LoadingState>().add_loading_state(
             LoadingState::new(M8LoadingState::Loading)
@@ -26,4 +34,9 @@ impl Plugin for M8AssetsPlugin {
                 .load_collection::<M8Assets>(),
         );
     }
+
+    #[cfg(not(feature = "render"))]
+    fn build(&self, app: &mut App) {
+        app.insert_state(M8LoadingState::Running);
+    }
 }
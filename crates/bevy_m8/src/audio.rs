@@ -2,96 +2,573 @@
 
 use bevy::prelude::*;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use crossbeam_channel::bounded;
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+use crossbeam_channel::Sender;
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    },
+    time::Duration,
 };
 
+use crate::{
+    ring_buffer::{RingConsumer, RingProducer, capacity_for_latency, ring_buffer},
+    wav::M8WavRecorder,
+};
+
+/// The default target latency of the audio passthrough.
+const DEFAULT_AUDIO_LATENCY_MS: u32 = 20;
+
+/// How long to wait between attempts to set up the audio passthrough.
+const AUDIO_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often the audio devices are checked for changes.
+const AUDIO_DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How long the meter's peak is held by default.
+const DEFAULT_PEAK_HOLD: Duration = Duration::from_secs(1);
+
+/// How long the meter takes to fall from full scale by default.
+const DEFAULT_METER_DECAY: Duration = Duration::from_millis(500);
+
 /// Stores the audio input and output streams.
 #[derive(Resource)]
 struct M8StreamResource {
-    _input: cpal::Stream,
-    _output: cpal::Stream,
+    input: cpal::Stream,
+    output: cpal::Stream,
+    /// The channels and sample rate of the input.
+    format: (u16, u32),
 }
 
 /// Error that can occur during audio processing.
 #[derive(Resource, Clone)]
 struct M8AudioError(Arc<AtomicBool>);
 
+/// Configuration of the audio passthrough. Changing it at
+/// runtime rebuilds the audio streams.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8AudioConfig {
+    /// The target latency between the M8 input and the output device.
+    pub latency_ms: u32,
+}
+
+impl Default for M8AudioConfig {
+    fn default() -> Self {
+        Self {
+            latency_ms: DEFAULT_AUDIO_LATENCY_MS,
+        }
+    }
+}
+
+/// Counters describing the health of the audio passthrough.
+#[derive(Resource, Clone, Default)]
+pub struct M8AudioStats {
+    underruns: Arc<AtomicU64>,
+    overruns: Arc<AtomicU64>,
+}
+
+impl M8AudioStats {
+    /// The number of output callbacks which ran out of buffered samples.
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// The number of input callbacks which found the buffer full.
+    pub fn overruns(&self) -> u64 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Pushes a block of input samples, counting an overrun if the buffer
+    /// couldn't take all of them. Samples which didn't fit are dropped.
+    pub fn push_input(&self, producer: &RingProducer, samples: &[f32]) {
+        let mut overrun = false;
+        for &sample in samples {
+            overrun |= !producer.push(sample);
+        }
+        if overrun {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Fills a block of output samples, with silence once the buffer runs
+    /// out, counting an underrun if it did.
+    pub fn pop_output(&self, consumer: &RingConsumer, samples: &mut [f32]) {
+        let mut underrun = false;
+        for sample in samples.iter_mut() {
+            *sample = consumer.pop().unwrap_or_else(|| {
+                underrun = true;
+                0.0
+            });
+        }
+        if underrun {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The loudest sample of each channel since the meter last read them,
+/// stored as the bits of an `f32`.
+#[derive(Resource, Clone, Default)]
+struct M8AudioPeaks {
+    left: Arc<AtomicU32>,
+    right: Arc<AtomicU32>,
+}
+
+impl M8AudioPeaks {
+    fn record(&self, left: f32, right: f32) {
+        // The bits of non-negative floats order like the floats themselves.
+        self.left.fetch_max(left.to_bits(), Ordering::Relaxed);
+        self.right.fetch_max(right.to_bits(), Ordering::Relaxed);
+    }
+
+    fn take(&self) -> (f32, f32) {
+        (
+            f32::from_bits(self.left.swap(0, Ordering::Relaxed)),
+            f32::from_bits(self.right.swap(0, Ordering::Relaxed)),
+        )
+    }
+}
+
+/// The level of the M8's audio per channel, between 0 and 1, falling back
+/// smoothly after loud passages so a meter doesn't flicker.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct M8AudioLevel {
+    pub left: f32,
+    pub right: f32,
+    /// The loudest level of either channel, held for
+    /// [M8AudioMeterConfig::peak_hold] before it falls.
+    pub peak: f32,
+}
+
+/// How the [M8AudioLevel] falls back after loud passages.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8AudioMeterConfig {
+    /// How long the peak is held before it falls.
+    pub peak_hold: Duration,
+    /// How long the levels take to fall from full scale to silence.
+    pub decay: Duration,
+}
+
+impl Default for M8AudioMeterConfig {
+    fn default() -> Self {
+        Self {
+            peak_hold: DEFAULT_PEAK_HOLD,
+            decay: DEFAULT_METER_DECAY,
+        }
+    }
+}
+
+/// Starts or stops recording the M8's audio to a WAV file, with the
+/// channels and sample rate of its input. Starting a recording stops the
+/// previous one.
+#[derive(Message, Debug, Clone, PartialEq, Eq)]
+pub enum M8AudioRecording {
+    Start(PathBuf),
+    Stop,
+}
+
+/// Where the input callback sends the samples to record, if anywhere. The
+/// callback only tries the lock, so it never waits on it.
+#[derive(Resource, Clone, Default)]
+struct M8AudioTap(Arc<Mutex<Option<Sender<Vec<f32>>>>>);
+
+/// The recording in progress.
+#[derive(Resource, Default)]
+struct M8AudioRecorder(Option<M8WavRecorder>);
+
+/// Reasons the audio passthrough couldn't be set up.
+#[derive(Debug)]
+enum M8AudioSetupError {
+    NoInputDevice,
+    NoOutputDevice,
+    Devices(cpal::DevicesError),
+    DefaultConfig(cpal::DefaultStreamConfigError),
+    SupportedConfigs(cpal::SupportedStreamConfigsError),
+    BuildStream(cpal::BuildStreamError),
+    PlayStream(cpal::PlayStreamError),
+}
+
+impl fmt::Display for M8AudioSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoInputDevice => write!(f, "No M8 audio input device found"),
+            Self::NoOutputDevice => write!(f, "No audio output device found"),
+            Self::Devices(e) => write!(f, "Failed to list audio devices: {}", e),
+            Self::DefaultConfig(e) => write!(f, "Failed to get the default stream config: {}", e),
+            Self::SupportedConfigs(e) => write!(f, "Failed to list the output configs: {}", e),
+            Self::BuildStream(e) => write!(f, "Failed to build an audio stream: {}", e),
+            Self::PlayStream(e) => write!(f, "Failed to play an audio stream: {}", e),
+        }
+    }
+}
+
+/// Paces the attempts to set up the audio passthrough after a failure.
+#[derive(Resource)]
+struct M8AudioRecovery {
+    timer: Timer,
+    last_error: Option<String>,
+}
+
+impl Default for M8AudioRecovery {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(AUDIO_RETRY_INTERVAL, TimerMode::Repeating),
+            last_error: None,
+        }
+    }
+}
+
+/// The audio devices in use, used to notice when they change.
+#[derive(Resource, Default, PartialEq, Eq)]
+struct M8AudioDevices {
+    input: Option<String>,
+    output: Option<String>,
+}
+
+impl M8AudioDevices {
+    fn current(host: &cpal::Host) -> Self {
+        Self {
+            input: find_m8_input(host)
+                .ok()
+                .flatten()
+                .and_then(|device| device_name(&device)),
+            output: host
+                .default_output_device()
+                .and_then(|device| device_name(&device)),
+        }
+    }
+}
+
+fn device_name(device: &cpal::Device) -> Option<String> {
+    device
+        .description()
+        .ok()
+        .map(|description| description.name().to_string())
+}
+
+fn find_m8_input(host: &cpal::Host) -> Result<Option<cpal::Device>, cpal::DevicesError> {
+    Ok(host.input_devices()?.find(|x| {
+        device_name(x)
+            .map(|name| name.contains("M8"))
+            .unwrap_or(false)
+    }))
+}
+
+/// Picks an output config matching the input's channels and sample rate, so
+/// samples can be passed straight through. Returns None if there is none.
+fn pick_output_config(
+    ranges: impl IntoIterator<Item = cpal::SupportedStreamConfigRange>,
+    input: &cpal::StreamConfig,
+) -> Option<cpal::StreamConfig> {
+    ranges
+        .into_iter()
+        .filter(|range| {
+            range.channels() == input.channels && range.sample_format() == cpal::SampleFormat::F32
+        })
+        .find_map(|range| range.try_with_sample_rate(input.sample_rate))
+        .map(|config| config.config())
+}
+
+fn build_m8_audio(
+    host: &cpal::Host,
+    config: M8AudioConfig,
+    error: Arc<AtomicBool>,
+    stats: M8AudioStats,
+    peaks: M8AudioPeaks,
+    tap: M8AudioTap,
+) -> Result<M8StreamResource, M8AudioSetupError> {
+    let input_device = find_m8_input(host)
+        .map_err(M8AudioSetupError::Devices)?
+        .ok_or(M8AudioSetupError::NoInputDevice)?;
+    let output_device = host
+        .default_output_device()
+        .ok_or(M8AudioSetupError::NoOutputDevice)?;
+
+    let input_config: cpal::StreamConfig = input_device
+        .default_input_config()
+        .map_err(M8AudioSetupError::DefaultConfig)?
+        .into();
+    let output_ranges = output_device
+        .supported_output_configs()
+        .map_err(M8AudioSetupError::SupportedConfigs)?;
+    let output_config = match pick_output_config(output_ranges, &input_config) {
+        Some(output_config) => output_config,
+        None => output_device
+            .default_output_config()
+            .map_err(M8AudioSetupError::DefaultConfig)?
+            .into(),
+    };
+
+    let capacity = capacity_for_latency(
+        output_config.sample_rate,
+        output_config.channels,
+        config.latency_ms,
+    );
+    let (producer, consumer) = ring_buffer(capacity);
+
+    // Start half full so that the output has the target latency to work with.
+    for _ in 0..producer.capacity() / 2 {
+        producer.push(0.0);
+    }
+
+    let error_input = error.clone();
+    let input_stats = stats.clone();
+    let channels = usize::from(input_config.channels.max(1));
+    let input_stream = input_device
+        .build_input_stream(
+            &input_config,
+            move |data: &[f32], _| {
+                input_stats.push_input(&producer, data);
+
+                let mut block_peaks = [0.0f32; 2];
+                for (index, &sample) in data.iter().enumerate() {
+                    // Channels past the second are metered as the right one.
+                    let channel = (index % channels).min(1);
+                    if sample.is_finite() {
+                        block_peaks[channel] = block_peaks[channel].max(sample.abs());
+                    }
+                }
+
+                let [left, right] = block_peaks;
+                if channels == 1 {
+                    peaks.record(left, left);
+                } else {
+                    peaks.record(left, right);
+                }
+
+                if let Ok(tap) = tap.0.try_lock()
+                    && let Some(tx) = tap.as_ref()
+                {
+                    tx.send(data.to_vec()).ok();
+                }
+            },
+            move |err| {
+                error!("M8 Audio Input Error: {:?}", err);
+                error_input.store(true, Ordering::SeqCst);
+            },
+            None,
+        )
+        .map_err(M8AudioSetupError::BuildStream)?;
+
+    let error_output = error.clone();
+    let output_stream = output_device
+        .build_output_stream(
+            &output_config,
+            move |data: &mut [f32], _| stats.pop_output(&consumer, data),
+            move |err| {
+                error!("Audio Output Error: {:?}", err);
+                error_output.store(true, Ordering::SeqCst);
+            },
+            None,
+        )
+        .map_err(M8AudioSetupError::BuildStream)?;
+
+    input_stream.play().map_err(M8AudioSetupError::PlayStream)?;
+    output_stream
+        .play()
+        .map_err(M8AudioSetupError::PlayStream)?;
+
+    Ok(M8StreamResource {
+        input: input_stream,
+        output: output_stream,
+        format: (input_config.channels, input_config.sample_rate),
+    })
+}
+
 fn setup_m8_audio(world: &mut World) {
     let host = cpal::default_host();
     let error = world.resource::<M8AudioError>().0.clone();
+    let stats = world.resource::<M8AudioStats>().clone();
+    let peaks = world.resource::<M8AudioPeaks>().clone();
+    let tap = world.resource::<M8AudioTap>().clone();
+    let config = *world.resource::<M8AudioConfig>();
 
-    let input_device = host.input_devices().unwrap().find(|x| {
-        x.description()
-            .map(|description| description.name().contains("M8"))
-            .unwrap_or(false)
-    });
-    let output_device = host.default_output_device().expect("No output device!");
-
-    if let Some(input_device) = input_device {
-        let input_config: cpal::StreamConfig = input_device.default_input_config().unwrap().into();
-        let output_config: cpal::StreamConfig =
-            output_device.default_output_config().unwrap().into();
-
-        let (tx, rx) = bounded::<f32>(8820);
-
-        let error_input = error.clone();
-        let input_stream = input_device
-            .build_input_stream(
-                &input_config,
-                move |data: &[f32], _| {
-                    for &sample in data {
-                        let _ = tx.try_send(sample);
-                    }
-                },
-                move |err| {
-                    error!("M8 Audio Input Error: {:?}", err);
-                    error_input.store(true, Ordering::SeqCst);
-                },
-                None,
-            )
-            .unwrap();
-
-        let error_output = error.clone();
-        let output_stream = output_device
-            .build_output_stream(
-                &output_config,
-                move |data: &mut [f32], _| {
-                    for sample in data.iter_mut() {
-                        *sample = rx.try_recv().unwrap_or(0.0);
-                    }
-                },
-                move |err| {
-                    error!("Audio Output Error: {:?}", err);
-                    error_output.store(true, Ordering::SeqCst);
-                },
-                None,
-            )
-            .unwrap();
+    world.insert_resource(M8AudioDevices::current(&host));
+    match build_m8_audio(&host, config, error.clone(), stats, peaks, tap.clone()) {
+        Ok(streams) => {
+            // The WAV header can't change halfway through the file.
+            let (channels, sample_rate) = streams.format;
+            if let Some(wav) = &world.resource::<M8AudioRecorder>().0
+                && (wav.spec().channels, wav.spec().sample_rate) != streams.format
+            {
+                warn!(
+                    "The M8's audio changed to {} channels at {}Hz, stopping the recording",
+                    channels, sample_rate
+                );
+                stop_recording(&tap, &mut world.resource_mut::<M8AudioRecorder>());
+            }
+            world.insert_non_send_resource(streams);
+            world.resource_mut::<M8AudioRecovery>().last_error = None;
+            error.store(false, Ordering::SeqCst);
+            info!(
+                "M8 Audio Stream Started with {}ms target latency.",
+                config.latency_ms
+            );
+        }
+        Err(e) => {
+            // Keep retrying, but only report each distinct failure once.
+            let message = e.to_string();
+            let mut recovery = world.resource_mut::<M8AudioRecovery>();
+            if recovery.last_error.as_ref() != Some(&message) {
+                warn!("{}, retrying every {:?}", message, AUDIO_RETRY_INTERVAL);
+                recovery.last_error = Some(message);
+            }
+            error.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Flags the audio streams for a rebuild when the M8 is plugged in or
+/// removed, or when the default output device changes.
+fn poll_m8_audio_devices(
+    time: Res<Time>,
+    mut timer: Local<Option<Timer>>,
+    devices: Res<M8AudioDevices>,
+    error: Res<M8AudioError>,
+) {
+    let timer =
+        timer.get_or_insert_with(|| Timer::new(AUDIO_DEVICE_POLL_INTERVAL, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    if M8AudioDevices::current(&cpal::default_host()) != *devices {
+        info!("Audio devices changed, rebuilding streams...");
+        error.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Flags the audio streams for a rebuild when the configuration changes.
+fn apply_m8_audio_config(config: Res<M8AudioConfig>, error: Res<M8AudioError>) {
+    if config.is_changed() && !config.is_added() {
+        info!("M8 audio configuration changed, rebuilding streams...");
+        error.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Moves the [M8AudioLevel] towards the loudest samples since the last
+/// update, letting it fall back at the configured rate.
+fn update_m8_audio_level(
+    time: Res<Time>,
+    config: Res<M8AudioMeterConfig>,
+    peaks: Res<M8AudioPeaks>,
+    mut level: ResMut<M8AudioLevel>,
+    mut peak_held: Local<Duration>,
+) {
+    let fall = if config.decay.is_zero() {
+        1.0
+    } else {
+        time.delta_secs() / config.decay.as_secs_f32()
+    };
+    let (left, right) = peaks.take();
+    let left = left.max(level.left - fall).clamp(0.0, 1.0);
+    let right = right.max(level.right - fall).clamp(0.0, 1.0);
+
+    let loudest = left.max(right);
+    let peak = if loudest >= level.peak {
+        *peak_held = Duration::ZERO;
+        loudest
+    } else {
+        *peak_held += time.delta();
+        if *peak_held < config.peak_hold {
+            level.peak
+        } else {
+            loudest.max(level.peak - fall)
+        }
+    };
+
+    level.set_if_neq(M8AudioLevel { left, right, peak });
+}
 
-        input_stream.play().unwrap();
-        output_stream.play().unwrap();
+/// Stops feeding the recording and finalizes its file.
+fn stop_recording(tap: &M8AudioTap, recorder: &mut M8AudioRecorder) {
+    // The file is only finalized once the callback's sender is gone too.
+    if let Ok(mut tap) = tap.0.lock() {
+        tap.take();
+    }
+    if recorder.0.take().is_some() {
+        info!("Stopped recording the M8's audio");
+    }
+}
 
-        world.insert_non_send_resource(M8StreamResource {
-            _input: input_stream,
-            _output: output_stream,
-        });
+fn record_m8_audio(
+    mut recordings: MessageReader<M8AudioRecording>,
+    tap: Res<M8AudioTap>,
+    mut recorder: ResMut<M8AudioRecorder>,
+    streams: Option<NonSend<M8StreamResource>>,
+) {
+    for recording in recordings.read() {
+        stop_recording(&tap, &mut recorder);
+        let M8AudioRecording::Start(path) = recording else {
+            continue;
+        };
+        let Some(streams) = streams.as_ref() else {
+            warn!("Can't record the M8's audio while its stream isn't running");
+            continue;
+        };
 
-        error.store(false, Ordering::SeqCst);
-        info!("M8 Audio Stream Started.");
+        let (channels, sample_rate) = streams.format;
+        match M8WavRecorder::create(path, channels, sample_rate) {
+            Ok(wav) => {
+                if let Ok(mut tap) = tap.0.lock() {
+                    *tap = Some(wav.sender());
+                }
+                info!("Recording the M8's audio to {}", path.display());
+                recorder.0 = Some(wav);
+            }
+            Err(e) => error!("Failed to create {}: {}", path.display(), e),
+        }
     }
 }
 
 fn recover_m8_audio(world: &mut World) {
     let error = world.resource::<M8AudioError>().0.clone();
+    if !error.load(Ordering::SeqCst) {
+        return;
+    }
 
-    if error.load(Ordering::SeqCst) {
+    // Rebuild straight away after a working stream fails, and
+    // pace the attempts while the setup keeps failing.
+    let delta = world.resource::<Time>().delta();
+    let mut recovery = world.resource_mut::<M8AudioRecovery>();
+    if recovery.last_error.is_some() && !recovery.timer.tick(delta).just_finished() {
+        return;
+    }
+    if recovery.last_error.is_none() {
         warn!("Attempting to recover M8 audio stream...");
-        world.remove_non_send_resource::<M8StreamResource>();
-        setup_m8_audio(world);
     }
+
+    world.remove_non_send_resource::<M8StreamResource>();
+    setup_m8_audio(world);
+}
+
+/// Pauses the audio streams and drops them before the app exits, rather
+/// than leaving them to be dropped with the world, which some backends
+/// answer with a crackle or a hang.
+fn stop_m8_audio(world: &mut World) {
+    world.resource_scope(|world, mut recorder: Mut<M8AudioRecorder>| {
+        stop_recording(world.resource::<M8AudioTap>(), &mut recorder);
+    });
+
+    let Some(streams) = world.remove_non_send_resource::<M8StreamResource>() else {
+        return;
+    };
+
+    // Silence the output first, so it doesn't play out what the input
+    // leaves behind.
+    if let Err(e) = streams.output.pause() {
+        warn!("Failed to pause the audio output: {}", e);
+    }
+    if let Err(e) = streams.input.pause() {
+        warn!("Failed to pause the audio input: {}", e);
+    }
+    drop(streams);
+    info!("M8 Audio Stream Stopped.");
 }
 
 /// Dirtywave M8 Audio plugin.
@@ -99,7 +576,29 @@ pub struct M8AudioPlugin;
 impl Plugin for M8AudioPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(M8AudioError(Arc::new(AtomicBool::new(false))));
+        app.init_resource::<M8AudioConfig>();
+        app.register_type::<M8AudioConfig>();
+        app.init_resource::<M8AudioStats>();
+        app.init_resource::<M8AudioPeaks>();
+        app.init_resource::<M8AudioLevel>();
+        app.init_resource::<M8AudioMeterConfig>();
+        app.register_type::<M8AudioLevel>();
+        app.register_type::<M8AudioMeterConfig>();
+        app.init_resource::<M8AudioRecovery>();
+        app.init_resource::<M8AudioTap>();
+        app.init_resource::<M8AudioRecorder>();
+        app.add_message::<M8AudioRecording>();
         setup_m8_audio(app.world_mut());
-        app.add_systems(Update, recover_m8_audio);
+        app.add_systems(
+            Update,
+            (
+                poll_m8_audio_devices,
+                apply_m8_audio_config,
+                recover_m8_audio,
+            )
+                .chain(),
+        );
+        app.add_systems(Update, (update_m8_audio_level, record_m8_audio));
+        app.add_systems(Last, stop_m8_audio.run_if(on_message::<AppExit>));
     }
 }
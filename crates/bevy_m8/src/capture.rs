@@ -0,0 +1,151 @@
+//! This file provides capture files of the raw serial stream.
+//!
+//! A capture is a sequence of frames, each holding the milliseconds elapsed
+//! since recording started and the length of the chunk (both little-endian
+//! `u32`s) followed by the bytes read from the M8 in one pass.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::log::{error, info};
+use crossbeam_channel::{Sender, unbounded};
+
+use crate::transport::M8Transport;
+
+/// How long a read waits once the end of a capture has been reached.
+const PLAYBACK_IDLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Records chunks of the serial stream to a capture file.
+///
+/// Chunks are handed to a background thread which does the writing, so
+/// recording doesn't add latency to the read path.
+pub struct M8Recorder {
+    tx: Sender<(Duration, Vec<u8>)>,
+    start: Instant,
+}
+
+/// Writes a single capture frame.
+pub fn write_frame(writer: &mut impl Write, elapsed: Duration, chunk: &[u8]) -> io::Result<()> {
+    writer.write_all(&(elapsed.as_millis() as u32).to_le_bytes())?;
+    writer.write_all(&(chunk.len() as u32).to_le_bytes())?;
+    writer.write_all(chunk)
+}
+
+/// Reads a single capture frame, returning `None` at the end of the capture.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<(Duration, Vec<u8>)>> {
+    let mut header = [0u8; 8];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let elapsed = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let mut chunk = vec![0u8; length as usize];
+    reader.read_exact(&mut chunk)?;
+    Ok(Some((Duration::from_millis(elapsed as u64), chunk)))
+}
+
+impl M8Recorder {
+    /// Creates the capture file and starts the thread writing to it.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let (tx, rx) = unbounded::<(Duration, Vec<u8>)>();
+
+        thread::spawn(move || {
+            for (elapsed, chunk) in rx.iter() {
+                let mut result = write_frame(&mut writer, elapsed, &chunk);
+                if result.is_ok() && rx.is_empty() {
+                    result = writer.flush();
+                }
+
+                if let Err(e) = result {
+                    error!("Failed to write to the capture file: {:?}", e);
+                    return;
+                }
+            }
+        });
+
+        Ok(Self {
+            tx,
+            start: Instant::now(),
+        })
+    }
+
+    /// Queues a chunk read from the M8 to be written.
+    pub fn record(&self, chunk: &[u8]) {
+        self.tx.send((self.start.elapsed(), chunk.to_vec())).ok();
+    }
+}
+
+/// Plays a capture file back with its original timing in place of a device.
+/// Anything written to it is discarded.
+pub struct M8Playback<R = BufReader<File>> {
+    reader: R,
+    start: Instant,
+    pending: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl M8Playback {
+    /// Opens the capture file for playback.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> M8Playback<R> {
+    /// Plays back the capture read from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            start: Instant::now(),
+            pending: Vec::new(),
+            position: 0,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read + Send + 'static> M8Transport for M8Playback<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.pending.len() {
+            if self.finished {
+                thread::sleep(PLAYBACK_IDLE_INTERVAL);
+                return Ok(0);
+            }
+
+            match read_frame(&mut self.reader)? {
+                Some((elapsed, chunk)) => {
+                    // Wait until the chunk arrived when it was recorded.
+                    if let Some(wait) = elapsed.checked_sub(self.start.elapsed()) {
+                        thread::sleep(wait);
+                    }
+                    self.pending = chunk;
+                    self.position = 0;
+                }
+                None => {
+                    info!("Reached the end of the capture");
+                    self.finished = true;
+                    return Ok(0);
+                }
+            }
+        }
+
+        let count = buf.len().min(self.pending.len() - self.position);
+        buf[..count].copy_from_slice(&self.pending[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+
+    fn write_all(&mut self, _data: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! This file provides the conversions between colours and the RGB bytes the
+//! M8 sends, shared by the decoder and encoder so both agree.
+
+#[cfg(feature = "bevy")]
+use bevy::color::{Color, Srgba};
+
+/// The RGB bytes of a colour sent by the M8, standing in for bevy's `Color`
+/// without the `bevy` feature.
+#[cfg(not(feature = "bevy"))]
+pub type Color = [u8; 3];
+
+/// Converts the RGB bytes sent by the M8 into an sRGB colour.
+#[cfg(feature = "bevy")]
+#[inline]
+pub fn color_from_rgb(&[red, green, blue]: &[u8; 3]) -> Color {
+    Color::Srgba(Srgba {
+        red: red as f32 / 255.0,
+        green: green as f32 / 255.0,
+        blue: blue as f32 / 255.0,
+        alpha: 1.0,
+    })
+}
+
+/// Converts the RGB bytes sent by the M8 into a colour.
+#[cfg(not(feature = "bevy"))]
+#[inline]
+pub fn color_from_rgb(rgb: &[u8; 3]) -> Color {
+    *rgb
+}
+
+/// Converts a colour into the RGB bytes the M8 would send for it, the
+/// inverse of [color_from_rgb]. Colours in other spaces are converted to
+/// sRGB first, and channels outside 0 to 1 are clamped.
+#[cfg(feature = "bevy")]
+#[inline]
+pub fn rgb_from_color(colour: Color) -> [u8; 3] {
+    let srgba = colour.to_srgba();
+    [
+        (srgba.red * 255.0).round() as u8,
+        (srgba.green * 255.0).round() as u8,
+        (srgba.blue * 255.0).round() as u8,
+    ]
+}
+
+/// Converts a colour into the RGB bytes the M8 would send for it.
+#[cfg(not(feature = "bevy"))]
+#[inline]
+pub fn rgb_from_color(colour: Color) -> [u8; 3] {
+    colour
+}
+
+/// Formats the colour as `#RRGGBB`, with the alpha appended if translucent.
+#[cfg(feature = "bevy")]
+pub(crate) fn color_to_hex(colour: Color) -> String {
+    colour.to_srgba().to_hex()
+}
+
+/// Formats the colour as `#RRGGBB`.
+#[cfg(not(feature = "bevy"))]
+pub(crate) fn color_to_hex([red, green, blue]: Color) -> String {
+    format!("#{red:02X}{green:02X}{blue:02X}")
+}
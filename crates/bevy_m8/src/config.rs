@@ -0,0 +1,102 @@
+//! This file provides a configuration file for setting up the M8 plugins
+//! without code.
+
+use std::{io, net::IpAddr, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DEFAULT_M8_PORT, M8Plugin,
+    assets::DEFAULT_FONT_PATH,
+    dimensions::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    keymap::M8KeyMap,
+    remote::{DEFAULT_ADDRESS, DEFAULT_PORT},
+};
+
+/// The settings of [M8Plugin] read from a RON file. Fields missing from the
+/// file keep their defaults.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct M8Config {
+    /// The serial port the M8 is preferred on.
+    pub port: String,
+    /// Overrides the baud rate of the serial port.
+    pub baud_rate: Option<u32>,
+    /// The initial width and height of the window.
+    pub resolution: (u32, u32),
+    /// Runs without a window or renderer.
+    pub headless: bool,
+    /// The asset path of the font atlas.
+    pub font_path: String,
+    /// The keys bound to the M8's buttons.
+    pub key_map: M8KeyMap,
+    /// The address the remote control listens on.
+    pub remote_address: IpAddr,
+    /// The port the remote control listens on.
+    pub remote_port: u16,
+}
+
+impl Default for M8Config {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_M8_PORT.into(),
+            baud_rate: None,
+            resolution: (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            headless: false,
+            font_path: DEFAULT_FONT_PATH.into(),
+            key_map: M8KeyMap::default(),
+            remote_address: DEFAULT_ADDRESS,
+            remote_port: DEFAULT_PORT,
+        }
+    }
+}
+
+impl M8Config {
+    /// Saves the configuration as RON.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(io::Error::other)?;
+        std::fs::write(path, ron)
+    }
+
+    /// Loads a configuration written by hand or with [M8Config::save].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let ron = std::fs::read_to_string(path)?;
+        ron::from_str(&ron).map_err(io::Error::other)
+    }
+}
+
+impl M8Plugin {
+    /// Creates the plugin from the configuration file at the given path.
+    pub fn from_config(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from(M8Config::load(path)?))
+    }
+}
+
+impl From<M8Config> for M8Plugin {
+    fn from(config: M8Config) -> Self {
+        let plugin = M8Plugin::new(config.port.clone())
+            .with_keymap(config.key_map)
+            .with_remote(config.remote_address, config.remote_port);
+        // The window settings only apply with the display.
+        #[cfg(feature = "render")]
+        let plugin = plugin
+            .with_resolution(config.resolution.0, config.resolution.1)
+            .with_headless(config.headless)
+            .with_font(config.font_path.clone());
+        let plugin = match config.baud_rate {
+            Some(baud_rate) => plugin.with_baud_rate(baud_rate),
+            None => plugin,
+        };
+        M8Plugin {
+            config: Some(config),
+            ..plugin
+        }
+    }
+}
+
+/// Logs the configuration the plugins were set up with.
+pub(crate) fn log_config(config: Res<M8Config>) {
+    info!("Using the M8 configuration {:?}", *config);
+}
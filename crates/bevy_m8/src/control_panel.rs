@@ -0,0 +1,128 @@
+//! This file provides an egui panel for controlling the M8 from within the
+//! app.
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass, egui};
+
+use crate::{
+    audio::M8AudioConfig,
+    decoder::M8DeviceInfo,
+    serial::{M8Connection, M8ConnectionStatus, M8Reconnect},
+};
+
+/// Key which shows and hides the control panel.
+const CONTROL_PANEL_TOGGLE_KEY: KeyCode = KeyCode::F1;
+
+/// The range of the audio latency slider, in milliseconds.
+const AUDIO_LATENCY_RANGE: std::ops::RangeInclusive<u32> = 5..=200;
+
+/// Whether the control panel is shown.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8ControlPanel {
+    pub open: bool,
+}
+
+fn toggle_control_panel(keys: Res<ButtonInput<KeyCode>>, mut panel: ResMut<M8ControlPanel>) {
+    if keys.just_pressed(CONTROL_PANEL_TOGGLE_KEY) {
+        panel.open = !panel.open;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn control_panel(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<M8ControlPanel>,
+    connection: Res<M8Connection>,
+    status: Res<M8ConnectionStatus>,
+    device_info: Option<Res<M8DeviceInfo>>,
+    audio: Option<ResMut<M8AudioConfig>>,
+    mut reconnect: MessageWriter<M8Reconnect>,
+    mut ports: Local<Option<Vec<String>>>,
+    mut selected_port: Local<Option<String>>,
+) -> Result {
+    if !panel.open {
+        return Ok(());
+    }
+
+    let mut open = panel.open;
+    let mut audio = audio;
+    egui::Window::new("M8")
+        .open(&mut open)
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.heading("Connection");
+            ui.label(if status.connected {
+                "Connected"
+            } else {
+                "Disconnected"
+            });
+            ui.label(format!("{} bytes read", status.bytes_read));
+            if let Some(info) = &device_info {
+                ui.label(format!(
+                    "{:?}, firmware {}.{}.{}",
+                    info.model(),
+                    info.major,
+                    info.minor,
+                    info.patch
+                ));
+            }
+
+            let ports = ports.get_or_insert_with(M8Connection::available_ports);
+            egui::ComboBox::from_label("Port")
+                .selected_text(selected_port.as_deref().unwrap_or("Automatic"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut *selected_port, None, "Automatic");
+                    for port in ports.iter() {
+                        ui.selectable_value(&mut *selected_port, Some(port.clone()), port);
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button("Refresh ports").clicked() {
+                    *ports = M8Connection::available_ports();
+                }
+                if ui.button("Reconnect").clicked() {
+                    reconnect.write(M8Reconnect {
+                        port: selected_port.clone(),
+                    });
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Enable").clicked() {
+                    connection.request_enable();
+                }
+                if ui.button("Redraw").clicked() {
+                    let _ = connection.tx.send(vec![b'R']);
+                }
+            });
+
+            if let Some(audio) = &mut audio {
+                ui.heading("Audio");
+                let mut latency_ms = audio.latency_ms;
+                let slider =
+                    egui::Slider::new(&mut latency_ms, AUDIO_LATENCY_RANGE).text("Latency (ms)");
+                // Only write on change, as any change rebuilds the streams.
+                if ui.add(slider).changed() {
+                    audio.latency_ms = latency_ms;
+                }
+            }
+        });
+    panel.open = open;
+    Ok(())
+}
+
+/// This plugin provides a control panel, toggled with F1, showing the
+/// connection and device and changing the port and audio latency.
+pub struct M8ControlPanelPlugin;
+
+impl Plugin for M8ControlPanelPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.add_message::<M8Reconnect>();
+        app.init_resource::<M8ControlPanel>();
+        app.register_type::<M8ControlPanel>();
+        app.add_systems(Update, toggle_control_panel);
+        app.add_systems(EguiPrimaryContextPass, control_panel);
+    }
+}
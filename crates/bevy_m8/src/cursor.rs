@@ -0,0 +1,158 @@
+//! This file provides a heuristic estimate of where the M8's cursor is,
+//! based on the rectangles the firmware draws.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    M8LoadingState, M8UpdateSystems,
+    decoder::{CELL_SIZE, M8Command, M8DeviceInfoChanged, Position, Size},
+    dimensions::M8DisplayDimensions,
+    serial::M8Commands,
+};
+
+/// The number of rectangles kept in the history by default.
+const DEFAULT_HISTORY_LEN: usize = 16;
+
+/// Describes what a cursor rectangle looks like. Themes vary, so
+/// this may need adjusting to match the one in use. The cell size follows
+/// the font of the connected M8 unless it was changed.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct M8CursorSignature {
+    /// The size of a single character cell.
+    pub cell_size: Size,
+    /// The narrowest rectangle one cell tall which counts as a row cursor.
+    pub min_row_width: u16,
+    /// The selection colour of the theme, any colour matches when None.
+    pub colour: Option<Color>,
+    /// The number of recent rectangles kept in the history.
+    pub history_len: usize,
+}
+
+impl Default for M8CursorSignature {
+    fn default() -> Self {
+        Self {
+            cell_size: Size::ZERO,
+            min_row_width: 0,
+            colour: None,
+            history_len: DEFAULT_HISTORY_LEN,
+        }
+        .with_cell_size(CELL_SIZE)
+    }
+}
+
+impl M8CursorSignature {
+    /// The signature for character cells of the given size, row cursors
+    /// being at least four cells wide.
+    pub fn with_cell_size(self, cell_size: UVec2) -> Self {
+        Self {
+            cell_size: cell_size.as_u16vec2(),
+            min_row_width: cell_size.x as u16 * 4,
+            ..self
+        }
+    }
+
+    /// Returns true if a rectangle drawn with these parameters looks like the cursor.
+    pub fn matches(&self, size: Size, colour: Color) -> bool {
+        let cell = size == self.cell_size;
+        let row = size.y == self.cell_size.y && size.x >= self.min_row_width;
+        let colour = self.colour.is_none_or(|expected| expected == colour);
+        (cell || row) && colour
+    }
+}
+
+/// A rectangle drawn by the M8.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct M8Rectangle {
+    pub pos: Position,
+    pub size: Size,
+    pub colour: Color,
+}
+
+/// The estimated position of the M8's cursor.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct M8CursorEstimate {
+    /// The last rectangle which matched the [M8CursorSignature].
+    pub rect: Option<M8Rectangle>,
+    /// The character cell the cursor starts in, counted from the top left.
+    pub cell: Option<UVec2>,
+    /// Set when the screen was cleared after the cursor was seen.
+    pub stale: bool,
+    #[reflect(ignore)]
+    history: VecDeque<M8Rectangle>,
+}
+
+impl M8CursorEstimate {
+    /// The most recently drawn rectangles, oldest first.
+    pub fn recent_rectangles(&self) -> impl Iterator<Item = &M8Rectangle> {
+        self.history.iter()
+    }
+}
+
+fn track_cursor(
+    commands: Res<M8Commands>,
+    signature: Res<M8CursorSignature>,
+    dimensions: Res<M8DisplayDimensions>,
+    mut estimate: ResMut<M8CursorEstimate>,
+) {
+    for command in commands.0.iter() {
+        let &M8Command::DrawRectangle { pos, size, colour } = command else {
+            continue;
+        };
+        let rectangle = M8Rectangle { pos, size, colour };
+
+        estimate.history.push_back(rectangle);
+        while estimate.history.len() > signature.history_len {
+            estimate.history.pop_front();
+        }
+
+        // The firmware clears the whole screen before drawing a new view.
+        if dimensions.is_covered_by(pos, size) {
+            estimate.stale = true;
+        } else if signature.matches(size, colour) {
+            estimate.rect = Some(rectangle);
+            estimate.cell = Some(UVec2::new(
+                (pos.x / signature.cell_size.x.max(1)) as u32,
+                (pos.y / signature.cell_size.y.max(1)) as u32,
+            ));
+            estimate.stale = false;
+        }
+    }
+}
+
+/// Resizes the cells of the [M8CursorSignature] to the font of the M8,
+/// unless they were set to something else.
+fn follow_cell_size(
+    mut device_info_changed: MessageReader<M8DeviceInfoChanged>,
+    mut signature: ResMut<M8CursorSignature>,
+) {
+    for M8DeviceInfoChanged { old, new } in device_info_changed.read() {
+        let old = old.map_or(CELL_SIZE, |old| old.cell_size());
+        if signature.cell_size == old.as_u16vec2() {
+            *signature = signature.with_cell_size(new.cell_size());
+        }
+    }
+}
+
+/// This plugin estimates where the M8's cursor is.
+pub struct M8CursorPlugin;
+
+impl Plugin for M8CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<M8DeviceInfoChanged>();
+        app.init_resource::<M8CursorSignature>();
+        app.init_resource::<M8CursorEstimate>();
+        app.register_type::<M8CursorSignature>();
+        app.register_type::<M8CursorEstimate>();
+        app.add_systems(
+            Update,
+            (follow_cell_size, track_cursor)
+                .chain()
+                .after(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running)),
+        );
+    }
+}
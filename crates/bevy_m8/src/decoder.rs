@@ -1,10 +1,20 @@
 //! This file provides SLIP decoding functionality.
+use glam::{U16Vec2, UVec2};
+use memchr::memchr2;
+use std::{collections::HashSet, fmt};
+
+#[cfg(feature = "bevy")]
 use bevy::{
-    color::{Color, Srgba},
+    color::Color,
+    ecs::{message::Message, reflect::ReflectResource, resource::Resource},
     log::warn,
-    math::U16Vec2,
+    reflect::Reflect,
 };
 
+#[cfg(not(feature = "bevy"))]
+use crate::color::Color;
+use crate::color::{color_from_rgb, color_to_hex};
+
 // // SLIP Protocol Constants.
 pub const SLIP_END: u8 = 0xC0;
 pub const SLIP_ESC: u8 = 0xDB;
@@ -22,17 +32,50 @@ enum State {
 pub struct SlipDecoder {
     state: State,
     buffer: Vec<u8>,
+    /// Set once a packet has been returned, its bytes are
+    /// kept in the buffer until the next byte arrives.
+    complete: bool,
 }
 
 /// The reserved capacity for the Slip Decoder.
 const SLIP_BUFFER_CAPACITY: usize = 1024;
 
 // M8 Command Constants
-const KEY_PRESS_STATE_COMMAND: u8 = 0xFB;
-const DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND: u8 = 0xFC;
-const DRAW_CHARACTER_COMMAND: u8 = 0xFD;
-const DRAW_RECTANGLE_COMMAND: u8 = 0xFE;
-const SYSTEM_INFO_COMMAND: u8 = 0xFF;
+pub const KEY_PRESS_STATE_COMMAND: u8 = 0xFB;
+pub const DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND: u8 = 0xFC;
+pub const DRAW_CHARACTER_COMMAND: u8 = 0xFD;
+pub const DRAW_RECTANGLE_COMMAND: u8 = 0xFE;
+pub const SYSTEM_INFO_COMMAND: u8 = 0xFF;
+
+/// The screen size of the original M8 models.
+const DISPLAY_SIZE: UVec2 = UVec2::new(320, 240);
+
+/// The screen size of the M8 Model:02.
+const MODEL_02_DISPLAY_SIZE: UVec2 = UVec2::new(480, 320);
+
+/// The character cell the original M8 models lay their text out on.
+pub(crate) const CELL_SIZE: UVec2 = UVec2::new(8, 10);
+
+/// The character cell of the original M8 models in the large font.
+const LARGE_CELL_SIZE: UVec2 = UVec2::new(10, 12);
+
+/// The character cell of the M8 Model:02.
+const MODEL_02_CELL_SIZE: UVec2 = UVec2::new(12, 15);
+
+/// The character cell of the M8 Model:02 in the large font.
+const MODEL_02_LARGE_CELL_SIZE: UVec2 = UVec2::new(15, 18);
+
+/// The longest waveform the original M8 models send.
+const MAX_WAVEFORM_LEN: usize = 320;
+
+/// The longest waveform the M8 Model:02 sends.
+const MODEL_02_MAX_WAVEFORM_LEN: usize = 480;
+
+/// The highest waveform sample, drawn on the bottom row of the oscilloscope.
+const MAX_WAVEFORM_SAMPLE: u32 = 16;
+
+/// The first firmware which appends an extra byte to the key state packet.
+const EXTENDED_KEY_STATE_VERSION: (u8, u8, u8) = (4, 0, 0);
 
 /// Specifies how big something should be.
 pub type Size = U16Vec2;
@@ -42,7 +85,8 @@ pub type Position = U16Vec2;
 
 /// A [Command] is sent from the M8 firmware and specifies what to
 /// draw and where to draw it on the display.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
 pub enum M8Command {
     /// A rectangle draw command
     DrawRectangle {
@@ -62,6 +106,9 @@ pub enum M8Command {
     /// An oscilloscope waveform draw command
     DrawOscilloscopeWaveform { colour: Color, waveform: Vec<u8> },
 
+    /// The state of the M8's keys, sent while in joypad mode.
+    KeyPressState { keys: u8 },
+
     /// System Info command
     SystemInfo {
         hardware_type: u8,
@@ -72,19 +119,227 @@ pub enum M8Command {
     },
 }
 
+/// Maps waveform samples to the pixels they are drawn on, see
+/// [M8Command::waveform_points].
+pub(crate) fn waveform_points(
+    waveform: &[u8],
+    height: u32,
+) -> impl Iterator<Item = (u32, u32)> + '_ {
+    waveform.iter().enumerate().map(move |(x, &sample)| {
+        // The samples span the rows from the top to the bottom one.
+        let bottom = height.saturating_sub(1);
+        let y = (sample as u32).min(MAX_WAVEFORM_SAMPLE) * bottom / MAX_WAVEFORM_SAMPLE;
+        (x as u32, y)
+    })
+}
+
+impl M8Command {
+    /// The pixels of an oscilloscope waveform, from the left of the screen.
+    /// Each sample is the row it is drawn on, counted down from the top of
+    /// the oscilloscope, and is scaled to an oscilloscope `height` pixels
+    /// tall, the largest sample landing on its bottom row. Other commands
+    /// have no points.
+    pub fn waveform_points(&self, height: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let waveform: &[u8] = match self {
+            Self::DrawOscilloscopeWaveform { waveform, .. } => waveform,
+            _ => &[],
+        };
+        waveform_points(waveform, height)
+    }
+}
+
+/// A one line summary of the command, e.g.
+/// `DrawRectangle @ (40,0) 10x10 #FFFFFF`.
+impl fmt::Display for M8Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DrawRectangle { pos, size, colour } => write!(
+                f,
+                "DrawRectangle @ ({},{}) {}x{} {}",
+                pos.x,
+                pos.y,
+                size.x,
+                size.y,
+                color_to_hex(*colour)
+            ),
+            Self::DrawCharacter {
+                c,
+                pos,
+                foreground,
+                background,
+            } => write!(
+                f,
+                "DrawCharacter {:?} @ ({},{}) {} on {}",
+                *c as char,
+                pos.x,
+                pos.y,
+                color_to_hex(*foreground),
+                color_to_hex(*background)
+            ),
+            Self::DrawOscilloscopeWaveform { colour, waveform } => write!(
+                f,
+                "DrawOscilloscopeWaveform {} {} samples",
+                color_to_hex(*colour),
+                waveform.len()
+            ),
+            Self::KeyPressState { keys } => write!(f, "KeyPressState {:#04X}", keys),
+            Self::SystemInfo {
+                hardware_type,
+                major,
+                minor,
+                patch,
+                font_mode,
+            } => write!(
+                f,
+                "SystemInfo hardware {} firmware {}.{}.{} font {}",
+                hardware_type, major, minor, patch, font_mode
+            ),
+        }
+    }
+}
+
+/// The generation of M8 hardware, decoded from the hardware type
+/// reported in [M8Command::SystemInfo].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub enum M8HardwareModel {
+    /// The M8 headless firmware running on a Teensy.
+    Headless,
+    /// The beta M8.
+    Beta,
+    /// The production M8.
+    Model01,
+    /// The production M8 Model:02.
+    Model02,
+}
+
+impl M8HardwareModel {
+    /// Decodes the hardware type, None if it isn't a known model.
+    pub fn from_hardware_type(hardware_type: u8) -> Option<Self> {
+        match hardware_type {
+            0 => Some(M8HardwareModel::Headless),
+            1 => Some(M8HardwareModel::Beta),
+            2 => Some(M8HardwareModel::Model01),
+            3 => Some(M8HardwareModel::Model02),
+            _ => None,
+        }
+    }
+
+    /// The size of this model's screen.
+    pub fn display_size(self) -> UVec2 {
+        match self {
+            M8HardwareModel::Model02 => MODEL_02_DISPLAY_SIZE,
+            _ => DISPLAY_SIZE,
+        }
+    }
+}
+
+/// Describes the connected M8, as reported by its latest
+/// [M8Command::SystemInfo].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(Resource, Reflect), reflect(Resource))]
+pub struct M8DeviceInfo {
+    pub hardware_type: u8,
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+    pub font_mode: u8,
+}
+
+impl M8DeviceInfo {
+    /// Returns true if the firmware is at least the given version.
+    pub fn firmware_at_least(&self, major: u8, minor: u8, patch: u8) -> bool {
+        (self.major, self.minor, self.patch) >= (major, minor, patch)
+    }
+
+    /// The hardware model, None if the hardware type isn't known.
+    pub fn model(&self) -> Option<M8HardwareModel> {
+        M8HardwareModel::from_hardware_type(self.hardware_type)
+    }
+
+    /// Returns true if this is an M8 Model:02.
+    pub fn is_model_02(&self) -> bool {
+        self.model() == Some(M8HardwareModel::Model02)
+    }
+
+    /// Returns the longest oscilloscope waveform this model sends.
+    pub fn max_waveform_len(&self) -> usize {
+        if self.is_model_02() {
+            MODEL_02_MAX_WAVEFORM_LEN
+        } else {
+            MAX_WAVEFORM_LEN
+        }
+    }
+
+    /// The character cell the M8 lays its text out on, which depends on
+    /// the model and the font it was set to.
+    pub fn cell_size(&self) -> UVec2 {
+        match (self.is_model_02(), self.font_mode) {
+            (false, 0) => CELL_SIZE,
+            (false, _) => LARGE_CELL_SIZE,
+            (true, 0) => MODEL_02_CELL_SIZE,
+            (true, _) => MODEL_02_LARGE_CELL_SIZE,
+        }
+    }
+
+    /// Returns true if the firmware sends the extended key state packet.
+    pub fn has_extended_key_state(&self) -> bool {
+        let (major, minor, patch) = EXTENDED_KEY_STATE_VERSION;
+        self.firmware_at_least(major, minor, patch)
+    }
+}
+
+/// Sent whenever the M8 reports a [M8DeviceInfo] which differs from
+/// the previous one, such as after a firmware update or font change.
+#[cfg(feature = "bevy")]
+#[derive(Message, Debug, Clone, Copy)]
+pub struct M8DeviceInfoChanged {
+    /// The previous device information, if any was received.
+    pub old: Option<M8DeviceInfo>,
+    pub new: M8DeviceInfo,
+}
+
+/// Sent once, when the M8 first identifies itself with a
+/// [M8Command::SystemInfo].
+#[cfg(feature = "bevy")]
+#[derive(Message, Debug, Clone, Copy)]
+pub struct M8Ready {
+    pub info: M8DeviceInfo,
+}
+
+/// The most waveform buffers kept around for reuse.
+const WAVEFORM_POOL_SIZE: usize = 8;
+
 /// The command decoder.
 pub struct CommandDecoder {
     current_colour: Color,
+    /// Waveform buffers handed back to be reused by the next waveforms.
+    waveform_pool: Vec<Vec<u8>>,
+    /// The latest device information, used to pick which packet
+    /// variants are valid.
+    device_info: Option<M8DeviceInfo>,
+    /// Unknown command bytes already warned about, so a newer firmware
+    /// doesn't fill the log with the same warning every frame.
+    unknown_commands: HashSet<u8>,
+    /// The command bytes decoded, all of them if unset.
+    enabled_commands: Option<HashSet<u8>>,
 }
 
+/// Reads the colour at the given offset, None if the packet is too short.
 #[inline]
-fn u8_slice_to_color(slice: &[u8]) -> Color {
-    Color::Srgba(Srgba {
-        red: slice[0] as f32 / 255.0,
-        green: slice[1] as f32 / 255.0,
-        blue: slice[2] as f32 / 255.0,
-        alpha: 1.0,
-    })
+fn u8_slice_to_color(buf: &[u8], offset: usize) -> Option<Color> {
+    let rgb = buf.get(offset..offset + 3)?.try_into().ok()?;
+    Some(color_from_rgb(rgb))
+}
+
+/// Reads the little endian u16 at the given offset, None if the packet is
+/// too short.
+#[inline]
+fn u8_slice_to_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    let &[low, high] = buf.get(offset..offset + 2)? else {
+        return None;
+    };
+    Some(u16::from_le_bytes([low, high]))
 }
 
 impl SlipDecoder {
@@ -93,10 +348,59 @@ impl SlipDecoder {
         Self {
             state: State::Normal,
             buffer: Vec::with_capacity(SLIP_BUFFER_CAPACITY),
+            complete: false,
+        }
+    }
+
+    /// Drops the unfinished packet and any pending escape, so decoding
+    /// starts over at the next `SLIP_END`.
+    #[cfg(feature = "bevy")]
+    pub fn reset(&mut self) {
+        self.state = State::Normal;
+        self.buffer.clear();
+        self.complete = false;
+    }
+
+    /// Feeds a chunk of bytes to the decoder, calling `on_packet` with every
+    /// packet it completes. Runs of plain bytes are copied in bulk.
+    ///
+    /// Chunks may end anywhere, including right after a `SLIP_ESC`: the
+    /// escape and the unfinished packet are kept and completed by the next
+    /// call, so the caller just passes on the bytes as they arrive.
+    pub fn process(&mut self, mut bytes: &[u8], mut on_packet: impl FnMut(&[u8])) {
+        while !bytes.is_empty() {
+            if self.state == State::Normal {
+                if self.complete {
+                    self.buffer.clear();
+                    self.complete = false;
+                }
+
+                let run = memchr2(SLIP_END, SLIP_ESC, bytes).unwrap_or(bytes.len());
+                self.buffer.extend_from_slice(&bytes[..run]);
+                bytes = &bytes[run..];
+            }
+
+            // Either a special byte or the byte following an escape.
+            let Some((&byte, rest)) = bytes.split_first() else {
+                break;
+            };
+            bytes = rest;
+            if let Some(packet) = self.process_byte(byte) {
+                on_packet(packet);
+            }
         }
     }
 
-    pub fn process_byte(&mut self, byte: u8) -> Option<Vec<u8>> {
+    /// Feeds a byte to the decoder, returning the packet it completes. An
+    /// escape waits for the byte following it, and an escape followed by
+    /// anything but `SLIP_ESC_END` or `SLIP_ESC_ESC` drops the packet.
+    pub fn process_byte(&mut self, byte: u8) -> Option<&[u8]> {
+        // The packet is borrowed from the buffer to avoid copying it.
+        if self.complete {
+            self.buffer.clear();
+            self.complete = false;
+        }
+
         match self.state {
             State::Normal => match byte {
                 SLIP_END => {
@@ -104,9 +408,8 @@ impl SlipDecoder {
                         return None;
                     }
 
-                    let packet = self.buffer.clone();
-                    self.buffer.clear();
-                    return Some(packet);
+                    self.complete = true;
+                    Some(&self.buffer)
                 }
                 SLIP_ESC => {
                     self.state = State::Escaped;
@@ -135,7 +438,42 @@ impl SlipDecoder {
 impl CommandDecoder {
     pub fn new() -> Self {
         Self {
-            current_colour: Color::WHITE,
+            current_colour: color_from_rgb(&[0xFF, 0xFF, 0xFF]),
+            waveform_pool: Vec::with_capacity(WAVEFORM_POOL_SIZE),
+            device_info: None,
+            unknown_commands: HashSet::new(),
+            enabled_commands: None,
+        }
+    }
+
+    /// Only decodes the commands with the given bytes, skipping the others
+    /// without building them. Decodes every command if unset.
+    pub fn set_enabled_commands(&mut self, enabled_commands: Option<HashSet<u8>>) {
+        self.enabled_commands = enabled_commands;
+    }
+
+    /// Returns true if the packet holds a command which isn't decoded, so
+    /// it isn't counted as an error.
+    pub fn skips(&self, packet: &[u8]) -> bool {
+        packet.first().is_some_and(|cmd_type| {
+            self.enabled_commands
+                .as_ref()
+                .is_some_and(|enabled| !enabled.contains(cmd_type))
+        })
+    }
+
+    /// Warns about unknown commands again, as the M8 on the other end may
+    /// have changed.
+    #[cfg(all(feature = "bevy", not(target_arch = "wasm32")))]
+    pub fn forget_unknown_commands(&mut self) {
+        self.unknown_commands.clear();
+    }
+
+    /// Hands back the waveform of a [M8Command::DrawOscilloscopeWaveform]
+    /// once drawn, so its allocation can be reused.
+    pub fn recycle_waveform(&mut self, waveform: Vec<u8>) {
+        if self.waveform_pool.len() < WAVEFORM_POOL_SIZE {
+            self.waveform_pool.push(waveform);
         }
     }
 
@@ -145,14 +483,25 @@ impl CommandDecoder {
         }
 
         let cmd_type = buf[0];
+        if self.skips(buf) {
+            // The device information picks the variants of later packets.
+            if cmd_type == SYSTEM_INFO_COMMAND {
+                self.parse_system_info(buf);
+            }
+            return None;
+        }
+
         match cmd_type {
             DRAW_CHARACTER_COMMAND => self.parse_character(buf),
             DRAW_RECTANGLE_COMMAND => self.parse_rectangle(buf),
             DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND => self.parse_waveform(buf),
             SYSTEM_INFO_COMMAND => self.parse_system_info(buf),
-            KEY_PRESS_STATE_COMMAND => None,
+            KEY_PRESS_STATE_COMMAND => self.parse_key_press_state(buf),
             _ => {
-                warn!("Unknown M8 command: {:02X}", buf[0]);
+                if self.unknown_commands.insert(cmd_type) {
+                    #[cfg(feature = "bevy")]
+                    warn!("Unknown M8 command: {:02X}", cmd_type);
+                }
                 None
             }
         }
@@ -161,24 +510,26 @@ impl CommandDecoder {
     fn parse_rectangle(&mut self, buf: &[u8]) -> Option<M8Command> {
         let len = buf.len();
 
-        if len < 5 {
+        // Any other length is a packet cut short, which would otherwise be
+        // mistaken for a shorter variant.
+        if !matches!(len, 5 | 8 | 9 | 12) {
             return None;
         }
 
         if len == 8 || len == 12 {
             let offset = if len == 8 { 5 } else { 9 };
-            self.current_colour = u8_slice_to_color(&buf[offset..offset + 3]);
+            self.current_colour = u8_slice_to_color(buf, offset)?;
         }
 
         Some(M8Command::DrawRectangle {
             pos: Position {
-                x: u16::from_le_bytes([buf[1], buf[2]]),
-                y: u16::from_le_bytes([buf[3], buf[4]]),
+                x: u8_slice_to_u16(buf, 1)?,
+                y: u8_slice_to_u16(buf, 3)?,
             },
             size: if len >= 9 {
                 Size {
-                    x: u16::from_le_bytes([buf[5], buf[6]]),
-                    y: u16::from_le_bytes([buf[7], buf[8]]),
+                    x: u8_slice_to_u16(buf, 5)?,
+                    y: u8_slice_to_u16(buf, 7)?,
                 }
             } else {
                 Size { x: 1, y: 1 }
@@ -192,36 +543,129 @@ impl CommandDecoder {
             return None;
         }
         Some(M8Command::DrawCharacter {
-            c: buf[1],
+            c: *buf.get(1)?,
             pos: Position {
-                x: u16::from_le_bytes([buf[2], buf[3]]),
-                y: u16::from_le_bytes([buf[4], buf[5]]),
+                x: u8_slice_to_u16(buf, 2)?,
+                y: u8_slice_to_u16(buf, 4)?,
             },
-            foreground: u8_slice_to_color(&buf[6..=8]),
-            background: u8_slice_to_color(&buf[9..=11]),
+            foreground: u8_slice_to_color(buf, 6)?,
+            background: u8_slice_to_color(buf, 9)?,
         })
     }
 
-    fn parse_waveform(&self, buf: &[u8]) -> Option<M8Command> {
-        if buf.len() < 4 {
-            return None;
+    fn parse_key_press_state(&self, buf: &[u8]) -> Option<M8Command> {
+        let extended = self
+            .device_info
+            .is_some_and(|info| info.has_extended_key_state());
+        match buf.len() {
+            2 => (),
+            3 if extended => (),
+            _ => return None,
         }
-        Some(M8Command::DrawOscilloscopeWaveform {
-            colour: u8_slice_to_color(&buf[1..=3]),
-            waveform: buf[4..].to_vec(),
-        })
+        Some(M8Command::KeyPressState { keys: *buf.get(1)? })
     }
 
-    fn parse_system_info(&self, buf: &[u8]) -> Option<M8Command> {
-        if buf.len() < 6 {
+    fn parse_waveform(&mut self, buf: &[u8]) -> Option<M8Command> {
+        let colour = u8_slice_to_color(buf, 1)?;
+        let max_len = self
+            .device_info
+            .map_or(MODEL_02_MAX_WAVEFORM_LEN, |info| info.max_waveform_len());
+        let samples = buf.get(4..)?;
+        if samples.len() > max_len {
+            #[cfg(feature = "bevy")]
+            warn!("Oscilloscope waveform too long: {} bytes", samples.len());
             return None;
         }
+        let mut waveform = self.waveform_pool.pop().unwrap_or_default();
+        waveform.clear();
+        waveform.extend_from_slice(samples);
+        Some(M8Command::DrawOscilloscopeWaveform { colour, waveform })
+    }
+
+    fn parse_system_info(&mut self, buf: &[u8]) -> Option<M8Command> {
+        let &[hardware_type, major, minor, patch, font_mode] = buf.get(1..6)? else {
+            return None;
+        };
+        self.device_info = Some(M8DeviceInfo {
+            hardware_type,
+            major,
+            minor,
+            patch,
+            font_mode,
+        });
         Some(M8Command::SystemInfo {
-            hardware_type: buf[1],
-            major: buf[2],
-            minor: buf[3],
-            patch: buf[4],
-            font_mode: buf[5],
+            hardware_type,
+            major,
+            minor,
+            patch,
+            font_mode,
         })
     }
 }
+
+/// Decodes the raw stream sent by the M8 into commands, one chunk of bytes
+/// at a time, without collecting them.
+pub struct M8StreamDecoder {
+    slip_decoder: SlipDecoder,
+    command_decoder: CommandDecoder,
+}
+
+impl M8StreamDecoder {
+    pub fn new() -> Self {
+        Self {
+            slip_decoder: SlipDecoder::new(),
+            command_decoder: CommandDecoder::new(),
+        }
+    }
+
+    /// Only decodes the commands with the given bytes, such as
+    /// [SYSTEM_INFO_COMMAND], skipping the others without building them.
+    pub fn with_enabled_commands(mut self, enabled_commands: impl IntoIterator<Item = u8>) -> Self {
+        self.command_decoder
+            .set_enabled_commands(Some(enabled_commands.into_iter().collect()));
+        self
+    }
+
+    /// Feeds a chunk of the stream to the decoder, calling `on_command` with
+    /// every command it completes. Returns the number of packets which
+    /// couldn't be decoded, not counting the commands skipped.
+    ///
+    /// A packet split across chunks, even between an escape and the byte it
+    /// escapes, is completed by a later call. Packets which fail to decode
+    /// don't reset the decoder, so they never lose the bytes after them.
+    ///
+    /// Waveforms are reused once `on_command` returns, so decoding doesn't
+    /// allocate after the first few commands.
+    pub fn decode(&mut self, bytes: &[u8], mut on_command: impl FnMut(&M8Command)) -> usize {
+        let mut errors = 0;
+        let command_decoder = &mut self.command_decoder;
+        self.slip_decoder
+            .process(bytes, |packet| match command_decoder.parse(packet) {
+                Some(command) => {
+                    on_command(&command);
+                    if let M8Command::DrawOscilloscopeWaveform { waveform, .. } = command {
+                        command_decoder.recycle_waveform(waveform);
+                    }
+                }
+                None => {
+                    if !command_decoder.skips(packet) {
+                        errors += 1;
+                    }
+                }
+            });
+        errors
+    }
+}
+
+impl Default for M8StreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes a complete recording of the raw stream sent by the M8, calling
+/// `on_command` with every command in it. Returns the number of packets
+/// which couldn't be decoded.
+pub fn decode_stream(bytes: &[u8], on_command: impl FnMut(&M8Command)) -> usize {
+    M8StreamDecoder::new().decode(bytes, on_command)
+}
@@ -0,0 +1,208 @@
+//! This file provides M8s beyond the first, each an entity with its own
+//! connection, commands and display.
+
+use bevy::prelude::*;
+
+use crate::{
+    M8LoadingState, M8UpdateSystems, configure_update_systems,
+    mock::M8MockConnection,
+    serial::{M8Commands, M8Connection, M8SerialPlugin, m8_decode_paused},
+};
+#[cfg(feature = "render")]
+use crate::{
+    decoder::{M8Command, M8DeviceInfo},
+    dimensions::M8DisplayDimensions,
+    display::{
+        M8ColorSpace, M8Display, M8DrawStyle, M8ScopeRegion, M8WaveformHistory, M8WaveformMode,
+        display_image,
+    },
+    fonts::M8Font,
+    theme::M8Theme,
+};
+
+/// An M8 connected alongside the one driving the [M8Connection] resource.
+///
+/// Spawning one connects to its port, retrying until the M8 shows up, and
+/// adds an [M8Connection] and [M8Commands] to the entity. With the `render`
+/// feature it also gets an [M8Display] and a [Sprite] showing it, to be
+/// placed with a [Transform].
+#[derive(Component, Debug, Clone, Default)]
+pub struct M8Device {
+    /// The serial port of the M8. Unlike the first M8, no other port is
+    /// tried when this one is missing.
+    pub port: String,
+    /// Replays a scripted stream instead of connecting to the port.
+    pub mock: Option<M8MockConnection>,
+    /// The baud rate of the serial port, 115200 if unset.
+    pub baud_rate: Option<u32>,
+}
+
+impl M8Device {
+    /// Creates a device connecting to the given serial port.
+    pub fn new(port: impl Into<String>) -> Self {
+        Self {
+            port: port.into(),
+            ..default()
+        }
+    }
+
+    /// Replays the mock connection instead of connecting to the port.
+    pub fn with_mock(self, mock: M8MockConnection) -> Self {
+        Self {
+            mock: Some(mock),
+            ..self
+        }
+    }
+}
+
+fn connect_device(add: On<Add, M8Device>, devices: Query<&M8Device>, mut commands: Commands) {
+    let Ok(device) = devices.get(add.entity) else {
+        return;
+    };
+
+    let connection = M8SerialPlugin {
+        preferred_device: Some(device.port.clone()),
+        mock: device.mock.clone(),
+        baud_rate: device.baud_rate,
+        ..default()
+    }
+    .connect(true);
+    commands
+        .entity(add.entity)
+        .insert((connection, M8Commands::default()));
+}
+
+fn receive_device_commands(mut devices: Query<(&M8Device, &M8Connection, &mut M8Commands)>) {
+    for (device, connection, mut commands) in devices.iter_mut() {
+        for error in connection.errors() {
+            warn!("{}: {}. {}", device.port, error, error.remedy());
+        }
+
+        if connection.is_connected() {
+            connection.receive(&mut commands);
+        } else {
+            commands.0.clear();
+        }
+    }
+}
+
+#[cfg(feature = "render")]
+fn add_device_display(
+    add: On<Add, M8Device>,
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let dimensions = M8DisplayDimensions::default();
+    let handle = images.add(display_image(dimensions));
+    commands.entity(add.entity).insert((
+        dimensions,
+        M8Display::new(handle.clone()),
+        Sprite::from_image(handle),
+    ));
+}
+
+/// Draws each device's commands into its display, in the font and theme
+/// of the first M8.
+#[cfg(feature = "render")]
+fn render_devices(
+    mut devices: Query<(&M8Commands, &mut M8Display, &mut M8DisplayDimensions), With<M8Device>>,
+    theme: Option<Res<M8Theme>>,
+    colour_space: Option<Res<M8ColorSpace>>,
+    waveform_mode: Option<Res<M8WaveformMode>>,
+    font: Res<M8Font>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (commands, mut display, mut dimensions) in devices.iter_mut() {
+        // Resize to the model reported before drawing what follows.
+        for command in commands.0.iter() {
+            let &M8Command::SystemInfo {
+                hardware_type,
+                major,
+                minor,
+                patch,
+                font_mode,
+            } = command
+            else {
+                continue;
+            };
+            let info = M8DeviceInfo {
+                hardware_type,
+                major,
+                minor,
+                patch,
+                font_mode,
+            };
+            let Some(model) = info.model() else {
+                continue;
+            };
+            let size = model.display_size();
+            let resized = M8DisplayDimensions {
+                width: size.x,
+                height: size.y,
+            };
+            if dimensions.set_if_neq(resized)
+                && let Some(image) = images.get_mut(display.image())
+            {
+                *image = display_image(resized);
+            }
+        }
+
+        let mut scope = M8ScopeRegion::default().0;
+        scope.max.x = dimensions.width;
+
+        let images_ptr: *mut Assets<Image> = &mut *images;
+        unsafe {
+            // Only borrowing the display mutably marks it for upload to the GPU.
+            let display_image = (*images_ptr).get_mut(display.image());
+            let atlas = (*images_ptr).get(&font.image);
+
+            if let (Some(display_image), Some(atlas)) = (display_image, atlas) {
+                let style = M8DrawStyle {
+                    font: &font,
+                    atlas,
+                    theme: theme.as_deref(),
+                    colour_space: colour_space.as_deref().copied().unwrap_or_default(),
+                    waveform_mode: waveform_mode.as_deref().copied().unwrap_or_default(),
+                    scope,
+                };
+                style.draw(
+                    display_image,
+                    &mut display.background,
+                    *dimensions,
+                    &commands.0,
+                    &mut M8WaveformHistory::default(),
+                );
+            }
+        }
+    }
+}
+
+/// This plugin provides [M8Device], for connecting M8s beyond the first.
+///
+/// The first M8 is still the one the resources belong to, and the only one
+/// the keyboard, audio, scrubber and watchdog work with.
+pub struct M8DevicesPlugin;
+
+impl Plugin for M8DevicesPlugin {
+    fn build(&self, app: &mut App) {
+        configure_update_systems(app);
+        app.add_observer(connect_device);
+        app.add_systems(
+            Update,
+            receive_device_commands
+                .in_set(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running).and(not(m8_decode_paused))),
+        );
+
+        #[cfg(feature = "render")]
+        {
+            app.add_observer(add_device_display);
+            app.add_systems(
+                Update,
+                render_devices
+                    .in_set(M8UpdateSystems::DisplayRender)
+                    .run_if(in_state(M8LoadingState::Running).and(resource_exists::<M8Font>)),
+            );
+        }
+    }
+}
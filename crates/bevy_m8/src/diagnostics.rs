@@ -0,0 +1,93 @@
+//! This file provides bevy diagnostics describing the health of the M8
+//! connection, decoding and audio.
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+};
+
+use crate::{
+    M8UpdateSystems,
+    audio::M8AudioStats,
+    serial::{M8Commands, M8Connection},
+};
+
+/// Bytes read from the M8 per second.
+pub const M8_BYTES_PER_SECOND: DiagnosticPath = DiagnosticPath::const_new("m8/bytes_per_second");
+
+/// Commands decoded from the M8 per frame.
+pub const M8_COMMANDS_PER_FRAME: DiagnosticPath =
+    DiagnosticPath::const_new("m8/commands_per_frame");
+
+/// Total number of packets from the M8 which couldn't be decoded.
+pub const M8_DECODE_ERRORS: DiagnosticPath = DiagnosticPath::const_new("m8/decode_errors");
+
+/// Total number of audio output callbacks which ran out of samples.
+pub const M8_AUDIO_UNDERRUNS: DiagnosticPath = DiagnosticPath::const_new("m8/audio_underruns");
+
+/// Total number of audio input callbacks which found the buffer full.
+pub const M8_AUDIO_OVERRUNS: DiagnosticPath = DiagnosticPath::const_new("m8/audio_overruns");
+
+/// 1 while the M8 is connected, 0 otherwise.
+pub const M8_CONNECTED: DiagnosticPath = DiagnosticPath::const_new("m8/connected");
+
+/// Total number of times the M8 connected or disconnected.
+pub const M8_CONNECTION_TRANSITIONS: DiagnosticPath =
+    DiagnosticPath::const_new("m8/connection_transitions");
+
+/// Values remembered between frames to compute the measurements.
+#[derive(Default)]
+struct Previous {
+    bytes_read: u64,
+    connected: bool,
+    transitions: u64,
+}
+
+fn measure(
+    time: Res<Time>,
+    connection: Res<M8Connection>,
+    commands: Res<M8Commands>,
+    audio: Option<Res<M8AudioStats>>,
+    mut previous: Local<Previous>,
+    mut diagnostics: Diagnostics,
+) {
+    let bytes_read = connection.bytes_read();
+    let delta = time.delta_secs_f64();
+    if delta > 0.0 {
+        let bytes = bytes_read - previous.bytes_read;
+        diagnostics.add_measurement(&M8_BYTES_PER_SECOND, || bytes as f64 / delta);
+    }
+    previous.bytes_read = bytes_read;
+
+    diagnostics.add_measurement(&M8_COMMANDS_PER_FRAME, || commands.0.len() as f64);
+    diagnostics.add_measurement(&M8_DECODE_ERRORS, || connection.decode_errors() as f64);
+
+    if let Some(audio) = audio {
+        diagnostics.add_measurement(&M8_AUDIO_UNDERRUNS, || audio.underruns() as f64);
+        diagnostics.add_measurement(&M8_AUDIO_OVERRUNS, || audio.overruns() as f64);
+    }
+
+    let connected = connection.is_connected();
+    if connected != previous.connected {
+        previous.transitions += 1;
+        previous.connected = connected;
+    }
+    diagnostics.add_measurement(&M8_CONNECTED, || connected as u8 as f64);
+    diagnostics.add_measurement(&M8_CONNECTION_TRANSITIONS, || previous.transitions as f64);
+}
+
+/// This plugin publishes the M8's metrics as bevy diagnostics.
+pub struct M8DiagnosticsPlugin;
+
+impl Plugin for M8DiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(M8_BYTES_PER_SECOND).with_suffix(" B/s"));
+        app.register_diagnostic(Diagnostic::new(M8_COMMANDS_PER_FRAME));
+        app.register_diagnostic(Diagnostic::new(M8_DECODE_ERRORS));
+        app.register_diagnostic(Diagnostic::new(M8_AUDIO_UNDERRUNS));
+        app.register_diagnostic(Diagnostic::new(M8_AUDIO_OVERRUNS));
+        app.register_diagnostic(Diagnostic::new(M8_CONNECTED));
+        app.register_diagnostic(Diagnostic::new(M8_CONNECTION_TRANSITIONS));
+        app.add_systems(Update, measure.after(M8UpdateSystems::Decode));
+    }
+}
@@ -0,0 +1,56 @@
+//! This file provides the size of the M8's screen.
+
+use bevy::prelude::*;
+
+use crate::decoder::{Position, Size};
+
+pub const DISPLAY_WIDTH: u32 = 320;
+pub const DISPLAY_HEIGHT: u32 = 240;
+
+/// The size of the framebuffer the M8's screen is drawn into.
+#[derive(Resource, Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource, Component)]
+pub struct M8DisplayDimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl M8DisplayDimensions {
+    /// The size of the framebuffer.
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(self.width, self.height)
+    }
+
+    /// The whole framebuffer.
+    pub fn rect(&self) -> URect {
+        URect::from_corners(UVec2::ZERO, self.size())
+    }
+
+    /// Returns true if the position lies on the framebuffer.
+    pub fn contains(&self, pos: Position) -> bool {
+        (pos.x as u32) < self.width && (pos.y as u32) < self.height
+    }
+
+    /// Returns true if the rectangle covers the whole framebuffer.
+    pub fn is_covered_by(&self, pos: Position, size: Size) -> bool {
+        pos == Position::ZERO && size.x as u32 >= self.width && size.y as u32 >= self.height
+    }
+
+    /// The part of the rectangle which lies on the framebuffer, None if none
+    /// of it does or it has no area. Rectangles reaching past `u16::MAX` are
+    /// cut off there rather than wrapping around.
+    pub fn clip(&self, pos: Position, size: Size) -> Option<URect> {
+        let min = pos.as_uvec2();
+        let max = pos.saturating_add(size).as_uvec2().min(self.size());
+        min.cmplt(max).all().then_some(URect { min, max })
+    }
+}
+
+impl Default for M8DisplayDimensions {
+    fn default() -> Self {
+        Self {
+            width: DISPLAY_WIDTH,
+            height: DISPLAY_HEIGHT,
+        }
+    }
+}
@@ -1,43 +1,217 @@
 //! This file provides the display for the Dirtywave M8.
 
-use std::ops::Add;
+use std::{collections::VecDeque, time::Duration};
 
 use bevy::{
+    app::ScheduleRunnerPlugin,
     asset::RenderAssetUsages,
     image::ImageSampler,
     math::{U16Vec2, u16vec2},
     prelude::*,
-    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
-    window::WindowResolution,
+    render::{
+        RenderPlugin,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        settings::WgpuSettings,
+    },
+    window::{
+        ExitCondition, MonitorSelection, PresentMode, PrimaryWindow, WindowFocused, WindowMode,
+        WindowPosition, WindowResolution,
+    },
+    winit::WinitPlugin,
 };
 
 use crate::{
-    M8LoadingState,
-    assets::M8Assets,
-    decoder::{M8Command, Position, Size},
-    keymap::M8KeyMap,
-    serial::M8Connection,
-    utils::keycode_to_mask,
+    HEADLESS_FRAME_TIME, M8LoadingState, M8UpdateSystems, configure_update_systems,
+    decoder::{
+        M8Command, M8DeviceInfo, M8DeviceInfoChanged, M8HardwareModel, Position, Size,
+        waveform_points,
+    },
+    dimensions::{DISPLAY_HEIGHT, DISPLAY_WIDTH, M8DisplayDimensions},
+    fonts::M8Font,
+    framebuffer::{draw_line, draw_vertical_span},
+    led_matrix::{M8PixelOutput, present_pixels},
+    serial::{M8Commands, M8Connection, M8DecodePaused, m8_decode_paused, m8_screen_changed},
+    theme::M8Theme,
 };
 
-pub const DISPLAY_WIDTH: u32 = 320;
-pub const DISPLAY_HEIGHT: u32 = 240;
-
 /// The title used for the Display window.
-const TITLE: &'static str = "Bevy M8";
+const TITLE: &str = "Bevy M8";
 
-/// The display which displays the M8.
-#[derive(Resource)]
+/// The text drawn over the display while the M8 is disconnected.
+const DEFAULT_DISCONNECTED_MESSAGE: &str = "DISCONNECTED";
+
+/// The tint dimming the display while the M8 is disconnected.
+const DISCONNECTED_TINT: Color = Color::srgb(0.45, 0.45, 0.45);
+
+/// The height of the oscilloscope at the top of the screen.
+const DEFAULT_SCOPE_HEIGHT: u32 = 17;
+
+/// How much of its colour a waveform in the history keeps per frame of age.
+const DEFAULT_WAVEFORM_FADE: f32 = 0.6;
+
+/// The display which displays the M8. Any `M8Device` has
+/// one of its own as a component.
+#[derive(Resource, Component)]
 pub struct M8Display {
     display: Handle<Image>,
-    background: Color,
+    pub(crate) background: Color,
 }
 
-fn setup_display(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+impl M8Display {
+    pub(crate) fn new(display: Handle<Image>) -> Self {
+        Self {
+            display,
+            background: Color::default(),
+        }
+    }
+
+    /// The image the M8's screen is drawn into.
+    pub fn image(&self) -> &Handle<Image> {
+        &self.display
+    }
+
+    /// The background colour of the M8's theme.
+    pub fn background(&self) -> Color {
+        self.background
+    }
+}
+
+/// How the RGB bytes sent by the M8 are interpreted when drawn. Colours
+/// replaced by the [M8Theme] are drawn as given.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub enum M8ColorSpace {
+    /// The bytes are sRGB and drawn as sent.
+    #[default]
+    Srgb,
+    /// The bytes are linear light, which brightens the mid tones, e.g.
+    /// 0x80 is drawn as sRGB 0xBC.
+    Linear,
+}
+
+impl M8ColorSpace {
+    /// Returns the colour to draw for one decoded from the M8.
+    pub fn interpret(self, colour: Color) -> Color {
+        match self {
+            M8ColorSpace::Srgb => colour,
+            M8ColorSpace::Linear => {
+                let Srgba {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                } = colour.to_srgba();
+                Color::linear_rgba(red, green, blue, alpha)
+            }
+        }
+    }
+}
+
+/// Counts how often the display was redrawn or left untouched.
+#[derive(Resource, Debug, Default, Clone, Copy, Reflect)]
+#[reflect(Resource)]
+pub struct M8DisplayStats {
+    /// Frames in which the display image was updated.
+    pub frames_rendered: u64,
+    /// Frames skipped because nothing was drawn.
+    pub frames_skipped: u64,
+}
+
+/// Receives the RGBA pixels, width and height of a captured frame.
+type M8FrameCallback = Box<dyn FnMut(&[u8], u32, u32) + Send + Sync>;
+
+/// Hands the display's pixels to a callback after it has been drawn, for
+/// exporting the screen. Nothing is captured unless this is inserted.
+#[derive(Resource)]
+pub struct M8FrameSink {
+    timer: Timer,
+    callback: M8FrameCallback,
+}
+
+impl M8FrameSink {
+    /// Calls `callback` with the RGBA pixels, width and height of the
+    /// display at most once every interval.
+    pub fn new(
+        interval: Duration,
+        callback: impl FnMut(&[u8], u32, u32) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            timer: Timer::new(interval, TimerMode::Repeating),
+            callback: Box::new(callback),
+        }
+    }
+}
+
+/// How the display behaves while the M8 is disconnected.
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8DisconnectedDisplay {
+    /// Keeps showing the last frame until the M8 redraws, instead of
+    /// clearing the display when it disconnects.
+    pub keep_frame: bool,
+    /// Dims the display while reconnecting.
+    pub dim: bool,
+    /// The text drawn in the middle of the display while disconnected,
+    /// nothing is drawn if it is empty.
+    pub message: String,
+    /// Also treats the M8 as disconnected once it has sent nothing for this
+    /// long, for streams which stall without the port going away.
+    pub stall_timeout: Option<Duration>,
+}
+
+impl Default for M8DisconnectedDisplay {
+    fn default() -> Self {
+        Self {
+            keep_frame: true,
+            dim: true,
+            message: DEFAULT_DISCONNECTED_MESSAGE.into(),
+            stall_timeout: None,
+        }
+    }
+}
+
+/// Marker for the sprite presenting the display.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct M8DisplaySprite;
+
+/// Marker for the camera looking at the display.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct M8DisplayCamera;
+
+/// Sent when the model of the connected M8 is first detected or changes,
+/// after the display has been resized to fit it.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct M8ModelDetected {
+    pub model: M8HardwareModel,
+    pub dimensions: M8DisplayDimensions,
+}
+
+/// Sent after the display was drawn with the area of it which changed, so
+/// a mirror of the display only has to update that part. Covers the whole
+/// display when it was replaced, such as after resizing it.
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct M8DirtyRegion {
+    pub rect: URect,
+}
+
+/// Sent when the display was cleared after the connected M8 sent nothing
+/// for the stream timeout of the [M8DisplayPlugin].
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct M8StreamLost {
+    /// How long the M8 had been silent.
+    pub silent_for: Duration,
+}
+
+/// The stream timeout chosen when adding the [M8DisplayPlugin].
+#[derive(Resource)]
+struct M8StreamTimeout(Duration);
+
+/// Creates a blank framebuffer of the given size.
+pub(crate) fn display_image(dimensions: M8DisplayDimensions) -> Image {
     let mut image = Image::new_fill(
         Extent3d {
-            width: DISPLAY_WIDTH,
-            height: DISPLAY_HEIGHT,
+            width: dimensions.width,
+            height: dimensions.height,
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
@@ -47,76 +221,311 @@ fn setup_display(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
     );
 
     image.sampler = ImageSampler::nearest();
+    image
+}
 
-    let handle = images.add(image);
-    commands.insert_resource(M8Display {
-        display: handle.clone(),
-        background: Color::default(),
-    });
-    commands.spawn(Sprite {
-        image: handle.clone(),
-        ..default()
-    });
+fn setup_display(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let dimensions = M8DisplayDimensions::default();
+    let handle = images.add(display_image(dimensions));
+    commands.insert_resource(dimensions);
+    commands.insert_resource(M8Display::new(handle.clone()));
+    commands.spawn((
+        M8DisplaySprite,
+        Sprite {
+            image: handle.clone(),
+            ..default()
+        },
+    ));
 
     commands.spawn((
+        M8DisplayCamera,
         Camera2d,
         Projection::Orthographic(OrthographicProjection {
             scaling_mode: bevy::camera::ScalingMode::Fixed {
-                width: DISPLAY_WIDTH as f32,
-                height: DISPLAY_HEIGHT as f32,
+                width: dimensions.width as f32,
+                height: dimensions.height as f32,
             },
             ..OrthographicProjection::default_2d()
         }),
     ));
 }
 
-fn draw_rectangle(display: &mut Image, pos: Position, size: Size, colour: Color) {
-    for y in pos.y..pos.y + size.y {
-        for x in pos.x..pos.x + size.x {
-            if x < DISPLAY_WIDTH as u16 && y < DISPLAY_HEIGHT as u16 {
-                display.set_color_at(x.into(), y.into(), colour).unwrap();
+/// Resizes the framebuffer and camera when the M8 turns out to be a model
+/// with a different screen size.
+#[allow(clippy::too_many_arguments)]
+fn resize_display(
+    mut device_info_changed: MessageReader<M8DeviceInfoChanged>,
+    mut model_detected: MessageWriter<M8ModelDetected>,
+    mut dirty: MessageWriter<M8DirtyRegion>,
+    mut dimensions: ResMut<M8DisplayDimensions>,
+    mut scope: ResMut<M8ScopeRegion>,
+    display: Res<M8Display>,
+    mut images: ResMut<Assets<Image>>,
+    mut cameras: Query<&mut Projection, With<M8DisplayCamera>>,
+) {
+    for M8DeviceInfoChanged { old, new } in device_info_changed.read() {
+        let Some(model) = new.model() else {
+            continue;
+        };
+        if old.and_then(|old| old.model()) == Some(model) {
+            continue;
+        }
+
+        let size = model.display_size();
+        let resized = M8DisplayDimensions {
+            width: size.x,
+            height: size.y,
+        };
+        if dimensions.set_if_neq(resized) {
+            scope.0.max.x = size.x;
+            info!(
+                "Detected {:?}, resizing the display to {}x{}",
+                model, size.x, size.y
+            );
+            if let Some(image) = images.get_mut(&display.display) {
+                *image = display_image(resized);
+                dirty.write(M8DirtyRegion {
+                    rect: resized.rect(),
+                });
+            }
+            for mut projection in cameras.iter_mut() {
+                if let Projection::Orthographic(orthographic) = &mut *projection {
+                    orthographic.scaling_mode = bevy::camera::ScalingMode::Fixed {
+                        width: size.x as f32,
+                        height: size.y as f32,
+                    };
+                }
+            }
+        }
+
+        model_detected.write(M8ModelDetected {
+            model,
+            dimensions: resized,
+        });
+    }
+}
+
+/// Draws the message centred on the display, in the font the M8 uses.
+fn draw_message(
+    display: &mut Image,
+    dimensions: M8DisplayDimensions,
+    font: &M8Font,
+    atlas: &Image,
+    message: &str,
+    background: Color,
+) {
+    let width = message.len() as u32 * font.glyph_width;
+    let x = dimensions.width.saturating_sub(width) / 2;
+    let y = dimensions.height.saturating_sub(font.glyph_height) / 2;
+    for (i, c) in message.bytes().enumerate() {
+        let pos = Position::new((x + i as u32 * font.glyph_width) as u16, y as u16);
+        draw_character(
+            display,
+            dimensions,
+            font,
+            atlas,
+            c,
+            pos,
+            Color::WHITE,
+            background,
+        );
+    }
+}
+
+/// Dims the display and draws the disconnected message while the M8 is
+/// away or stalled, asking it to redraw over the message once it is back.
+#[allow(clippy::too_many_arguments)]
+fn show_connection_state(
+    time: Res<Time>,
+    settings: Res<M8DisconnectedDisplay>,
+    connection: Option<Res<M8Connection>>,
+    commands: Res<M8Commands>,
+    paused: Option<Res<M8DecodePaused>>,
+    mut last_command: Local<Duration>,
+    mut shown: Local<bool>,
+    display: Res<M8Display>,
+    dimensions: Res<M8DisplayDimensions>,
+    font: Res<M8Font>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprites: Query<&mut Sprite, With<M8DisplaySprite>>,
+    mut dirty: MessageWriter<M8DirtyRegion>,
+) {
+    // Nothing arrives while decoding is paused, which isn't a stall.
+    if !commands.0.is_empty() || m8_decode_paused(paused) {
+        *last_command = time.elapsed();
+    }
+    let stalled = settings
+        .stall_timeout
+        .is_some_and(|timeout| time.elapsed() - *last_command > timeout);
+    // Without a connection the app pushes the commands, so only stalls count.
+    let away = connection
+        .as_ref()
+        .is_some_and(|connection| !connection.is_connected());
+    let disconnected = away || stalled;
+    if disconnected == *shown && !settings.is_changed() {
+        return;
+    }
+    *shown = disconnected;
+
+    if disconnected {
+        let atlas = images.get(&font.image).cloned();
+        if let Some(image) = images.get_mut(&display.display) {
+            if !settings.keep_frame {
+                *image = display_image(*dimensions);
+            }
+            if let Some(atlas) = atlas
+                && !settings.message.is_empty()
+            {
+                let background = display.background;
+                draw_message(
+                    image,
+                    *dimensions,
+                    &font,
+                    &atlas,
+                    &settings.message,
+                    background,
+                );
             }
+            dirty.write(M8DirtyRegion {
+                rect: dimensions.rect(),
+            });
+        }
+    } else if let Some(connection) = &connection
+        && (!settings.message.is_empty() || !settings.keep_frame)
+    {
+        let _ = connection.tx.send(vec![b'R']);
+    }
+
+    let tint = if disconnected && settings.dim {
+        DISCONNECTED_TINT
+    } else {
+        Color::WHITE
+    };
+    for mut sprite in sprites.iter_mut() {
+        sprite.color = tint;
+    }
+}
+
+/// Clears the display to the background once the connected M8 has sent
+/// nothing for the stream timeout, asking it to redraw once it is back.
+/// While it is disconnected the [M8DisconnectedDisplay] decides instead.
+#[allow(clippy::too_many_arguments)]
+fn clear_lost_stream(
+    time: Res<Time>,
+    timeout: Res<M8StreamTimeout>,
+    connection: Option<Res<M8Connection>>,
+    commands: Res<M8Commands>,
+    paused: Option<Res<M8DecodePaused>>,
+    mut last_command: Local<Option<Duration>>,
+    mut lost: Local<bool>,
+    display: Res<M8Display>,
+    dimensions: Res<M8DisplayDimensions>,
+    mut images: ResMut<Assets<Image>>,
+    mut dirty: MessageWriter<M8DirtyRegion>,
+    mut stream_lost: MessageWriter<M8StreamLost>,
+) {
+    let now = time.elapsed();
+    let away = connection
+        .as_ref()
+        .is_some_and(|connection| !connection.is_connected());
+    if !commands.0.is_empty() || away || m8_decode_paused(paused) {
+        if *lost
+            && !away
+            && let Some(connection) = &connection
+        {
+            let _ = connection.tx.send(vec![b'R']);
+        }
+        *last_command = Some(now);
+        *lost = false;
+        return;
+    }
+
+    let silent_for = now - *last_command.get_or_insert(now);
+    if *lost || silent_for <= timeout.0 {
+        return;
+    }
+    *lost = true;
+
+    if let Some(image) = images.get_mut(&display.display) {
+        draw_rectangle(
+            image,
+            *dimensions,
+            Position::new(0, 0),
+            Size::new(dimensions.width as u16, dimensions.height as u16),
+            display.background,
+        );
+        dirty.write(M8DirtyRegion {
+            rect: dimensions.rect(),
+        });
+    }
+    stream_lost.write(M8StreamLost { silent_for });
+}
+
+/// Fills a rectangle, blending translucent colours over the pixels
+/// already drawn so the display itself stays opaque.
+fn draw_rectangle(
+    display: &mut Image,
+    dimensions: M8DisplayDimensions,
+    pos: Position,
+    size: Size,
+    colour: Color,
+) {
+    let Some(area) = dimensions.clip(pos, size) else {
+        return;
+    };
+
+    let alpha = colour.alpha();
+    let opaque = colour.with_alpha(1.0);
+    for y in area.min.y..area.max.y {
+        for x in area.min.x..area.max.x {
+            let colour = if alpha >= 1.0 {
+                opaque
+            } else {
+                let below = display.get_color_at(x, y).unwrap();
+                below.mix(&opaque, alpha)
+            };
+            display.set_color_at(x, y, colour).unwrap();
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_character(
     display: &mut Image,
-    font: &Image,
+    dimensions: M8DisplayDimensions,
+    font: &M8Font,
+    atlas: &Image,
     c: u8,
     pos: Position,
     foreground: Color,
     background: Color,
 ) {
-    const GLYPH_WIDTH: u32 = 5;
-    const GLYPH_HEIGHT: u32 = 7;
-    const TEXT_OFFSET_Y: u16 = 3;
     if c == 32 {
         draw_rectangle(
             display,
-            pos.add(u16vec2(0, TEXT_OFFSET_Y)),
-            U16Vec2::new(GLYPH_WIDTH as u16, GLYPH_HEIGHT as u16),
+            dimensions,
+            pos.saturating_add(u16vec2(0, font.text_offset_y)),
+            U16Vec2::new(font.glyph_width as u16, font.glyph_height as u16),
             background,
         );
         return;
     }
 
-    let id = c.saturating_sub(33) as u32;
-    let src_x_start = id * GLYPH_WIDTH;
+    let glyph = font.glyph_rect(c);
 
-    for y in 0..GLYPH_HEIGHT {
-        for x in 0..GLYPH_WIDTH {
-            let is_on = font
-                .get_color_at(src_x_start + x, y)
+    for y in 0..glyph.height() {
+        for x in 0..glyph.width() {
+            let is_on = atlas
+                .get_color_at(glyph.min.x + x, glyph.min.y + y)
                 .map(|p| p.luminance() > 0.5)
                 .unwrap_or(false);
 
             let final_colour = if is_on { foreground } else { background };
 
             let dx = pos.x as u32 + x;
-            let dy = pos.y as u32 + y + TEXT_OFFSET_Y as u32;
+            let dy = pos.y as u32 + y + font.text_offset_y as u32;
 
-            if dx < DISPLAY_WIDTH && dy < DISPLAY_HEIGHT {
+            if dx < dimensions.width && dy < dimensions.height {
                 if is_on {
                     display.set_color_at(dx, dy, final_colour).unwrap();
                 } else if foreground != background {
@@ -127,161 +536,482 @@ fn draw_character(
     }
 }
 
-fn draw_waveform(display: &mut Image, colour: Color, waveform: Vec<u8>, background: Color) {
-    const WAVEFORM_MAX_HEIGHT: u32 = 16;
-    let start_x = 0;
+/// How the oscilloscope waveform is drawn.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub enum M8WaveformMode {
+    /// One pixel per sample, as the M8 draws it.
+    #[default]
+    Raw,
+    /// Successive samples are connected by lines.
+    Lines,
+    /// Samples are filled in from the middle of the oscilloscope.
+    Filled,
+}
 
-    for x in start_x..DISPLAY_WIDTH {
-        for y in 0..=WAVEFORM_MAX_HEIGHT {
-            display.set_color_at(x, y, background).unwrap();
-        }
+/// The part of the screen the oscilloscope is drawn in. It is cleared to the
+/// background before every waveform, so nothing of a taller previous trace
+/// is left behind. Its width follows the screen when a model with a
+/// different screen size is detected.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8ScopeRegion(pub URect);
+
+impl Default for M8ScopeRegion {
+    fn default() -> Self {
+        Self(URect::new(0, 0, DISPLAY_WIDTH, DEFAULT_SCOPE_HEIGHT))
     }
+}
 
-    let draw_start_x = 0;
-    for (i, &val) in waveform.iter().enumerate() {
-        let clamped_y = (val as u32).min(WAVEFORM_MAX_HEIGHT);
-        let x = draw_start_x + i as u32;
+/// Keeps the last waveforms to draw behind the current one, each older
+/// one fading further into the background. Off while `length` is 0.
+#[derive(Resource, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct M8WaveformHistory {
+    /// How many previous waveforms are drawn.
+    pub length: usize,
+    /// How much of its colour a waveform keeps each time it gets older,
+    /// between 0 and 1.
+    pub fade: f32,
+    /// The previous waveforms and their colours, newest last.
+    #[reflect(ignore)]
+    frames: VecDeque<(Color, Vec<u8>)>,
+}
 
-        if x < DISPLAY_WIDTH {
-            display.set_color_at(x, clamped_y, colour).unwrap();
+impl Default for M8WaveformHistory {
+    fn default() -> Self {
+        Self {
+            length: 0,
+            fade: DEFAULT_WAVEFORM_FADE,
+            frames: VecDeque::new(),
         }
     }
 }
 
-fn render(
-    connection: Res<M8Connection>,
-    mut display: ResMut<M8Display>,
-    m8_assets: Res<M8Assets>,
-    mut images: ResMut<Assets<Image>>,
-) {
-    let images_ptr: *mut Assets<Image> = &mut *images;
-    unsafe {
-        let display_image = (*images_ptr).get_mut(&display.display);
-        let font = (*images_ptr).get(&m8_assets.font_small);
-
-        if let (Some(display_image), Some(font)) = (display_image, font) {
-            while let Ok(cmd) = connection.rx.try_recv() {
-                match cmd {
-                    M8Command::DrawRectangle { pos, size, colour } => {
-                        if pos.x == 0
-                            && pos.y <= 0
-                            && size.x == DISPLAY_WIDTH as u16
-                            && size.y >= DISPLAY_HEIGHT as u16
-                        {
-                            display.background = colour;
-                        }
+impl M8WaveformHistory {
+    /// Keeps the given number of previous waveforms, fading by `fade` as
+    /// they get older.
+    pub fn new(length: usize, fade: f32) -> Self {
+        Self {
+            length,
+            fade,
+            ..default()
+        }
+    }
 
-                        draw_rectangle(display_image, pos, size, colour);
-                    }
-                    M8Command::DrawCharacter {
-                        c,
-                        pos,
-                        foreground,
-                        background,
-                    } => {
-                        draw_character(display_image, font, c, pos, foreground, background);
-                    }
-                    M8Command::DrawOscilloscopeWaveform { colour, waveform } => {
-                        draw_waveform(display_image, colour, waveform, display.background);
-                    }
-                    M8Command::SystemInfo {
-                        hardware_type,
-                        major,
-                        minor,
-                        patch,
-                        font_mode,
-                    } => (),
-                }
+    fn push(&mut self, colour: Color, waveform: &[u8]) {
+        // The oldest waveform's buffer takes the new one, so a full history
+        // doesn't allocate every frame.
+        let mut samples = Vec::new();
+        while self.frames.len() >= self.length.max(1) {
+            if let Some((_, oldest)) = self.frames.pop_front() {
+                samples = oldest;
             }
         }
+        if self.length > 0 {
+            samples.clear();
+            samples.extend_from_slice(waveform);
+            self.frames.push_back((colour, samples));
+        }
     }
 }
 
-pub const M8_EDIT: u8 = 1 << 0;
-pub const M8_OPTION: u8 = 1 << 1;
-pub const M8_RIGHT: u8 = 1 << 2;
-pub const M8_START: u8 = 1 << 3;
-pub const M8_SELECT: u8 = 1 << 4;
-pub const M8_DOWN: u8 = 1 << 5;
-pub const M8_UP: u8 = 1 << 6;
-pub const M8_LEFT: u8 = 1 << 7;
-pub const M8_KEY_COUNT: usize = 8;
-
-fn input(
-    keys: Res<ButtonInput<KeyCode>>,
-    key_map: Res<M8KeyMap>,
-    connection: Res<M8Connection>,
-    mut prev_mask: Local<u8>,
+#[allow(clippy::too_many_arguments)]
+fn draw_waveform(
+    display: &mut Image,
+    dimensions: M8DisplayDimensions,
+    scope: URect,
+    colour: Color,
+    waveform: &[u8],
+    background: Color,
+    mode: M8WaveformMode,
+    history: &M8WaveformHistory,
 ) {
-    if keys.just_pressed(KeyCode::KeyE) {
-        info!("Sending Enable");
-        let _ = connection.tx.send(vec![b'E']);
+    let bounds = scope.intersect(URect::from_corners(UVec2::ZERO, dimensions.size()));
+    if bounds.is_empty() {
+        return;
     }
 
-    if keys.just_pressed(KeyCode::KeyR) {
-        info!("Sending Reset");
-        let _ = connection.tx.send(vec![b'R']);
+    for x in bounds.min.x..bounds.max.x {
+        for y in bounds.min.y..bounds.max.y {
+            display.set_color_at(x, y, background).unwrap();
+        }
+    }
+
+    let mut strength = 1.0;
+    for (old_colour, old_waveform) in history.frames.iter().rev() {
+        strength *= history.fade.clamp(0.0, 1.0);
+        let faded = background.mix(old_colour, strength);
+        draw_trace(display, scope, bounds, faded, old_waveform, mode);
     }
+    draw_trace(display, scope, bounds, colour, waveform, mode);
+}
 
-    let mut keycodes = Vec::with_capacity(M8_KEY_COUNT);
+/// Draws one waveform over the oscilloscope, leaving out the pixels outside
+/// `bounds`.
+fn draw_trace(
+    display: &mut Image,
+    scope: URect,
+    bounds: URect,
+    colour: Color,
+    waveform: &[u8],
+    mode: M8WaveformMode,
+) {
+    let origin = scope.min.as_ivec2();
+    let middle = origin.y + (scope.height() / 2) as i32;
+    let mut previous: Option<IVec2> = None;
+    for (x, y) in waveform_points(waveform, scope.height()) {
+        let point = origin + IVec2::new(x as i32, y as i32);
+        if point.x >= bounds.max.x as i32 {
+            break;
+        }
 
-    if keys.pressed(key_map.edit_keycode()) {
-        keycodes.push(key_map.edit_keycode());
+        match mode {
+            M8WaveformMode::Raw => {
+                draw_vertical_span(display, point.x, point.y, point.y, bounds, colour)
+            }
+            M8WaveformMode::Lines => {
+                draw_line(display, previous.unwrap_or(point), point, bounds, colour)
+            }
+            M8WaveformMode::Filled => {
+                draw_vertical_span(display, point.x, middle, point.y, bounds, colour)
+            }
+        }
+        previous = Some(point);
     }
+}
 
-    if keys.pressed(key_map.option_keycode()) {
-        keycodes.push(key_map.option_keycode());
+/// What the M8's commands are drawn with, shared by the M8's display and
+/// those of any `M8Device`.
+pub(crate) struct M8DrawStyle<'a> {
+    pub font: &'a M8Font,
+    pub atlas: &'a Image,
+    pub theme: Option<&'a M8Theme>,
+    pub colour_space: M8ColorSpace,
+    pub waveform_mode: M8WaveformMode,
+    pub scope: URect,
+}
+
+impl M8DrawStyle<'_> {
+    fn remap(&self, colour: Color) -> Color {
+        self.theme
+            .and_then(|theme| theme.replacement(colour))
+            .unwrap_or_else(|| self.colour_space.interpret(colour))
     }
 
-    if keys.pressed(key_map.right_keycode()) {
-        keycodes.push(key_map.right_keycode());
+    /// The area of the display the command draws over, before clipping.
+    fn bounds(&self, command: &M8Command) -> Option<URect> {
+        match *command {
+            M8Command::DrawRectangle { pos, size, .. } => {
+                let min = pos.as_uvec2();
+                Some(URect::from_corners(min, min + size.as_uvec2()))
+            }
+            M8Command::DrawCharacter { pos, .. } => {
+                let min = pos.as_uvec2() + UVec2::new(0, self.font.text_offset_y as u32);
+                let size = UVec2::new(self.font.glyph_width, self.font.glyph_height);
+                Some(URect::from_corners(min, min + size))
+            }
+            M8Command::DrawOscilloscopeWaveform { .. } => Some(self.scope),
+            M8Command::KeyPressState { .. } | M8Command::SystemInfo { .. } => None,
+        }
     }
 
-    if keys.pressed(key_map.left_keycode()) {
-        keycodes.push(key_map.left_keycode());
+    /// Draws the commands into the display, keeping track of the background
+    /// the M8 clears its screen to. Returns the area of the display drawn
+    /// over, None if nothing visible was drawn.
+    pub(crate) fn draw(
+        &self,
+        display: &mut Image,
+        background: &mut Color,
+        dimensions: M8DisplayDimensions,
+        commands: &[M8Command],
+        waveform_history: &mut M8WaveformHistory,
+    ) -> Option<URect> {
+        let screen = dimensions.rect();
+        let mut dirty: Option<URect> = None;
+        for cmd in commands {
+            if let Some(bounds) = self.bounds(cmd).map(|bounds| bounds.intersect(screen))
+                && !bounds.is_empty()
+            {
+                dirty = Some(dirty.map_or(bounds, |dirty| dirty.union(bounds)));
+            }
+
+            match *cmd {
+                M8Command::DrawRectangle { pos, size, colour } => {
+                    let colour = self.remap(colour);
+                    // A translucent rectangle tints the screen rather
+                    // than clearing it, so it isn't the background.
+                    if dimensions.is_covered_by(pos, size) && colour.alpha() >= 1.0 {
+                        *background = colour;
+                    }
+
+                    draw_rectangle(display, dimensions, pos, size, colour);
+                }
+                M8Command::DrawCharacter {
+                    c,
+                    pos,
+                    foreground,
+                    background,
+                } => {
+                    let (foreground, background) = (self.remap(foreground), self.remap(background));
+                    draw_character(
+                        display, dimensions, self.font, self.atlas, c, pos, foreground, background,
+                    );
+                }
+                M8Command::DrawOscilloscopeWaveform {
+                    colour,
+                    ref waveform,
+                } => {
+                    let colour = self.remap(colour);
+                    draw_waveform(
+                        display,
+                        dimensions,
+                        self.scope,
+                        colour,
+                        waveform,
+                        *background,
+                        self.waveform_mode,
+                        waveform_history,
+                    );
+                    waveform_history.push(colour, waveform);
+                }
+                M8Command::KeyPressState { .. } | M8Command::SystemInfo { .. } => (),
+            }
+        }
+        dirty
     }
+}
 
-    if keys.pressed(key_map.up_keycode()) {
-        keycodes.push(key_map.up_keycode());
+#[allow(clippy::too_many_arguments)]
+fn render(
+    commands: Res<M8Commands>,
+    mut display: ResMut<M8Display>,
+    mut stats: ResMut<M8DisplayStats>,
+    dimensions: Res<M8DisplayDimensions>,
+    waveform_mode: Res<M8WaveformMode>,
+    scope: Res<M8ScopeRegion>,
+    mut waveform_history: ResMut<M8WaveformHistory>,
+    theme: Option<Res<M8Theme>>,
+    colour_space: Res<M8ColorSpace>,
+    font: Res<M8Font>,
+    mut images: ResMut<Assets<Image>>,
+    mut dirty: MessageWriter<M8DirtyRegion>,
+) {
+    let images_ptr: *mut Assets<Image> = &mut *images;
+    unsafe {
+        // Only borrowing the display mutably marks it for upload to the GPU.
+        let display_image = (*images_ptr).get_mut(&display.display);
+        let atlas = (*images_ptr).get(&font.image);
+
+        if let (Some(display_image), Some(atlas)) = (display_image, atlas) {
+            let style = M8DrawStyle {
+                font: &font,
+                atlas,
+                theme: theme.as_deref(),
+                colour_space: *colour_space,
+                waveform_mode: *waveform_mode,
+                scope: scope.0,
+            };
+            stats.frames_rendered += 1;
+            if let Some(rect) = style.draw(
+                display_image,
+                &mut display.background,
+                *dimensions,
+                &commands.0,
+                &mut waveform_history,
+            ) {
+                dirty.write(M8DirtyRegion { rect });
+            }
+        }
     }
+}
 
-    if keys.pressed(key_map.down_keycode()) {
-        keycodes.push(key_map.down_keycode());
+fn capture_frame(
+    time: Res<Time>,
+    mut sink: ResMut<M8FrameSink>,
+    display: Res<M8Display>,
+    images: Res<Assets<Image>>,
+) {
+    if !sink.timer.tick(time.delta()).just_finished() {
+        return;
     }
 
-    if keys.pressed(key_map.select_keycode()) {
-        keycodes.push(key_map.select_keycode());
+    if let Some(image) = images.get(&display.display)
+        && let Some(pixels) = image.data.as_deref()
+    {
+        (sink.callback)(pixels, image.width(), image.height());
     }
+}
+
+fn skip_render(mut stats: ResMut<M8DisplayStats>) {
+    stats.frames_skipped += 1;
+}
 
-    if keys.pressed(key_map.start_keycode()) {
-        keycodes.push(key_map.start_keycode());
+/// Asks the M8 to stream its display again when the primary window regains
+/// focus.
+fn enable_on_focus(
+    mut focused: MessageReader<WindowFocused>,
+    primary: Query<(), With<PrimaryWindow>>,
+    connection: Res<M8Connection>,
+) {
+    let regained = focused
+        .read()
+        .any(|event| event.focused && primary.contains(event.window));
+    if regained {
+        info!("Window focused, enabling the M8 again");
+        connection.request_enable();
     }
+}
 
-    let mask = keycode_to_mask(keycodes, &key_map);
+/// This plugin presents the M8's screen in a window and sends the
+/// keyboard to the M8. It brings in bevy's `DefaultPlugins`.
+pub struct M8DisplayPlugin {
+    /// The initial size of the window.
+    pub resolution: UVec2,
+    /// Runs without a window or renderer, still decoding into the display image.
+    pub headless: bool,
+    /// How the colours sent by the M8 are interpreted.
+    pub colour_space: M8ColorSpace,
+    /// How frames are presented to the window. `Immediate` and `Mailbox`
+    /// show the M8's screen with less latency, vsync saves power.
+    pub present_mode: PresentMode,
+    /// Fills the monitor with a borderless window instead of opening one of
+    /// `resolution`.
+    pub fullscreen: bool,
+    /// The monitor the window is centred on, or filled when fullscreen.
+    pub monitor: MonitorSelection,
+    /// Sends the enable command again whenever the window regains focus,
+    /// for M8s which stop streaming after the computer sleeps.
+    pub enable_on_focus: bool,
+    /// Draws nothing until the M8 has identified itself, see
+    /// [M8HandshakeState](crate::M8HandshakeState), rather than guessing
+    /// its model for the first frames. The frame it identifies itself in
+    /// is drawn.
+    pub wait_for_handshake: bool,
+    /// Clears the display to the background and sends [M8StreamLost] once
+    /// the connected M8 has sent nothing for this long, as when its firmware
+    /// crashed, instead of leaving the last frame up.
+    pub stream_timeout: Option<Duration>,
+}
 
-    if mask != *prev_mask {
-        info!("Sending mask: {:?}", mask);
-        let _ = connection.tx.send(vec![b'C', mask]);
-        *prev_mask = mask;
+impl Default for M8DisplayPlugin {
+    fn default() -> Self {
+        Self {
+            resolution: UVec2::new(DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            headless: false,
+            colour_space: M8ColorSpace::default(),
+            present_mode: PresentMode::AutoVsync,
+            fullscreen: false,
+            monitor: MonitorSelection::Primary,
+            enable_on_focus: false,
+            wait_for_handshake: false,
+            stream_timeout: None,
+        }
     }
 }
 
-pub struct M8DisplayPlugin;
 impl Plugin for M8DisplayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                present_mode: bevy::window::PresentMode::AutoVsync,
-                //mode: bevy::window::WindowMode::BorderlessFullscreen(MonitorSelection::Primary),
-                resolution: WindowResolution::new(DISPLAY_WIDTH, DISPLAY_HEIGHT),
-                title: TITLE.into(),
+        configure_update_systems(app);
+        if self.headless {
+            app.add_plugins(
+                DefaultPlugins
+                    .set(WindowPlugin {
+                        primary_window: None,
+                        exit_condition: ExitCondition::DontExit,
+                        ..default()
+                    })
+                    .set(RenderPlugin {
+                        render_creation: WgpuSettings {
+                            backends: None,
+                            ..default()
+                        }
+                        .into(),
+                        ..default()
+                    })
+                    .disable::<WinitPlugin>()
+                    // Bevy's plugins only bring a schedule runner when built
+                    // without a window, it takes over from winit here.
+                    .add(ScheduleRunnerPlugin::run_loop(HEADLESS_FRAME_TIME)),
+            );
+        } else {
+            app.add_plugins(DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    present_mode: self.present_mode,
+                    mode: if self.fullscreen {
+                        WindowMode::BorderlessFullscreen(self.monitor)
+                    } else {
+                        WindowMode::Windowed
+                    },
+                    position: WindowPosition::Centered(self.monitor),
+                    resolution: WindowResolution::new(self.resolution.x, self.resolution.y),
+                    title: TITLE.into(),
+                    ..default()
+                }),
                 ..default()
-            }),
-            ..default()
-        }));
+            }));
+        }
 
+        app.add_message::<M8DeviceInfoChanged>();
+        app.add_message::<M8ModelDetected>();
+        app.add_message::<M8DirtyRegion>();
+        app.add_message::<M8StreamLost>();
+        app.init_resource::<M8DisconnectedDisplay>();
+        app.register_type::<M8DisconnectedDisplay>();
+        app.init_resource::<M8DisplayStats>();
+        app.init_resource::<M8WaveformMode>();
+        app.insert_resource(self.colour_space);
+        app.init_resource::<M8WaveformHistory>();
+        app.init_resource::<M8ScopeRegion>();
+        app.register_type::<M8DisplayStats>();
+        app.register_type::<M8DisplayDimensions>();
+        app.register_type::<M8WaveformMode>();
+        app.register_type::<M8ColorSpace>();
+        app.register_type::<M8WaveformHistory>();
+        app.register_type::<M8ScopeRegion>();
         app.add_systems(Startup, setup_display);
-        app.add_systems(Update, render.run_if(in_state(M8LoadingState::Running)));
-        app.add_systems(Update, input.run_if(in_state(M8LoadingState::Running)));
+        let render_systems = (
+            resize_display,
+            render.run_if(m8_screen_changed),
+            skip_render.run_if(not(m8_screen_changed)),
+            capture_frame.run_if(resource_exists::<M8FrameSink>),
+            present_pixels.run_if(resource_exists::<M8PixelOutput>),
+        )
+            .chain()
+            .in_set(M8UpdateSystems::DisplayRender)
+            .run_if(in_state(M8LoadingState::Running));
+        if self.wait_for_handshake {
+            // The device info is inserted before rendering, unlike the
+            // handshake state, so the redraw sent along with the M8's
+            // SystemInfo is drawn rather than lost.
+            app.add_systems(
+                Update,
+                render_systems.run_if(resource_exists::<M8DeviceInfo>),
+            );
+        } else {
+            app.add_systems(Update, render_systems);
+        }
+        if self.enable_on_focus {
+            app.add_systems(
+                Update,
+                enable_on_focus.run_if(resource_exists::<M8Connection>),
+            );
+        }
+        if let Some(timeout) = self.stream_timeout {
+            app.insert_resource(M8StreamTimeout(timeout));
+            app.add_systems(
+                Update,
+                clear_lost_stream
+                    .after(M8UpdateSystems::DisplayRender)
+                    .before(show_connection_state)
+                    .run_if(in_state(M8LoadingState::Running)),
+            );
+        }
+        app.add_systems(
+            Update,
+            show_connection_state
+                .after(M8UpdateSystems::DisplayRender)
+                .run_if(in_state(M8LoadingState::Running)),
+        );
     }
 }
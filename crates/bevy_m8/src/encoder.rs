@@ -0,0 +1,82 @@
+//! This file provides encoding of M8 commands into SLIP frames.
+
+use crate::color::rgb_from_color;
+use crate::decoder::{
+    DRAW_CHARACTER_COMMAND, DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND, DRAW_RECTANGLE_COMMAND,
+    KEY_PRESS_STATE_COMMAND, M8Command, SLIP_END, SLIP_ESC, SLIP_ESC_END, SLIP_ESC_ESC,
+    SYSTEM_INFO_COMMAND,
+};
+
+/// Encodes a command into the payload the M8 would send for it.
+pub fn encode_command(command: &M8Command) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match command {
+        M8Command::DrawRectangle { pos, size, colour } => {
+            buf.push(DRAW_RECTANGLE_COMMAND);
+            buf.extend_from_slice(&pos.x.to_le_bytes());
+            buf.extend_from_slice(&pos.y.to_le_bytes());
+            buf.extend_from_slice(&size.x.to_le_bytes());
+            buf.extend_from_slice(&size.y.to_le_bytes());
+            buf.extend_from_slice(&rgb_from_color(*colour));
+        }
+        M8Command::DrawCharacter {
+            c,
+            pos,
+            foreground,
+            background,
+        } => {
+            buf.push(DRAW_CHARACTER_COMMAND);
+            buf.push(*c);
+            buf.extend_from_slice(&pos.x.to_le_bytes());
+            buf.extend_from_slice(&pos.y.to_le_bytes());
+            buf.extend_from_slice(&rgb_from_color(*foreground));
+            buf.extend_from_slice(&rgb_from_color(*background));
+        }
+        M8Command::DrawOscilloscopeWaveform { colour, waveform } => {
+            buf.push(DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND);
+            buf.extend_from_slice(&rgb_from_color(*colour));
+            buf.extend_from_slice(waveform);
+        }
+        M8Command::KeyPressState { keys } => {
+            buf.extend_from_slice(&[KEY_PRESS_STATE_COMMAND, *keys]);
+        }
+        M8Command::SystemInfo {
+            hardware_type,
+            major,
+            minor,
+            patch,
+            font_mode,
+        } => {
+            buf.extend_from_slice(&[
+                SYSTEM_INFO_COMMAND,
+                *hardware_type,
+                *major,
+                *minor,
+                *patch,
+                *font_mode,
+            ]);
+        }
+    }
+    buf
+}
+
+/// Appends `payload` to `out` as a SLIP frame.
+pub fn slip_encode(payload: &[u8], out: &mut Vec<u8>) {
+    for &byte in payload {
+        match byte {
+            SLIP_END => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+            _ => out.push(byte),
+        }
+    }
+    out.push(SLIP_END);
+}
+
+/// Encodes the commands into the byte stream the M8 would send for them.
+pub fn encode_stream(commands: &[M8Command]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for command in commands {
+        slip_encode(&encode_command(command), &mut out);
+    }
+    out
+}
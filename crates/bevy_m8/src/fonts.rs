@@ -0,0 +1,135 @@
+//! This file provides the fonts used to draw characters on the display.
+
+use bevy::prelude::*;
+
+use crate::{
+    M8LoadingState,
+    assets::{DEFAULT_FONT_PATH, M8Assets},
+};
+
+/// Width of a glyph cell in the stock M8 font.
+const SMALL_GLYPH_WIDTH: u32 = 5;
+
+/// Height of a glyph cell in the stock M8 font.
+const SMALL_GLYPH_HEIGHT: u32 = 7;
+
+/// The stock M8 font lays out every glyph on a single row.
+const SMALL_GLYPH_COLUMNS: u32 = 94;
+
+/// The first character present in the stock M8 font ('!').
+const SMALL_FIRST_CHAR: u8 = 33;
+
+/// Vertical offset applied to characters drawn with the stock M8 font.
+const SMALL_TEXT_OFFSET_Y: u16 = 3;
+
+/// A font atlas along with the layout of the glyphs within it.
+#[derive(Resource, Debug, Clone)]
+pub struct M8Font {
+    /// The atlas image containing every glyph.
+    pub image: Handle<Image>,
+    /// Vertical offset applied when drawing characters.
+    pub text_offset_y: u16,
+    /// Width of a single glyph cell in pixels.
+    pub glyph_width: u32,
+    /// Height of a single glyph cell in pixels.
+    pub glyph_height: u32,
+    /// Number of glyph cells per row of the atlas.
+    pub columns: u32,
+    /// The character stored in the first cell of the atlas.
+    pub first_char: u8,
+}
+
+impl M8Font {
+    /// Creates the layout of the stock M8 font for the given atlas.
+    pub fn small(image: Handle<Image>) -> Self {
+        Self {
+            image,
+            text_offset_y: SMALL_TEXT_OFFSET_Y,
+            glyph_width: SMALL_GLYPH_WIDTH,
+            glyph_height: SMALL_GLYPH_HEIGHT,
+            columns: SMALL_GLYPH_COLUMNS,
+            first_char: SMALL_FIRST_CHAR,
+        }
+    }
+
+    /// Returns the area of the atlas that holds the glyph for `c`.
+    pub fn glyph_rect(&self, c: u8) -> URect {
+        let index = c.saturating_sub(self.first_char) as u32;
+        let x = (index % self.columns) * self.glyph_width;
+        let y = (index / self.columns) * self.glyph_height;
+        URect::new(x, y, x + self.glyph_width, y + self.glyph_height)
+    }
+}
+
+/// The font settings chosen when adding the [M8FontsPlugin].
+#[derive(Resource)]
+struct M8FontSettings {
+    font_path: String,
+    text_offset_y: Option<u16>,
+}
+
+fn load_fonts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    m8_assets: Res<M8Assets>,
+    settings: Res<M8FontSettings>,
+) {
+    // The stock font has already been loaded by the loading state.
+    let image = if settings.font_path == DEFAULT_FONT_PATH {
+        m8_assets.font_small.clone()
+    } else {
+        asset_server.load(settings.font_path.clone())
+    };
+
+    let mut font = M8Font::small(image);
+    if let Some(text_offset_y) = settings.text_offset_y {
+        font.text_offset_y = text_offset_y;
+    }
+    commands.insert_resource(font);
+}
+
+/// This plugin provides the fonts used by the display.
+pub struct M8FontsPlugin {
+    /// The asset path of the font atlas.
+    pub font_path: String,
+    /// Overrides the vertical offset of the font when set.
+    pub text_offset_y: Option<u16>,
+}
+
+impl Default for M8FontsPlugin {
+    fn default() -> Self {
+        Self {
+            font_path: DEFAULT_FONT_PATH.into(),
+            text_offset_y: None,
+        }
+    }
+}
+
+impl M8FontsPlugin {
+    /// Uses the font atlas at the given asset path instead of the stock font.
+    pub fn with_font_path(self, font_path: impl Into<String>) -> Self {
+        Self {
+            font_path: font_path.into(),
+            ..self
+        }
+    }
+
+    /// Draws characters this many pixels below their position instead of
+    /// the font's own offset, to line the glyphs up with a firmware's.
+    pub fn with_text_offset_y(self, text_offset_y: u16) -> Self {
+        Self {
+            text_offset_y: Some(text_offset_y),
+            ..self
+        }
+    }
+}
+
+impl Plugin for M8FontsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(M8FontSettings {
+            font_path: self.font_path.clone(),
+            text_offset_y: self.text_offset_y,
+        });
+        app.add_systems(OnEnter(M8LoadingState::Running), load_fonts);
+    }
+}
@@ -0,0 +1,58 @@
+//! This file provides rasterization primitives for the display framebuffer.
+
+use bevy::prelude::*;
+
+/// Sets a single pixel, ignoring pixels outside of `bounds`.
+#[inline]
+fn plot(display: &mut Image, x: i32, y: i32, bounds: URect, colour: Color) {
+    if x >= bounds.min.x as i32
+        && y >= bounds.min.y as i32
+        && x < bounds.max.x as i32
+        && y < bounds.max.y as i32
+    {
+        display.set_color_at(x as u32, y as u32, colour).ok();
+    }
+}
+
+/// Draws a line between two points (inclusive) using Bresenham's algorithm.
+/// Pixels outside of `bounds` are clipped.
+pub fn draw_line(display: &mut Image, from: IVec2, to: IVec2, bounds: URect, colour: Color) {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+
+    let mut error = dx + dy;
+    let (mut x, mut y) = (from.x, from.y);
+    loop {
+        plot(display, x, y, bounds, colour);
+        if x == to.x && y == to.y {
+            break;
+        }
+
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Draws a vertical span in column `x` between `y0` and `y1` (inclusive).
+/// Pixels outside of `bounds` are clipped.
+pub fn draw_vertical_span(
+    display: &mut Image,
+    x: i32,
+    y0: i32,
+    y1: i32,
+    bounds: URect,
+    colour: Color,
+) {
+    for y in y0.min(y1)..=y0.max(y1) {
+        plot(display, x, y, bounds, colour);
+    }
+}
@@ -0,0 +1,230 @@
+//! This file provides the M8's buttons and sends the buttons held on every
+//! input source to the M8.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+#[cfg(feature = "render")]
+use crate::piano::M8Piano;
+use crate::{
+    decoder::M8Command,
+    keymap::M8KeyMap,
+    macros::M8LiveInput,
+    serial::{M8Commands, M8Connection},
+    utils::keycode_to_mask,
+};
+
+/// How long a button is held before it starts repeating by default.
+const DEFAULT_KEY_REPEAT_DELAY: Duration = Duration::from_millis(400);
+
+/// The default time between two repeats of a held button.
+const DEFAULT_KEY_REPEAT_INTERVAL: Duration = Duration::from_millis(60);
+
+pub const M8_EDIT: u8 = 1 << 0;
+pub const M8_OPTION: u8 = 1 << 1;
+pub const M8_RIGHT: u8 = 1 << 2;
+pub const M8_START: u8 = 1 << 3;
+pub const M8_SELECT: u8 = 1 << 4;
+pub const M8_DOWN: u8 = 1 << 5;
+pub const M8_UP: u8 = 1 << 6;
+pub const M8_LEFT: u8 = 1 << 7;
+pub const M8_KEY_COUNT: usize = 8;
+
+/// A button on the M8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum M8Button {
+    Edit,
+    Option,
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Start,
+}
+
+impl M8Button {
+    /// Every button on the M8.
+    pub const ALL: [M8Button; M8_KEY_COUNT] = [
+        M8Button::Edit,
+        M8Button::Option,
+        M8Button::Up,
+        M8Button::Down,
+        M8Button::Left,
+        M8Button::Right,
+        M8Button::Select,
+        M8Button::Start,
+    ];
+
+    /// The bit of the button in the control byte.
+    pub fn bit(self) -> u8 {
+        match self {
+            M8Button::Edit => M8_EDIT,
+            M8Button::Option => M8_OPTION,
+            M8Button::Up => M8_UP,
+            M8Button::Down => M8_DOWN,
+            M8Button::Left => M8_LEFT,
+            M8Button::Right => M8_RIGHT,
+            M8Button::Select => M8_SELECT,
+            M8Button::Start => M8_START,
+        }
+    }
+
+    /// Combines the buttons into a control byte.
+    pub fn mask(buttons: &[M8Button]) -> u8 {
+        buttons.iter().fold(0, |mask, button| mask | button.bit())
+    }
+}
+
+/// The M8 buttons held this update, combined from every input source.
+///
+/// Sources add the bits of their held buttons to the mask in
+/// [M8UpdateSystems::Input]. It is cleared before that set runs, and once
+/// every source has run a single control byte is sent if the mask changed,
+/// so sources never send conflicting control bytes of their own.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8InputState {
+    /// The OR of the bits of every button held this update.
+    pub mask: u8,
+}
+
+/// The M8 buttons last sent, combined from every input source.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8KeyboardState {
+    /// The OR of the bits of every button held when the mask was last sent.
+    pub last_mask: u8,
+}
+
+/// The M8 buttons the device reports as held, from its key press state
+/// frames. Comparing it with [M8KeyboardState::last_mask] shows whether a
+/// control byte was dropped on the way to the M8.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8DeviceButtonState(pub u8);
+
+/// Repeats held arrow buttons, like the key repeat of an operating system.
+/// Remote `KeyHold` events go through the keyboard, so they repeat too.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8KeyRepeat {
+    /// Whether held arrow buttons are repeated.
+    pub enabled: bool,
+    /// How long a button is held before it starts repeating.
+    pub delay: Duration,
+    /// The time between two repeats.
+    pub interval: Duration,
+}
+
+impl Default for M8KeyRepeat {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay: DEFAULT_KEY_REPEAT_DELAY,
+            interval: DEFAULT_KEY_REPEAT_INTERVAL,
+        }
+    }
+}
+
+pub(crate) fn reset_input_state(mut input: ResMut<M8InputState>) {
+    input.mask = 0;
+}
+
+/// Adds the buttons held on the keyboard to the [M8InputState], and sends
+/// Enable and Reset on their keys.
+pub(crate) fn keyboard_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_map: Res<M8KeyMap>,
+    connection: Res<M8Connection>,
+    mut input: ResMut<M8InputState>,
+    #[cfg(feature = "render")] piano: Option<Res<M8Piano>>,
+) {
+    // Keys playing the piano aren't sent as buttons.
+    #[cfg(feature = "render")]
+    let claimed = |keycode: KeyCode| piano.as_ref().is_some_and(|piano| piano.claims(keycode));
+    #[cfg(not(feature = "render"))]
+    let claimed = |_: KeyCode| false;
+
+    if keys.just_pressed(KeyCode::KeyE)
+        && !key_map.is_bound(KeyCode::KeyE)
+        && !claimed(KeyCode::KeyE)
+    {
+        info!("Sending Enable");
+        let _ = connection.tx.send(vec![b'E']);
+    }
+
+    if keys.just_pressed(KeyCode::KeyR)
+        && !key_map.is_bound(KeyCode::KeyR)
+        && !claimed(KeyCode::KeyR)
+    {
+        info!("Sending Reset");
+        let _ = connection.tx.send(vec![b'R']);
+    }
+
+    // Keys which aren't bound to a button are ignored.
+    let keycodes = keys
+        .get_pressed()
+        .copied()
+        .filter(|&keycode| !claimed(keycode))
+        .collect();
+    input.mask |= keycode_to_mask(keycodes, &key_map);
+}
+
+/// Adds the buttons held through [M8Connection::send_buttons] to the
+/// [M8InputState].
+pub(crate) fn connection_input(connection: Res<M8Connection>, mut input: ResMut<M8InputState>) {
+    input.mask |= connection.held_buttons();
+}
+
+/// Sends the buttons of every input source as a single control byte, so
+/// chords reach the M8 together.
+pub(crate) fn send_input(
+    time: Res<Time>,
+    repeat: Res<M8KeyRepeat>,
+    mut next_repeat: Local<Duration>,
+    mut repeating: Local<bool>,
+    input: Res<M8InputState>,
+    mut live_input: MessageWriter<M8LiveInput>,
+    mut state: ResMut<M8KeyboardState>,
+) {
+    let mask = input.mask;
+    if mask != state.last_mask {
+        live_input.write(M8LiveInput(vec![b'C', mask]));
+        state.last_mask = mask;
+        if std::mem::take(&mut *repeating) {
+            *next_repeat = time.elapsed() + repeat.interval;
+        } else {
+            info!("Sending mask: {:?}", mask);
+            *next_repeat = time.elapsed() + repeat.delay;
+        }
+        return;
+    }
+
+    // Repeat by releasing the held arrows for an update and pressing them
+    // again in the next, so no update sends more than one control byte.
+    let arrows = mask & (M8_UP | M8_DOWN | M8_LEFT | M8_RIGHT);
+    if repeat.enabled && arrows != 0 && time.elapsed() >= *next_repeat {
+        debug!("Repeating mask: {:?}", mask);
+        let released = mask & !arrows;
+        live_input.write(M8LiveInput(vec![b'C', released]));
+        state.last_mask = released;
+        *repeating = true;
+    }
+}
+
+/// Updates the [M8DeviceButtonState] from the key press states decoded
+/// this frame.
+pub(crate) fn receive_button_state(
+    commands: Res<M8Commands>,
+    mut button_state: ResMut<M8DeviceButtonState>,
+) {
+    let last = commands.0.iter().rev().find_map(|command| match command {
+        &M8Command::KeyPressState { keys } => Some(keys),
+        _ => None,
+    });
+    if let Some(keys) = last {
+        button_state.set_if_neq(M8DeviceButtonState(keys));
+    }
+}
@@ -3,11 +3,23 @@
 use bevy::input::keyboard::KeyCode;
 use bevy::prelude::*;
 
+use crate::{
+    M8LoadingState, M8UpdateSystems,
+    input::{
+        M8Button, M8DeviceButtonState, M8InputState, M8KeyRepeat, M8KeyboardState,
+        connection_input, keyboard_input, receive_button_state, reset_input_state, send_input,
+    },
+    serial::m8_connected,
+};
+
 /// The Key map resource for defining
 /// the key bindings for interaction with
 /// the M8.
 #[allow(unused)]
-#[derive(Resource)]
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct M8KeyMap {
     edit: KeyCode,
     option: KeyCode,
@@ -67,6 +79,35 @@ impl M8KeyMap {
     pub fn start_keycode(&self) -> KeyCode {
         self.start
     }
+    /// Returns the key bound to the button.
+    pub fn keycode(&self, button: M8Button) -> KeyCode {
+        match button {
+            M8Button::Edit => self.edit,
+            M8Button::Option => self.option,
+            M8Button::Up => self.up,
+            M8Button::Down => self.down,
+            M8Button::Left => self.left,
+            M8Button::Right => self.right,
+            M8Button::Select => self.select,
+            M8Button::Start => self.start,
+        }
+    }
+
+    /// Returns true if the key is bound to one of the M8's buttons.
+    pub fn is_bound(&self, keycode: KeyCode) -> bool {
+        [
+            self.edit,
+            self.option,
+            self.right,
+            self.left,
+            self.up,
+            self.down,
+            self.select,
+            self.start,
+        ]
+        .contains(&keycode)
+    }
+
     pub fn with_edit_keycode(self, keycode: KeyCode) -> Self {
         Self {
             edit: keycode,
@@ -126,11 +167,44 @@ impl M8KeyMap {
 
 /// The Key Map plugin, providing a means
 /// of controlling the key bindings used
-/// in the app.
-pub struct M8KeyMapPlugin;
+/// in the app. It also sends the buttons held
+/// on the keyboard and other input sources.
+#[derive(Default)]
+pub struct M8KeyMapPlugin {
+    pub key_map: M8KeyMap,
+}
 
 impl Plugin for M8KeyMapPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(M8KeyMap::default());
+        app.insert_resource(self.key_map);
+        app.register_type::<M8KeyMap>();
+        app.init_resource::<M8InputState>();
+        app.init_resource::<M8KeyboardState>();
+        app.init_resource::<M8KeyRepeat>();
+        app.init_resource::<M8DeviceButtonState>();
+        app.register_type::<M8InputState>();
+        app.register_type::<M8KeyboardState>();
+        app.register_type::<M8KeyRepeat>();
+        app.register_type::<M8DeviceButtonState>();
+        app.add_systems(Update, reset_input_state.before(M8UpdateSystems::Input));
+        app.add_systems(
+            Update,
+            (keyboard_input, connection_input)
+                .in_set(M8UpdateSystems::Input)
+                .run_if(in_state(M8LoadingState::Running).and(m8_connected)),
+        );
+        app.add_systems(
+            Update,
+            send_input
+                .after(M8UpdateSystems::Input)
+                .before(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running).and(m8_connected)),
+        );
+        app.add_systems(
+            Update,
+            receive_button_state
+                .after(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running).and(m8_connected)),
+        );
     }
 }
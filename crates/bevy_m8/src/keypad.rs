@@ -0,0 +1,124 @@
+//! This file provides an on-screen keypad which can be pressed with the
+//! mouse or by touch, for setups without a keyboard.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    M8UpdateSystems,
+    display::{M8DisplayCamera, M8DisplaySprite},
+    input::{M8Button, M8InputState},
+};
+
+/// The size of a keypad button, in display pixels.
+const BUTTON_SIZE: Vec2 = Vec2::new(30.0, 30.0);
+
+/// Colour of a released keypad button.
+const RELEASED_COLOUR: Color = Color::srgba(1.0, 1.0, 1.0, 0.15);
+
+/// Colour of a pressed keypad button.
+const PRESSED_COLOUR: Color = Color::srgba(1.0, 1.0, 1.0, 0.4);
+
+/// Where each button sits relative to the centre of the display, laid out
+/// like the M8: the arrows on the left and the other buttons on the right.
+const LAYOUT: [(M8Button, Vec2); 8] = [
+    (M8Button::Up, Vec2::new(-110.0, -38.0)),
+    (M8Button::Down, Vec2::new(-110.0, -102.0)),
+    (M8Button::Left, Vec2::new(-142.0, -70.0)),
+    (M8Button::Right, Vec2::new(-78.0, -70.0)),
+    (M8Button::Select, Vec2::new(94.0, -54.0)),
+    (M8Button::Start, Vec2::new(126.0, -54.0)),
+    (M8Button::Option, Vec2::new(94.0, -86.0)),
+    (M8Button::Edit, Vec2::new(126.0, -86.0)),
+];
+
+/// The buttons held on the on-screen keypad. They are added to the
+/// [M8InputState] along with the other input sources.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8Keypad {
+    pub mask: u8,
+}
+
+/// A button of the on-screen keypad.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct M8KeypadButton(pub M8Button);
+
+fn spawn_keypad(mut commands: Commands, displays: Query<Entity, Added<M8DisplaySprite>>) {
+    for display in displays.iter() {
+        commands.entity(display).with_children(|parent| {
+            for (button, position) in LAYOUT {
+                parent.spawn((
+                    M8KeypadButton(button),
+                    Sprite {
+                        color: RELEASED_COLOUR,
+                        custom_size: Some(BUTTON_SIZE),
+                        ..default()
+                    },
+                    Transform::from_translation(position.extend(1.0)),
+                ));
+            }
+        });
+    }
+}
+
+fn keypad_input(
+    mouse: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<M8DisplayCamera>>,
+    mut buttons: Query<(&M8KeypadButton, &GlobalTransform, &mut Sprite)>,
+    mut keypad: ResMut<M8Keypad>,
+    mut input: ResMut<M8InputState>,
+) {
+    let mut pointers: Vec<Vec2> = touches.iter().map(|touch| touch.position()).collect();
+    if mouse.pressed(MouseButton::Left)
+        && let Ok(window) = windows.single()
+        && let Some(cursor) = window.cursor_position()
+    {
+        pointers.push(cursor);
+    }
+
+    let Ok((camera, camera_transform)) = cameras.single() else {
+        return;
+    };
+    let points: Vec<Vec2> = pointers
+        .into_iter()
+        .filter_map(|pointer| camera.viewport_to_world_2d(camera_transform, pointer).ok())
+        .collect();
+
+    let mut mask = 0;
+    for (&M8KeypadButton(button), transform, mut sprite) in buttons.iter_mut() {
+        // Hit test in the button's own space, so rotating the display works.
+        let inverse = transform.affine().inverse();
+        let pressed = points.iter().any(|point| {
+            let local = inverse.transform_point3(point.extend(0.0)).truncate();
+            local.abs().cmple(BUTTON_SIZE / 2.0).all()
+        });
+
+        if pressed {
+            mask |= button.bit();
+        }
+        sprite.color = if pressed {
+            PRESSED_COLOUR
+        } else {
+            RELEASED_COLOUR
+        };
+    }
+
+    keypad.set_if_neq(M8Keypad { mask });
+    input.mask |= mask;
+}
+
+/// This plugin shows an on-screen keypad for the M8's buttons.
+pub struct M8KeypadPlugin;
+
+impl Plugin for M8KeypadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<M8Keypad>();
+        app.register_type::<M8Keypad>();
+        app.add_systems(
+            Update,
+            (spawn_keypad, keypad_input.in_set(M8UpdateSystems::Input)).chain(),
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! This file provides pixel outputs, which push the drawn display to
+//! hardware such as an RGB LED matrix.
+
+use std::{
+    io::Write,
+    sync::{Mutex, PoisonError},
+};
+
+use bevy::prelude::*;
+
+use crate::display::{M8DirtyRegion, M8Display};
+
+/// The bytes of each pixel of the display.
+const RGBA: usize = 4;
+
+/// The magic word starting every Adalight frame.
+const ADALIGHT_MAGIC: &[u8; 3] = b"Ada";
+
+/// The most LEDs an Adalight frame can address.
+const ADALIGHT_MAX_LEDS: u32 = 1 << 16;
+
+/// Receives the display's pixels whenever part of it was drawn.
+pub trait M8PixelSink: Send + Sync + 'static {
+    /// Presents the RGBA pixels, `width` by `height` in rows from the top.
+    fn present(&mut self, rgba: &[u8], width: u32, height: u32);
+}
+
+/// Presents the display to an [M8PixelSink] after it has been drawn, only
+/// in frames where the [M8DirtyRegion] touches what is presented. Nothing
+/// is presented unless this is inserted.
+#[derive(Resource)]
+pub struct M8PixelOutput {
+    sink: Box<dyn M8PixelSink>,
+    region: Option<URect>,
+    pixels: Vec<u8>,
+}
+
+impl M8PixelOutput {
+    /// Presents the whole display to the sink.
+    pub fn new(sink: impl M8PixelSink) -> Self {
+        Self {
+            sink: Box::new(sink),
+            region: None,
+            pixels: Vec::new(),
+        }
+    }
+
+    /// Only presents the given area of the display, e.g. the oscilloscope.
+    pub fn with_region(self, region: URect) -> Self {
+        Self {
+            region: Some(region),
+            ..self
+        }
+    }
+}
+
+/// Drives an RGB LED matrix over a serial port, SPI or any other writer,
+/// using the Adalight protocol most LED controller firmwares understand.
+///
+/// The pixels are scaled down by averaging the ones each LED covers, and
+/// written in rows from the top, with every other row reversed when the
+/// matrix is wired in a serpentine.
+pub struct M8LedMatrix<W> {
+    /// Behind a mutex so writers which aren't `Sync`, like serial ports,
+    /// can be used.
+    writer: Mutex<W>,
+    size: UVec2,
+    serpentine: bool,
+    frame: Vec<u8>,
+    failed: bool,
+}
+
+impl<W: Write + Send + 'static> M8LedMatrix<W> {
+    /// Creates a matrix of `width` by `height` LEDs written to by `writer`,
+    /// such as an opened serial port.
+    pub fn new(writer: W, width: u32, height: u32) -> Self {
+        let size = UVec2::new(width.max(1), height.max(1));
+        assert!(
+            size.element_product() <= ADALIGHT_MAX_LEDS,
+            "Adalight addresses at most {} LEDs",
+            ADALIGHT_MAX_LEDS
+        );
+        Self {
+            writer: Mutex::new(writer),
+            size,
+            serpentine: false,
+            frame: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Reverses every other row, for matrices wired back and forth.
+    pub fn with_serpentine(self, serpentine: bool) -> Self {
+        Self { serpentine, ..self }
+    }
+
+    /// Writes the Adalight header: the magic word, the LED count less one
+    /// and a checksum of it.
+    fn write_header(&mut self) {
+        let [high, low] = ((self.size.element_product() - 1) as u16).to_be_bytes();
+        self.frame.extend_from_slice(ADALIGHT_MAGIC);
+        self.frame
+            .extend_from_slice(&[high, low, high ^ low ^ 0x55]);
+    }
+
+    /// The average colour of the pixels the LED covers.
+    fn sample(&self, rgba: &[u8], width: u32, height: u32, led: UVec2) -> [u8; 3] {
+        let min = led * UVec2::new(width, height) / self.size;
+        let max = ((led + 1) * UVec2::new(width, height) / self.size).max(min + 1);
+        let mut sum = [0u32; 3];
+        for y in min.y..max.y.min(height) {
+            for x in min.x..max.x.min(width) {
+                let i = (y * width + x) as usize * RGBA;
+                for (sum, &channel) in sum.iter_mut().zip(&rgba[i..i + 3]) {
+                    *sum += channel as u32;
+                }
+            }
+        }
+        let count = (max - min).element_product().max(1);
+        sum.map(|sum| (sum / count) as u8)
+    }
+}
+
+impl<W: Write + Send + 'static> M8PixelSink for M8LedMatrix<W> {
+    fn present(&mut self, rgba: &[u8], width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.frame.clear();
+        self.write_header();
+        for y in 0..self.size.y {
+            for column in 0..self.size.x {
+                let x = if self.serpentine && y % 2 == 1 {
+                    self.size.x - 1 - column
+                } else {
+                    column
+                };
+                let colour = self.sample(rgba, width, height, UVec2::new(x, y));
+                self.frame.extend_from_slice(&colour);
+            }
+        }
+
+        // Only the first of a run of failures is logged, the matrix may
+        // well be unplugged for a while.
+        let writer = self
+            .writer
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        match writer.write_all(&self.frame).and_then(|()| writer.flush()) {
+            Ok(()) => self.failed = false,
+            Err(e) if !self.failed => {
+                error!("Failed to write to the LED matrix: {:?}", e);
+                self.failed = true;
+            }
+            Err(_) => (),
+        }
+    }
+}
+
+pub(crate) fn present_pixels(
+    mut dirty: MessageReader<M8DirtyRegion>,
+    mut output: ResMut<M8PixelOutput>,
+    display: Res<M8Display>,
+    images: Res<Assets<Image>>,
+) {
+    let Some(image) = images.get(display.image()) else {
+        return;
+    };
+    let Some(data) = image.data.as_deref() else {
+        return;
+    };
+    let screen = URect::new(0, 0, image.width(), image.height());
+    let region = output
+        .region
+        .map_or(screen, |region| region.intersect(screen));
+    // Every region is read, so none is left over for the next frame.
+    let mut drawn = false;
+    for dirty in dirty.read() {
+        drawn |= !dirty.rect.intersect(region).is_empty();
+    }
+    if !drawn || region.is_empty() {
+        return;
+    }
+
+    if region == screen {
+        output.sink.present(data, image.width(), image.height());
+        return;
+    }
+
+    // Copy the rows of the region out of the display.
+    let output = &mut *output;
+    output.pixels.clear();
+    for y in region.min.y..region.max.y {
+        let start = (y * image.width() + region.min.x) as usize * RGBA;
+        let end = start + region.width() as usize * RGBA;
+        output.pixels.extend_from_slice(&data[start..end]);
+    }
+    output
+        .sink
+        .present(&output.pixels, region.width(), region.height());
+}
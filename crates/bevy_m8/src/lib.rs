@@ -1,18 +1,262 @@
 //! Dirtywave M8 accessible from within a bevy app.
+//!
+//! [M8Plugin] adds everything at once. Its parts are plugins of their own
+//! and can be composed directly when only some of them are wanted:
+//!
+//! - [M8AssetsPlugin] loads the assets and drives [M8LoadingState], the
+//!   other plugins wait for it.
+//! - [M8SerialPlugin] connects to the M8, or plays back a capture, and
+//!   sends the [M8LiveInput] of the other plugins to the M8. It brings in
+//!   [M8DecoderPlugin], which decodes the commands received into
+//!   [M8Commands] and drives [M8HandshakeState] once the M8 identifies
+//!   itself.
+//! - [M8DisplayPlugin], [M8FontsPlugin] and [M8KeyMapPlugin] draw the screen
+//!   and send the keyboard, with [M8ZoomPlugin], [M8RotationPlugin] and
+//!   [M8CursorPlugin] building on them.
+//! - [M8AudioPlugin] plays the M8's audio.
+//! - [M8RemotePlugin], [M8MacroPlugin] and [M8WatchdogPlugin] add remote
+//!   control, macros and the display watchdog.
+//! - `M8DevicesPlugin` connects further M8s spawned as `M8Device` entities.
+//!
+//! Without [M8SerialPlugin] no port is opened and [M8DecoderPlugin] decodes
+//! the commands the app pushes into [M8Commands] during
+//! [M8UpdateSystems::Input] instead. Along with [M8AssetsPlugin],
+//! [M8DisplayPlugin] and [M8FontsPlugin] that draws commands played back
+//! from elsewhere, such as a capture decoded with [M8StreamDecoder].
+//!
+//! The display, fonts and the plugins building on them need the `render`
+//! feature, which is on by default. Without it [M8Plugin] runs the serial
+//! connection, decoder, audio and remote control in a windowless app.
+//!
+//! Everything bevy related needs the default `bevy` feature. Without it the
+//! crate only holds the wire format: [M8Command], [M8StreamDecoder],
+//! [decode_stream] and the encoder, with colours as their RGB bytes.
+//! [color_from_rgb] and [rgb_from_color] convert between colours and
+//! those bytes either way.
 
+#[cfg(feature = "bevy")]
 mod assets;
+#[cfg(feature = "bevy")]
 mod audio;
+#[cfg(feature = "bevy")]
+mod capture;
+mod color;
+#[cfg(feature = "serde")]
+mod config;
+#[cfg(all(feature = "egui", not(target_arch = "wasm32")))]
+mod control_panel;
+#[cfg(feature = "render")]
+mod cursor;
 mod decoder;
+#[cfg(all(feature = "bevy", not(target_arch = "wasm32")))]
+mod devices;
+#[cfg(feature = "bevy")]
+mod diagnostics;
+#[cfg(feature = "bevy")]
+mod dimensions;
+#[cfg(feature = "render")]
 mod display;
+mod encoder;
+#[cfg(feature = "render")]
+mod fonts;
+#[cfg(feature = "render")]
+mod framebuffer;
+#[cfg(feature = "bevy")]
+mod input;
+#[cfg(feature = "bevy")]
 mod keymap;
+#[cfg(feature = "render")]
+mod keypad;
+#[cfg(feature = "render")]
+mod led_matrix;
+#[cfg(feature = "bevy")]
+mod macros;
+#[cfg(feature = "midi")]
+mod midi;
+#[cfg(feature = "bevy")]
+mod mock;
+#[cfg(feature = "render")]
+mod piano;
+#[cfg(feature = "bevy")]
 mod remote;
+#[cfg(feature = "bevy")]
+mod ring_buffer;
+#[cfg(feature = "render")]
+mod rotation;
+#[cfg(feature = "bevy")]
+mod screen_text;
+#[cfg(feature = "bevy")]
+mod scrubber;
+#[cfg(feature = "bevy")]
 mod serial;
+#[cfg(feature = "term")]
+mod term;
+#[cfg(feature = "bevy")]
+mod theme;
+#[cfg(feature = "bevy")]
+mod transport;
+#[cfg(feature = "bevy")]
 mod utils;
+#[cfg(feature = "bevy")]
+mod watchdog;
+#[cfg(feature = "bevy")]
+mod wav;
+#[cfg(all(feature = "bevy", target_arch = "wasm32"))]
+mod web_serial;
+#[cfg(feature = "render")]
+mod zoom;
 
+#[cfg(feature = "bevy")]
 use bevy::prelude::*;
-pub use keymap::M8KeyMap;
+#[cfg(feature = "render")]
+use bevy::window::{MonitorSelection, PresentMode};
+#[cfg(feature = "bevy")]
+use std::{collections::HashSet, net::IpAddr, path::PathBuf, time::Duration};
+
+#[cfg(feature = "bevy")]
+pub use assets::M8AssetsPlugin;
+#[cfg(feature = "bevy")]
+pub use audio::{
+    M8AudioConfig, M8AudioLevel, M8AudioMeterConfig, M8AudioPlugin, M8AudioRecording, M8AudioStats,
+};
+#[cfg(feature = "bevy")]
+pub use capture::M8Playback;
+#[cfg(not(feature = "bevy"))]
+pub use color::Color;
+pub use color::{color_from_rgb, rgb_from_color};
+#[cfg(feature = "serde")]
+pub use config::M8Config;
+#[cfg(all(feature = "egui", not(target_arch = "wasm32")))]
+pub use control_panel::{M8ControlPanel, M8ControlPanelPlugin};
+#[cfg(feature = "render")]
+pub use cursor::{M8CursorEstimate, M8CursorPlugin, M8CursorSignature, M8Rectangle};
+pub use decoder::{
+    DRAW_CHARACTER_COMMAND, DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND, DRAW_RECTANGLE_COMMAND,
+    KEY_PRESS_STATE_COMMAND, M8Command, M8DeviceInfo, M8HardwareModel, M8StreamDecoder, Position,
+    SYSTEM_INFO_COMMAND, Size, decode_stream,
+};
+#[cfg(feature = "bevy")]
+pub use decoder::{M8DeviceInfoChanged, M8Ready};
+#[cfg(all(feature = "bevy", not(target_arch = "wasm32")))]
+pub use devices::{M8Device, M8DevicesPlugin};
+#[cfg(feature = "bevy")]
+pub use diagnostics::{
+    M8_AUDIO_OVERRUNS, M8_AUDIO_UNDERRUNS, M8_BYTES_PER_SECOND, M8_COMMANDS_PER_FRAME,
+    M8_CONNECTED, M8_CONNECTION_TRANSITIONS, M8_DECODE_ERRORS, M8DiagnosticsPlugin,
+};
+#[cfg(feature = "bevy")]
+pub use dimensions::{DISPLAY_HEIGHT, DISPLAY_WIDTH, M8DisplayDimensions};
+#[cfg(feature = "render")]
+pub use display::{
+    M8ColorSpace, M8DirtyRegion, M8DisconnectedDisplay, M8Display, M8DisplayCamera,
+    M8DisplayPlugin, M8DisplaySprite, M8DisplayStats, M8FrameSink, M8ModelDetected, M8ScopeRegion,
+    M8StreamLost, M8WaveformHistory, M8WaveformMode,
+};
+pub use encoder::{encode_command, encode_stream};
+#[cfg(feature = "render")]
+pub use fonts::{M8Font, M8FontsPlugin};
+#[cfg(feature = "render")]
+pub use framebuffer::{draw_line, draw_vertical_span};
+#[cfg(feature = "bevy")]
+pub use input::{
+    M8_DOWN, M8_EDIT, M8_LEFT, M8_OPTION, M8_RIGHT, M8_SELECT, M8_START, M8_UP, M8Button,
+    M8DeviceButtonState, M8InputState, M8KeyRepeat, M8KeyboardState,
+};
+#[cfg(feature = "bevy")]
+pub use keymap::{M8KeyMap, M8KeyMapPlugin};
+#[cfg(feature = "render")]
+pub use keypad::{M8Keypad, M8KeypadButton, M8KeypadPlugin};
+#[cfg(feature = "render")]
+pub use led_matrix::{M8LedMatrix, M8PixelOutput, M8PixelSink};
+#[cfg(feature = "bevy")]
+pub use macros::{M8LiveInput, M8Macro, M8MacroCommand, M8MacroPlugin, M8MacroStep, M8Macros};
+#[cfg(feature = "midi")]
+pub use midi::M8MidiPlugin;
+#[cfg(feature = "bevy")]
+pub use mock::M8MockConnection;
+#[cfg(feature = "render")]
+pub use piano::{M8Piano, M8PianoKey, M8PianoPlugin};
+#[cfg(feature = "bevy")]
+pub use remote::M8RemotePlugin;
+#[cfg(feature = "bevy")]
+pub use ring_buffer::{RingConsumer, RingProducer, capacity_for_latency, ring_buffer};
+#[cfg(feature = "render")]
+pub use rotation::{M8DisplayRotation, M8RotationPlugin};
+#[cfg(feature = "bevy")]
+pub use screen_text::{M8ScreenText, M8ScreenTextPlugin};
+#[cfg(feature = "bevy")]
+pub use scrubber::{M8Scrubber, M8ScrubberCommand, M8ScrubberPlugin};
+#[cfg(all(feature = "bevy", not(target_arch = "wasm32")))]
+pub use serial::classify_open_error;
+#[cfg(feature = "bevy")]
+pub use serial::{
+    M8CommandEvent, M8Commands, M8Connection, M8ConnectionChanged, M8ConnectionError,
+    M8ConnectionIssue, M8ConnectionStatus, M8DecodePaused, M8DecoderPlugin, M8FrameEvent,
+    M8RawFrame, M8Reconnect, M8ScreenState, M8SerialPlugin, M8SerialThroughput, M8StreamResync,
+    m8_connected, m8_decode_paused, m8_screen_changed,
+};
+#[cfg(feature = "term")]
+pub use term::M8TermRenderer;
+#[cfg(feature = "bevy")]
+pub use theme::{M8Theme, M8ThemePlugin};
+#[cfg(feature = "bevy")]
+pub use transport::M8Transport;
+#[cfg(feature = "bevy")]
+pub use watchdog::{M8WatchdogConfig, M8WatchdogPlugin, M8WatchdogStats};
+#[cfg(feature = "bevy")]
+pub use wav::M8WavRecorder;
+#[cfg(all(feature = "bevy", target_arch = "wasm32"))]
+pub use web_serial::M8WebSerial;
+#[cfg(feature = "render")]
+pub use zoom::{M8DisplayView, M8Zoom, M8ZoomPlugin};
+
+/// Everything needed to use the M8 from a bevy app.
+///
+/// The plugins, commands, messages, resources and components in here are
+/// considered stable and only change with a breaking release. Items exported
+/// from the crate root but left out of the prelude, such as the wire encoder
+/// and decoder and the diagnostic paths, may still change between minor
+/// releases.
+#[cfg(feature = "bevy")]
+pub mod prelude {
+    #[cfg(feature = "serde")]
+    pub use crate::M8Config;
+    #[cfg(feature = "midi")]
+    pub use crate::M8MidiPlugin;
+    #[cfg(feature = "term")]
+    pub use crate::M8TermRenderer;
+    pub use crate::{
+        M8AssetsPlugin, M8AudioConfig, M8AudioLevel, M8AudioMeterConfig, M8AudioPlugin,
+        M8AudioRecording, M8AudioStats, M8Button, M8Command, M8CommandEvent, M8Commands,
+        M8Connection, M8ConnectionChanged, M8ConnectionError, M8ConnectionIssue,
+        M8ConnectionStatus, M8DecodePaused, M8DecoderPlugin, M8DeviceButtonState, M8DeviceInfo,
+        M8DeviceInfoChanged, M8DiagnosticsPlugin, M8DisplayDimensions, M8FrameEvent,
+        M8HandshakeState, M8HardwareModel, M8InputState, M8KeyMap, M8KeyMapPlugin, M8KeyRepeat,
+        M8KeyboardState, M8LiveInput, M8LoadingState, M8Macro, M8MacroCommand, M8MacroPlugin,
+        M8MacroStep, M8Macros, M8MockConnection, M8Playback, M8Plugin, M8RawFrame, M8Ready,
+        M8Reconnect, M8RemotePlugin, M8ScreenState, M8ScreenText, M8ScreenTextPlugin, M8Scrubber,
+        M8ScrubberCommand, M8ScrubberPlugin, M8SerialPlugin, M8SerialThroughput, M8StreamResync,
+        M8Theme, M8ThemePlugin, M8Transport, M8UpdateSystems, M8WatchdogConfig, M8WatchdogPlugin,
+        M8WatchdogStats, M8WavRecorder, Position, Size, m8_connected, m8_decode_paused,
+        m8_screen_changed,
+    };
+    #[cfg(feature = "render")]
+    pub use crate::{
+        M8ColorSpace, M8CursorEstimate, M8CursorPlugin, M8CursorSignature, M8DirtyRegion,
+        M8DisconnectedDisplay, M8Display, M8DisplayCamera, M8DisplayPlugin, M8DisplayRotation,
+        M8DisplaySprite, M8DisplayStats, M8DisplayView, M8Font, M8FontsPlugin, M8FrameSink,
+        M8Keypad, M8KeypadButton, M8KeypadPlugin, M8LedMatrix, M8ModelDetected, M8Piano,
+        M8PianoKey, M8PianoPlugin, M8PixelOutput, M8PixelSink, M8Rectangle, M8RotationPlugin,
+        M8ScopeRegion, M8StreamLost, M8WaveformHistory, M8WaveformMode, M8Zoom, M8ZoomPlugin,
+    };
+    #[cfg(all(feature = "egui", not(target_arch = "wasm32")))]
+    pub use crate::{M8ControlPanel, M8ControlPanelPlugin};
+    #[cfg(not(target_arch = "wasm32"))]
+    pub use crate::{M8Device, M8DevicesPlugin};
+}
 
 /// Dirtywave M8 accessible from within a bevy app.
+#[cfg(feature = "bevy")]
 #[derive(Debug, Default, Clone, Eq, PartialEq, Hash, States)]
 pub enum M8LoadingState {
     #[default]
@@ -20,29 +264,462 @@ pub enum M8LoadingState {
     Running,
 }
 
+/// Whether the M8 has identified itself with the [M8Command::SystemInfo] it
+/// sends once enabled, driven by [M8SerialPlugin]. Until then the model, and
+/// with it the display size and font, is only a guess.
+///
+/// [M8Ready] is sent in the update the handshake completes. The state stays
+/// `Ready` across reconnects, as the M8 identifying itself again can only
+/// change the [M8DeviceInfo].
+#[cfg(feature = "bevy")]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash, States)]
+pub enum M8HandshakeState {
+    #[default]
+    Waiting,
+    Ready,
+}
+
+/// The stages the M8 runs through every update, chained in this order.
+///
+/// Systems ordered `.after(M8UpdateSystems::Decode)` see the commands of
+/// this update in [M8Commands], and those ordered
+/// `.before(M8UpdateSystems::DisplayRender)` as well run before they are
+/// drawn. Systems after [M8UpdateSystems::DisplayRender] see the display
+/// with this update's commands drawn onto it.
+#[cfg(feature = "bevy")]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum M8UpdateSystems {
+    /// Collects the buttons held on every input source into
+    /// [M8InputState], which is then sent to the M8 as one control byte.
+    Input,
+    /// Receives the commands decoded from the M8 into [M8Commands].
+    Decode,
+    /// Draws the decoded commands onto the display.
+    DisplayRender,
+}
+
 /// If no port is defined, this is the assigned default one.
-const DEFAULT_M8_PORT: &'static str = "/dev/ttyACM0";
+#[cfg(feature = "bevy")]
+const DEFAULT_M8_PORT: &str = "/dev/ttyACM0";
+
+/// How often the app updates when running without a window.
+#[cfg(feature = "bevy")]
+const HEADLESS_FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// The M8 Bevy Plugin, adding all of the M8's plugins.
+#[cfg(feature = "bevy")]
+pub struct M8Plugin {
+    port: String,
+    #[cfg(feature = "render")]
+    font_path: String,
+    #[cfg(feature = "render")]
+    text_offset_y: Option<u16>,
+    mock: Option<M8MockConnection>,
+    record_path: Option<PathBuf>,
+    playback_path: Option<PathBuf>,
+    #[cfg(feature = "render")]
+    rotation: M8DisplayRotation,
+    #[cfg(feature = "render")]
+    resolution: UVec2,
+    baud_rate: Option<u32>,
+    write_retries: Option<u32>,
+    resync_after: Option<Duration>,
+    enabled_commands: Option<HashSet<u8>>,
+    raw_frames: bool,
+    max_commands: Option<usize>,
+    log_commands: bool,
+    #[cfg(feature = "render")]
+    headless: bool,
+    #[cfg(feature = "render")]
+    colour_space: M8ColorSpace,
+    #[cfg(feature = "render")]
+    present_mode: PresentMode,
+    #[cfg(feature = "render")]
+    fullscreen: bool,
+    #[cfg(feature = "render")]
+    monitor: MonitorSelection,
+    #[cfg(feature = "render")]
+    enable_on_focus: bool,
+    #[cfg(feature = "render")]
+    wait_for_handshake: bool,
+    #[cfg(feature = "render")]
+    stream_timeout: Option<Duration>,
+    key_map: M8KeyMap,
+    remote_address: IpAddr,
+    remote_port: u16,
+    /// The configuration file the plugin was created from.
+    #[cfg(feature = "serde")]
+    config: Option<M8Config>,
+}
+
+#[cfg(feature = "bevy")]
+impl M8Plugin {
+    /// Creates the plugin, preferring the M8 on the given port.
+    pub fn new(port: impl Into<String>) -> Self {
+        Self {
+            port: port.into(),
+            ..default()
+        }
+    }
+
+    /// Prefers the M8 on the given port.
+    pub fn with_port(self, port: impl Into<String>) -> Self {
+        Self {
+            port: port.into(),
+            ..self
+        }
+    }
+
+    /// Sets the initial size of the window.
+    #[cfg(feature = "render")]
+    pub fn with_resolution(self, width: u32, height: u32) -> Self {
+        Self {
+            resolution: UVec2::new(width, height),
+            ..self
+        }
+    }
+
+    /// Overrides the baud rate used to open the serial port.
+    pub fn with_baud_rate(self, baud_rate: u32) -> Self {
+        Self {
+            baud_rate: Some(baud_rate),
+            ..self
+        }
+    }
+
+    /// Overrides how often a failed write to the M8 is retried before it
+    /// is reported, 3 times by default.
+    pub fn with_write_retries(self, write_retries: u32) -> Self {
+        Self {
+            write_retries: Some(write_retries),
+            ..self
+        }
+    }
+
+    /// Overrides how long bytes may keep arriving without completing a packet
+    /// before the SLIP decoder is reset, half a second by default.
+    pub fn with_resync_after(self, resync_after: Duration) -> Self {
+        Self {
+            resync_after: Some(resync_after),
+            ..self
+        }
+    }
+
+    /// Only decodes the commands with the given bytes, such as
+    /// [SYSTEM_INFO_COMMAND], skipping the others without building them.
+    pub fn with_enabled_commands(self, enabled_commands: impl IntoIterator<Item = u8>) -> Self {
+        Self {
+            enabled_commands: Some(enabled_commands.into_iter().collect()),
+            ..self
+        }
+    }
+
+    /// Also sends every frame from the M8 undecoded as an [M8RawFrame], for
+    /// forwarding the stream to another consumer.
+    pub fn with_raw_frames(self, raw_frames: bool) -> Self {
+        Self { raw_frames, ..self }
+    }
+
+    /// Keeps at most this many decoded commands each frame, dropping the
+    /// oldest, to bound the memory used when frames are slow.
+    pub fn with_max_commands(self, max_commands: usize) -> Self {
+        Self {
+            max_commands: Some(max_commands),
+            ..self
+        }
+    }
+
+    /// Logs every decoded command at the trace level, as one line each.
+    pub fn with_command_log(self, log_commands: bool) -> Self {
+        Self {
+            log_commands,
+            ..self
+        }
+    }
+
+    /// Runs without a window or renderer, e.g. to record or drive the M8
+    /// from a server.
+    #[cfg(feature = "render")]
+    pub fn with_headless(self, headless: bool) -> Self {
+        Self { headless, ..self }
+    }
+
+    /// Interprets the colours sent by the M8 in the given colour space, to
+    /// match the look of the hardware on a particular panel.
+    #[cfg(feature = "render")]
+    pub fn with_colour_space(self, colour_space: M8ColorSpace) -> Self {
+        Self {
+            colour_space,
+            ..self
+        }
+    }
+
+    /// Sets how frames are presented to the window, `AutoVsync` by default.
+    #[cfg(feature = "render")]
+    pub fn with_present_mode(self, present_mode: PresentMode) -> Self {
+        Self {
+            present_mode,
+            ..self
+        }
+    }
+
+    /// Fills the monitor with a borderless window instead of opening one of
+    /// the set resolution.
+    #[cfg(feature = "render")]
+    pub fn with_fullscreen(self, fullscreen: bool) -> Self {
+        Self { fullscreen, ..self }
+    }
 
-/// The M8 Bevy Plugin.
-pub struct M8Plugin(pub String);
+    /// Opens the window on the given monitor instead of the primary one, for
+    /// showing the M8 on a secondary display or projector.
+    #[cfg(feature = "render")]
+    pub fn with_monitor(self, monitor: MonitorSelection) -> Self {
+        Self { monitor, ..self }
+    }
+
+    /// Sends the enable command again whenever the window regains focus,
+    /// working around M8s which stop streaming after the computer sleeps.
+    #[cfg(feature = "render")]
+    pub fn with_enable_on_focus(self, enable_on_focus: bool) -> Self {
+        Self {
+            enable_on_focus,
+            ..self
+        }
+    }
+
+    /// Draws nothing until the M8 has identified itself, see
+    /// [M8HandshakeState], so the first frames aren't drawn for the wrong
+    /// model. Streams which never send the system info, like most mocks,
+    /// then never draw.
+    #[cfg(feature = "render")]
+    pub fn with_wait_for_handshake(self, wait_for_handshake: bool) -> Self {
+        Self {
+            wait_for_handshake,
+            ..self
+        }
+    }
+
+    /// Clears the display and sends [M8StreamLost] once the connected M8
+    /// has sent nothing for this long, instead of leaving the last frame up.
+    #[cfg(feature = "render")]
+    pub fn with_stream_timeout(self, stream_timeout: Duration) -> Self {
+        Self {
+            stream_timeout: Some(stream_timeout),
+            ..self
+        }
+    }
+
+    /// Uses the given key bindings instead of the defaults.
+    pub fn with_keymap(self, key_map: M8KeyMap) -> Self {
+        Self { key_map, ..self }
+    }
+
+    /// Listens for remote control on the given address and port.
+    pub fn with_remote(self, address: impl Into<IpAddr>, port: u16) -> Self {
+        Self {
+            remote_address: address.into(),
+            remote_port: port,
+            ..self
+        }
+    }
+
+    /// Uses the font atlas at the given asset path instead of the stock font.
+    #[cfg(feature = "render")]
+    pub fn with_font(self, font_path: impl Into<String>) -> Self {
+        Self {
+            font_path: font_path.into(),
+            ..self
+        }
+    }
+
+    /// Replays the mock connection instead of connecting to a device.
+    pub fn with_mock(self, mock: M8MockConnection) -> Self {
+        Self {
+            mock: Some(mock),
+            ..self
+        }
+    }
+
+    /// Loops a built-in demo instead of connecting to a device, for
+    /// screenshots and trying the crate out without an M8.
+    pub fn with_demo(self) -> Self {
+        self.with_mock(M8MockConnection::demo())
+    }
+
+    /// Records the raw serial stream to a capture file.
+    pub fn with_recording(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            record_path: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Plays back a capture file instead of connecting to a device.
+    pub fn with_playback(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            playback_path: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Rotates the presented display, for monitors mounted in portrait.
+    #[cfg(feature = "render")]
+    pub fn with_rotation(self, rotation: M8DisplayRotation) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// Overrides the vertical offset used when drawing characters.
+    #[cfg(feature = "render")]
+    pub fn with_text_offset_y(self, text_offset_y: u16) -> Self {
+        Self {
+            text_offset_y: Some(text_offset_y),
+            ..self
+        }
+    }
+}
+
+/// Orders the update stages, done by every plugin using them so each
+/// can be added on its own.
+#[cfg(feature = "bevy")]
+fn configure_update_systems(app: &mut App) {
+    app.configure_sets(
+        Update,
+        (
+            M8UpdateSystems::Input,
+            M8UpdateSystems::Decode,
+            M8UpdateSystems::DisplayRender,
+        )
+            .chain(),
+    );
+}
+
+#[cfg(feature = "bevy")]
 impl Plugin for M8Plugin {
     fn build(&self, app: &mut App) {
-        // Add the Serial Interaction Plugin.
+        // The display brings in bevy's plugins, which come first as the
+        // states the other plugins use need them.
+        #[cfg(feature = "render")]
+        app.add_plugins(display::M8DisplayPlugin {
+            resolution: self.resolution,
+            headless: self.headless,
+            colour_space: self.colour_space,
+            present_mode: self.present_mode,
+            fullscreen: self.fullscreen,
+            monitor: self.monitor,
+            enable_on_focus: self.enable_on_focus,
+            wait_for_handshake: self.wait_for_handshake,
+            stream_timeout: self.stream_timeout,
+        });
+
+        // Without the display, which brings in the window, the app runs on
+        // bevy's plugins without a window at the headless frame rate.
+        #[cfg(not(feature = "render"))]
+        app.add_plugins(
+            DefaultPlugins.set(bevy::app::ScheduleRunnerPlugin::run_loop(
+                HEADLESS_FRAME_TIME,
+            )),
+        );
+
+        // Add the decoder, then the Serial Interaction Plugin feeding it.
         app.add_plugins((
+            serial::M8DecoderPlugin {
+                log_commands: self.log_commands,
+            },
             serial::M8SerialPlugin {
-                preferred_device: self.0.clone().into(),
+                preferred_device: self.port.clone().into(),
+                mock: self.mock.clone(),
+                record_path: self.record_path.clone(),
+                playback_path: self.playback_path.clone(),
+                baud_rate: self.baud_rate,
+                write_retries: self.write_retries,
+                resync_after: self.resync_after,
+                enabled_commands: self.enabled_commands.clone(),
+                raw_frames: self.raw_frames,
+                max_commands: self.max_commands,
+                log_commands: self.log_commands,
+            },
+            remote::M8RemotePlugin::default()
+                .with_address(self.remote_address)
+                .with_port(self.remote_port),
+            keymap::M8KeyMapPlugin {
+                key_map: self.key_map,
             },
-            display::M8DisplayPlugin,
-            remote::M8RemotePlugin::default(),
-            keymap::M8KeyMapPlugin,
             assets::M8AssetsPlugin,
             audio::M8AudioPlugin,
+            macros::M8MacroPlugin,
+            screen_text::M8ScreenTextPlugin,
+            watchdog::M8WatchdogPlugin,
+            theme::M8ThemePlugin,
+        ));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugins(devices::M8DevicesPlugin);
+
+        #[cfg(feature = "render")]
+        app.add_plugins((
+            fonts::M8FontsPlugin {
+                font_path: self.font_path.clone(),
+                text_offset_y: self.text_offset_y,
+            },
+            zoom::M8ZoomPlugin,
+            rotation::M8RotationPlugin {
+                rotation: self.rotation,
+            },
+            cursor::M8CursorPlugin,
         ));
+
+        #[cfg(feature = "serde")]
+        if let Some(config) = &self.config {
+            app.insert_resource(config.clone());
+            app.add_systems(Startup, config::log_config);
+        }
     }
 }
 
+#[cfg(feature = "bevy")]
 impl Default for M8Plugin {
     fn default() -> Self {
-        Self(DEFAULT_M8_PORT.into())
+        Self {
+            port: DEFAULT_M8_PORT.into(),
+            #[cfg(feature = "render")]
+            font_path: assets::DEFAULT_FONT_PATH.into(),
+            #[cfg(feature = "render")]
+            text_offset_y: None,
+            mock: None,
+            record_path: None,
+            playback_path: None,
+            #[cfg(feature = "render")]
+            rotation: M8DisplayRotation::Deg0,
+            #[cfg(feature = "render")]
+            resolution: UVec2::new(DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            baud_rate: None,
+            write_retries: None,
+            resync_after: None,
+            enabled_commands: None,
+            raw_frames: false,
+            max_commands: None,
+            log_commands: false,
+            #[cfg(feature = "render")]
+            headless: false,
+            #[cfg(feature = "render")]
+            colour_space: M8ColorSpace::default(),
+            #[cfg(feature = "render")]
+            present_mode: PresentMode::AutoVsync,
+            #[cfg(feature = "render")]
+            fullscreen: false,
+            #[cfg(feature = "render")]
+            monitor: MonitorSelection::Primary,
+            #[cfg(feature = "render")]
+            enable_on_focus: false,
+            #[cfg(feature = "render")]
+            wait_for_handshake: false,
+            #[cfg(feature = "render")]
+            stream_timeout: None,
+            key_map: M8KeyMap::default(),
+            remote_address: remote::DEFAULT_ADDRESS,
+            remote_port: remote::DEFAULT_PORT,
+            #[cfg(feature = "serde")]
+            config: None,
+        }
     }
 }
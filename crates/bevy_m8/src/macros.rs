@@ -0,0 +1,319 @@
+//! This file provides recording and playback of macros, timed sequences
+//! of the controller and keyjazz input sent to the M8.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{input::M8KeyboardState, serial::M8Connection};
+
+/// Key which starts and stops recording a macro by default.
+const DEFAULT_MACRO_RECORD_KEY: KeyCode = KeyCode::F5;
+
+/// Key which plays the last recorded macro by default.
+const DEFAULT_MACRO_PLAY_KEY: KeyCode = KeyCode::F6;
+
+/// Key which cancels the macro being played by default.
+const DEFAULT_MACRO_CANCEL_KEY: KeyCode = KeyCode::F7;
+
+/// Releases every key on the M8.
+const RELEASE_KEYS: [u8; 2] = [b'C', 0];
+
+/// Bytes sent to the M8 by live input, such as the keyboard or MIDI.
+/// [M8SerialPlugin](crate::M8SerialPlugin) forwards them to the M8. With
+/// [M8MacroPlugin] they are also recorded while recording a macro, and
+/// dropped while one plays.
+#[derive(Message, Debug, Clone)]
+pub struct M8LiveInput(pub Vec<u8>);
+
+/// A single message of a macro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct M8MacroStep {
+    /// Milliseconds since the start of the macro.
+    pub at_ms: u64,
+    /// The bytes sent to the M8.
+    pub bytes: Vec<u8>,
+}
+
+/// A timed sequence of input sent to the M8.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct M8Macro {
+    pub steps: Vec<M8MacroStep>,
+}
+
+impl M8Macro {
+    /// Returns how long the macro takes to play.
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.steps.last().map_or(0, |step| step.at_ms))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl M8Macro {
+    /// Saves the macro as RON.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(std::io::Error::other)?;
+        std::fs::write(path, ron)
+    }
+
+    /// Loads a macro saved with [M8Macro::save].
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let ron = std::fs::read_to_string(path)?;
+        ron::from_str(&ron).map_err(std::io::Error::other)
+    }
+}
+
+/// Controls the recording and playback of macros.
+#[derive(Message, Debug, Clone)]
+pub enum M8MacroCommand {
+    StartRecording,
+    StopRecording,
+    Play(M8Macro),
+    /// Plays the last recorded macro, if there is one.
+    PlayLast,
+    /// Stops the macro being played and releases the M8's keys.
+    Cancel,
+}
+
+#[derive(Debug, Default)]
+enum MacroState {
+    #[default]
+    Idle,
+    Recording {
+        started: Duration,
+        steps: Vec<M8MacroStep>,
+    },
+    Playing {
+        started: Duration,
+        playing: M8Macro,
+        next: usize,
+        /// The button mask the M8 was last sent.
+        sent_mask: u8,
+    },
+}
+
+/// The state of the macro recorder and player, and the keys which
+/// control them.
+#[derive(Resource, Debug)]
+pub struct M8Macros {
+    /// Starts and stops recording a macro.
+    pub record_key: KeyCode,
+    /// Plays the last recorded macro.
+    pub play_key: KeyCode,
+    /// Cancels the macro being played.
+    pub cancel_key: KeyCode,
+    state: MacroState,
+    last: Option<M8Macro>,
+}
+
+impl Default for M8Macros {
+    fn default() -> Self {
+        Self {
+            record_key: DEFAULT_MACRO_RECORD_KEY,
+            play_key: DEFAULT_MACRO_PLAY_KEY,
+            cancel_key: DEFAULT_MACRO_CANCEL_KEY,
+            state: MacroState::default(),
+            last: None,
+        }
+    }
+}
+
+impl M8Macros {
+    /// Sets the key which starts and stops recording a macro.
+    pub fn with_record_key(self, record_key: KeyCode) -> Self {
+        Self { record_key, ..self }
+    }
+
+    /// Sets the key which plays the last recorded macro.
+    pub fn with_play_key(self, play_key: KeyCode) -> Self {
+        Self { play_key, ..self }
+    }
+
+    /// Sets the key which cancels the macro being played.
+    pub fn with_cancel_key(self, cancel_key: KeyCode) -> Self {
+        Self { cancel_key, ..self }
+    }
+
+    /// Returns true while a macro is being recorded.
+    pub fn is_recording(&self) -> bool {
+        matches!(self.state, MacroState::Recording { .. })
+    }
+
+    /// Returns true while a macro plays, during which live input is dropped.
+    pub fn is_playing(&self) -> bool {
+        matches!(self.state, MacroState::Playing { .. })
+    }
+
+    /// The last recorded macro.
+    pub fn last(&self) -> Option<&M8Macro> {
+        self.last.as_ref()
+    }
+}
+
+fn macro_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    macros: Res<M8Macros>,
+    mut commands: MessageWriter<M8MacroCommand>,
+) {
+    if keys.just_pressed(macros.record_key) {
+        commands.write(if macros.is_recording() {
+            M8MacroCommand::StopRecording
+        } else {
+            M8MacroCommand::StartRecording
+        });
+    }
+
+    if keys.just_pressed(macros.play_key) {
+        commands.write(M8MacroCommand::PlayLast);
+    }
+
+    if keys.just_pressed(macros.cancel_key) {
+        commands.write(M8MacroCommand::Cancel);
+    }
+}
+
+fn handle_macro_commands(
+    time: Res<Time>,
+    connection: Res<M8Connection>,
+    mut macros: ResMut<M8Macros>,
+    mut keyboard: ResMut<M8KeyboardState>,
+    mut commands: MessageReader<M8MacroCommand>,
+) {
+    for command in commands.read() {
+        match command {
+            M8MacroCommand::StartRecording => {
+                if macros.is_playing() {
+                    warn!("Can't record a macro while one plays");
+                    continue;
+                }
+                info!("Recording macro...");
+                macros.state = MacroState::Recording {
+                    started: time.elapsed(),
+                    steps: Vec::new(),
+                };
+            }
+            M8MacroCommand::StopRecording => {
+                if let MacroState::Recording { steps, .. } = std::mem::take(&mut macros.state) {
+                    info!("Recorded macro with {} steps", steps.len());
+                    macros.last = Some(M8Macro { steps });
+                }
+            }
+            M8MacroCommand::Play(playing) => play(&mut macros, playing.clone(), &time, &keyboard),
+            M8MacroCommand::PlayLast => match macros.last.clone() {
+                Some(playing) => play(&mut macros, playing, &time, &keyboard),
+                None => warn!("No macro has been recorded"),
+            },
+            M8MacroCommand::Cancel => {
+                if macros.is_playing() {
+                    info!("Macro cancelled");
+                    macros.state = MacroState::Idle;
+                    let _ = connection.tx.send(RELEASE_KEYS.to_vec());
+                    connection.stop_keyjazz();
+                    // Buttons still held get sent again by the keyboard input.
+                    keyboard.last_mask = 0;
+                }
+            }
+        }
+    }
+}
+
+fn play(macros: &mut M8Macros, playing: M8Macro, time: &Time, keyboard: &M8KeyboardState) {
+    if macros.is_recording() {
+        warn!("Can't play a macro while recording one");
+        return;
+    }
+    info!(
+        "Playing macro, live input is suppressed for {:?}",
+        playing.duration()
+    );
+    macros.state = MacroState::Playing {
+        started: time.elapsed(),
+        playing,
+        next: 0,
+        sent_mask: keyboard.last_mask,
+    };
+}
+
+/// Sends the steps of the playing macro which are due.
+fn play_macro(
+    time: Res<Time>,
+    connection: Res<M8Connection>,
+    mut macros: ResMut<M8Macros>,
+    mut keyboard: ResMut<M8KeyboardState>,
+) {
+    let MacroState::Playing {
+        started,
+        playing,
+        next,
+        sent_mask,
+    } = &mut macros.state
+    else {
+        return;
+    };
+
+    let elapsed = time.elapsed().saturating_sub(*started).as_millis() as u64;
+    while let Some(step) = playing.steps.get(*next)
+        && step.at_ms <= elapsed
+    {
+        if let &[b'C', mask] = step.bytes.as_slice() {
+            *sent_mask = mask;
+        }
+        let _ = connection.tx.send(step.bytes.clone());
+        *next += 1;
+    }
+
+    if *next == playing.steps.len() {
+        info!("Macro finished");
+        // Keys released or pressed during playback were dropped, so let the
+        // keyboard input send its mask again if it differs from the M8's.
+        keyboard.last_mask = *sent_mask;
+        macros.state = MacroState::Idle;
+    }
+}
+
+/// Forwards live input to the M8, recording it into the macro being
+/// recorded and dropping it while a macro plays.
+pub(crate) fn forward_live_input(
+    time: Res<Time>,
+    connection: Res<M8Connection>,
+    mut macros: Option<ResMut<M8Macros>>,
+    mut inputs: MessageReader<M8LiveInput>,
+) {
+    for M8LiveInput(bytes) in inputs.read() {
+        let Some(macros) = macros.as_deref_mut() else {
+            let _ = connection.tx.send(bytes.clone());
+            continue;
+        };
+        match &mut macros.state {
+            MacroState::Idle => (),
+            MacroState::Recording { started, steps } => steps.push(M8MacroStep {
+                at_ms: time.elapsed().saturating_sub(*started).as_millis() as u64,
+                bytes: bytes.clone(),
+            }),
+            MacroState::Playing { .. } => {
+                debug!("Dropped live input while a macro plays: {:?}", bytes);
+                continue;
+            }
+        }
+        let _ = connection.tx.send(bytes.clone());
+    }
+}
+
+/// This plugin provides recording and playback of macros.
+pub struct M8MacroPlugin;
+
+impl Plugin for M8MacroPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<M8MacroCommand>();
+        app.init_resource::<M8Macros>();
+        app.add_systems(
+            Update,
+            (macro_input, handle_macro_commands, play_macro)
+                .chain()
+                .before(forward_live_input),
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use bevy_m8::M8Plugin;
+use bevy_m8::prelude::*;
 
 fn main() {
     App::new().add_plugins(M8Plugin::default()).run();
@@ -0,0 +1,163 @@
+//! This file provides MIDI input which plays the M8's keyjazz.
+
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, unbounded};
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+use crate::{
+    macros::M8LiveInput,
+    serial::{KEYJAZZ_STOP, keyjazz_message},
+};
+
+/// The name the MIDI client registers with.
+const MIDI_CLIENT_NAME: &str = "bevy_m8";
+
+const NOTE_OFF_STATUS: u8 = 0x80;
+const NOTE_ON_STATUS: u8 = 0x90;
+
+/// A note event received from the MIDI input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum M8MidiNote {
+    On { note: u8, velocity: u8 },
+    Off { note: u8 },
+}
+
+impl M8MidiNote {
+    /// Parses a note-on or note-off message, on any channel.
+    fn parse(message: &[u8]) -> Option<Self> {
+        let &[status, note, velocity] = message else {
+            return None;
+        };
+
+        match status & 0xF0 {
+            // A note-on with no velocity is a note-off by convention.
+            NOTE_ON_STATUS if velocity == 0 => Some(Self::Off { note }),
+            NOTE_ON_STATUS => Some(Self::On { note, velocity }),
+            NOTE_OFF_STATUS => Some(Self::Off { note }),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps the MIDI input open, it closes when dropped.
+struct M8MidiInput {
+    _connection: MidiInputConnection<()>,
+    notes: Receiver<M8MidiNote>,
+}
+
+/// The note currently played through keyjazz, if any.
+#[derive(Resource, Default)]
+struct M8KeyjazzNote(Option<u8>);
+
+fn connect_midi(world: &mut World) {
+    let port_name = world.resource::<M8MidiSettings>().port_name.clone();
+
+    let mut input = match MidiInput::new(MIDI_CLIENT_NAME) {
+        Ok(input) => input,
+        Err(e) => {
+            error!("Failed to create MIDI input: {}", e);
+            return;
+        }
+    };
+    input.ignore(Ignore::All);
+
+    let ports = input.ports();
+    let port = ports.iter().find(|port| {
+        let Ok(name) = input.port_name(port) else {
+            return false;
+        };
+        match &port_name {
+            Some(port_name) => name.contains(port_name.as_str()),
+            None => true,
+        }
+    });
+
+    let Some(port) = port.cloned() else {
+        warn!("No MIDI input port found matching {:?}", port_name);
+        return;
+    };
+    let name = input.port_name(&port).unwrap_or_default();
+
+    let (tx, rx) = unbounded();
+    let connection = input.connect(
+        &port,
+        MIDI_CLIENT_NAME,
+        move |_, message, _| {
+            if let Some(note) = M8MidiNote::parse(message) {
+                tx.send(note).ok();
+            }
+        },
+        (),
+    );
+
+    match connection {
+        Ok(connection) => {
+            info!("Connected to MIDI input {}", name);
+            world.insert_non_send_resource(M8MidiInput {
+                _connection: connection,
+                notes: rx,
+            });
+        }
+        Err(e) => error!("Failed to connect to MIDI input {}: {}", name, e),
+    }
+}
+
+/// Sends the received notes to the M8 as keyjazz commands.
+fn play_keyjazz(
+    midi: Option<NonSend<M8MidiInput>>,
+    mut live_input: MessageWriter<M8LiveInput>,
+    mut playing: ResMut<M8KeyjazzNote>,
+) {
+    let Some(midi) = midi else {
+        return;
+    };
+
+    for note in midi.notes.try_iter() {
+        match note {
+            M8MidiNote::On { note, velocity } => {
+                live_input.write(M8LiveInput(keyjazz_message(note, velocity)));
+                playing.0 = Some(note);
+            }
+            // Only stop the note if it is the one still playing, the M8
+            // plays a single keyjazz note at a time.
+            M8MidiNote::Off { note } if playing.0 == Some(note) => {
+                live_input.write(M8LiveInput(KEYJAZZ_STOP.to_vec()));
+                playing.0 = None;
+            }
+            M8MidiNote::Off { .. } => (),
+        }
+    }
+}
+
+/// The MIDI settings chosen when adding the [M8MidiPlugin].
+#[derive(Resource)]
+struct M8MidiSettings {
+    port_name: Option<String>,
+}
+
+/// This plugin plays the M8's keyjazz from a MIDI input.
+#[derive(Default)]
+pub struct M8MidiPlugin {
+    port_name: Option<String>,
+}
+
+impl M8MidiPlugin {
+    /// Uses the first MIDI input whose name contains `port_name`,
+    /// instead of the first one found.
+    pub fn with_port_name(self, port_name: impl Into<String>) -> Self {
+        Self {
+            port_name: Some(port_name.into()),
+        }
+    }
+}
+
+impl Plugin for M8MidiPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(M8MidiSettings {
+            port_name: self.port_name.clone(),
+        });
+        app.init_resource::<M8KeyjazzNote>();
+        app.add_systems(Startup, connect_midi);
+        app.add_systems(Update, play_keyjazz);
+    }
+}
@@ -0,0 +1,188 @@
+//! This file provides a mock M8 for development without the hardware.
+
+use std::{f32::consts::TAU, io, thread, time::Duration};
+
+use bevy::prelude::*;
+use crossbeam_channel::Sender;
+
+use crate::{
+    decoder::{CELL_SIZE, M8Command, Position, Size},
+    dimensions::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    encoder::encode_stream,
+    transport::M8Transport,
+};
+
+/// The default number of bytes handed out by each read.
+const DEFAULT_MOCK_CHUNK_SIZE: usize = 256;
+
+/// The default pause before each read, standing in for the serial timeout.
+const DEFAULT_MOCK_READ_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The title drawn in the middle of the demo screen.
+const DEMO_TITLE: &[u8] = b"BEVY_M8";
+
+/// The message scrolling along the bottom of the demo screen.
+const DEMO_MESSAGE: &[u8] = b"NO M8 CONNECTED - PLUG ONE IN TO TAKE OVER THE SCREEN";
+
+/// The distance the message scrolls each frame.
+const DEMO_SCROLL_STEP: u16 = 2;
+
+/// The advance and height of a character on the M8's screen.
+const DEMO_CHARACTER_SIZE: Size = Size::new(CELL_SIZE.x as u16, CELL_SIZE.y as u16);
+
+/// The frames in one loop of the demo, the message scrolls across once.
+const DEMO_FRAMES: u16 =
+    (DISPLAY_WIDTH as u16 + DEMO_MESSAGE.len() as u16 * DEMO_CHARACTER_SIZE.x) / DEMO_SCROLL_STEP;
+
+/// The height of the oscilloscope at the top of the screen.
+const DEMO_WAVEFORM_HEIGHT: f32 = 16.0;
+
+const DEMO_BACKGROUND: Color = Color::srgb_u8(0x00, 0x00, 0x00);
+const DEMO_TITLE_COLOUR: Color = Color::srgb_u8(0x00, 0xFF, 0xFF);
+const DEMO_MESSAGE_COLOUR: Color = Color::srgb_u8(0xFF, 0xFF, 0xFF);
+const DEMO_WAVEFORM_COLOUR: Color = Color::srgb_u8(0x00, 0xFF, 0x00);
+
+/// Draws a line of text starting at `x`, skipping characters off either
+/// edge of the screen.
+fn demo_text(text: &[u8], x: i32, y: u16, foreground: Color) -> impl Iterator<Item = M8Command> {
+    let advance = DEMO_CHARACTER_SIZE.x as i32;
+    text.iter().enumerate().filter_map(move |(i, &c)| {
+        let x = x + i as i32 * advance;
+        (x >= 0 && x + advance <= DISPLAY_WIDTH as i32).then(|| M8Command::DrawCharacter {
+            c,
+            pos: Position::new(x as u16, y),
+            foreground,
+            background: DEMO_BACKGROUND,
+        })
+    })
+}
+
+/// The commands of one loop of the demo: a title, a scrolling message and
+/// a moving waveform, as the M8 would draw them.
+fn demo_commands() -> Vec<M8Command> {
+    let width = DISPLAY_WIDTH as u16;
+    let height = DISPLAY_HEIGHT as u16;
+    let title_x = (width - DEMO_TITLE.len() as u16 * DEMO_CHARACTER_SIZE.x) / 2;
+    let title_y = (height - DEMO_CHARACTER_SIZE.y) / 2;
+    let message_y = height - DEMO_CHARACTER_SIZE.y * 3;
+
+    let mut commands = vec![M8Command::DrawRectangle {
+        pos: Position::ZERO,
+        size: Size::new(width, height),
+        colour: DEMO_BACKGROUND,
+    }];
+    commands.extend(demo_text(
+        DEMO_TITLE,
+        title_x.into(),
+        title_y,
+        DEMO_TITLE_COLOUR,
+    ));
+
+    for frame in 0..DEMO_FRAMES {
+        let phase = frame as f32 / DEMO_FRAMES as f32 * TAU;
+        let waveform = (0..width)
+            .map(|x| {
+                let angle = x as f32 / width as f32 * TAU * 2.0 + phase * 4.0;
+                ((angle.sin() + 1.0) / 2.0 * (DEMO_WAVEFORM_HEIGHT - 1.0)).round() as u8
+            })
+            .collect();
+        commands.push(M8Command::DrawOscilloscopeWaveform {
+            colour: DEMO_WAVEFORM_COLOUR,
+            waveform,
+        });
+
+        commands.push(M8Command::DrawRectangle {
+            pos: Position::new(0, message_y),
+            size: Size::new(width, DEMO_CHARACTER_SIZE.y),
+            colour: DEMO_BACKGROUND,
+        });
+        let x = width as i32 - (frame * DEMO_SCROLL_STEP) as i32;
+        commands.extend(demo_text(DEMO_MESSAGE, x, message_y, DEMO_MESSAGE_COLOUR));
+    }
+    commands
+}
+
+/// A connection which replays a scripted byte stream in a loop
+/// in place of a real M8. Anything written to it is discarded, unless
+/// [M8MockConnection::with_written] asks for it.
+#[derive(Debug, Clone)]
+pub struct M8MockConnection {
+    stream: Vec<u8>,
+    position: usize,
+    chunk_size: usize,
+    read_interval: Duration,
+    written: Option<Sender<Vec<u8>>>,
+}
+
+impl M8MockConnection {
+    /// Replays the raw bytes of a captured serial stream.
+    pub fn from_bytes(stream: Vec<u8>) -> Self {
+        Self {
+            stream,
+            position: 0,
+            chunk_size: DEFAULT_MOCK_CHUNK_SIZE,
+            read_interval: DEFAULT_MOCK_READ_INTERVAL,
+            written: None,
+        }
+    }
+
+    /// Replays the commands encoded as the M8 would send them.
+    pub fn from_commands(commands: &[M8Command]) -> Self {
+        Self::from_bytes(encode_stream(commands))
+    }
+
+    /// Loops a built-in demo, a title with a scrolling message and a moving
+    /// waveform, to show something without a device.
+    pub fn demo() -> Self {
+        Self::from_commands(&demo_commands())
+    }
+
+    pub fn with_chunk_size(self, chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_read_interval(self, read_interval: Duration) -> Self {
+        Self {
+            read_interval,
+            ..self
+        }
+    }
+
+    /// Hands everything written to the mock to `written`, one write at a
+    /// time, to check what a real M8 would have been sent.
+    pub fn with_written(self, written: Sender<Vec<u8>>) -> Self {
+        Self {
+            written: Some(written),
+            ..self
+        }
+    }
+}
+
+impl M8Transport for M8MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        thread::sleep(self.read_interval);
+        if self.stream.is_empty() {
+            return Ok(0);
+        }
+
+        if self.position >= self.stream.len() {
+            self.position = 0;
+        }
+
+        let end = (self.position + self.chunk_size.min(buf.len())).min(self.stream.len());
+        let count = end - self.position;
+        buf[..count].copy_from_slice(&self.stream[self.position..end]);
+        self.position = end;
+        Ok(count)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        if let Some(written) = &self.written {
+            written.send(data.to_vec()).ok();
+        }
+        Ok(())
+    }
+}
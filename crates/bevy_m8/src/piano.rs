@@ -0,0 +1,295 @@
+//! This file provides an on-screen piano which plays the M8's keyjazz, by
+//! clicking its keys or from rows of the computer keyboard.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    display::{M8DisplayCamera, M8DisplaySprite},
+    macros::M8LiveInput,
+    serial::{KEYJAZZ_STOP, keyjazz_message},
+};
+
+/// Toggles playing the piano from the computer keyboard.
+const PIANO_KEYBOARD_TOGGLE_KEY: KeyCode = KeyCode::F8;
+
+/// Lowers the octave while the piano is played from the keyboard.
+const OCTAVE_DOWN_KEY: KeyCode = KeyCode::Minus;
+
+/// Raises the octave while the piano is played from the keyboard.
+const OCTAVE_UP_KEY: KeyCode = KeyCode::Equal;
+
+/// The highest octave keyjazz reaches into.
+const MAX_OCTAVE: u8 = 10;
+
+/// The size of a white key, in display pixels.
+const WHITE_KEY_SIZE: Vec2 = Vec2::new(16.0, 40.0);
+
+/// The size of a black key, in display pixels.
+const BLACK_KEY_SIZE: Vec2 = Vec2::new(10.0, 24.0);
+
+/// How far below the centre of the display the piano sits.
+const PIANO_OFFSET: f32 = -80.0;
+
+/// Colour of a released white key.
+const WHITE_KEY_COLOUR: Color = Color::srgba(1.0, 1.0, 1.0, 0.25);
+
+/// Colour of a released black key.
+const BLACK_KEY_COLOUR: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+
+/// Colour of a held key.
+const HELD_KEY_COLOUR: Color = Color::srgba(1.0, 1.0, 1.0, 0.6);
+
+/// The semitones of the white keys within an octave.
+const WHITE_KEYS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+/// The semitones of the black keys, with the white key each one follows.
+const BLACK_KEYS: [(u8, usize); 5] = [(1, 0), (3, 1), (6, 3), (8, 4), (10, 5)];
+
+/// The keys playing the piano, with their semitone above the base octave.
+/// Like a tracker, the bottom row plays the base octave and the top row the
+/// one above it.
+const KEYBOARD_LAYOUT: [(KeyCode, u8); 30] = [
+    (KeyCode::KeyZ, 0),
+    (KeyCode::KeyS, 1),
+    (KeyCode::KeyX, 2),
+    (KeyCode::KeyD, 3),
+    (KeyCode::KeyC, 4),
+    (KeyCode::KeyV, 5),
+    (KeyCode::KeyG, 6),
+    (KeyCode::KeyB, 7),
+    (KeyCode::KeyH, 8),
+    (KeyCode::KeyN, 9),
+    (KeyCode::KeyJ, 10),
+    (KeyCode::KeyM, 11),
+    (KeyCode::Comma, 12),
+    (KeyCode::KeyL, 13),
+    (KeyCode::Period, 14),
+    (KeyCode::KeyQ, 12),
+    (KeyCode::Digit2, 13),
+    (KeyCode::KeyW, 14),
+    (KeyCode::Digit3, 15),
+    (KeyCode::KeyE, 16),
+    (KeyCode::KeyR, 17),
+    (KeyCode::Digit5, 18),
+    (KeyCode::KeyT, 19),
+    (KeyCode::Digit6, 20),
+    (KeyCode::KeyY, 21),
+    (KeyCode::Digit7, 22),
+    (KeyCode::KeyU, 23),
+    (KeyCode::KeyI, 24),
+    (KeyCode::Digit9, 25),
+    (KeyCode::KeyO, 26),
+];
+
+/// The settings of the on-screen piano.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8Piano {
+    /// The octave the piano's first key plays, C of octave 0 being note 0.
+    pub octave: u8,
+    /// The velocity notes are played at.
+    pub velocity: u8,
+    /// Plays the piano from the computer keyboard. Its keys are then no
+    /// longer sent as the M8's buttons.
+    pub keyboard: bool,
+}
+
+impl Default for M8Piano {
+    fn default() -> Self {
+        Self {
+            octave: 3,
+            velocity: 0x64,
+            keyboard: false,
+        }
+    }
+}
+
+impl M8Piano {
+    /// Whether the key plays the piano rather than the M8's buttons.
+    pub(crate) fn claims(&self, keycode: KeyCode) -> bool {
+        self.keyboard
+            && KEYBOARD_LAYOUT
+                .iter()
+                .any(|&(layout_key, _)| layout_key == keycode)
+    }
+
+    /// The note played by the key `semitone` semitones above the base
+    /// octave, if it is within keyjazz's range.
+    fn note(&self, semitone: u8) -> Option<u8> {
+        let note = self.octave.min(MAX_OCTAVE) * 12 + semitone;
+        (note <= 0x7F).then_some(note)
+    }
+}
+
+/// A key of the on-screen piano, with its semitone within the octave.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct M8PianoKey(pub u8);
+
+/// The notes held on the piano, in the order they were pressed. The M8
+/// plays a single keyjazz note at a time, the last one held.
+#[derive(Resource, Default)]
+struct M8HeldNotes(Vec<u8>);
+
+fn spawn_piano(mut commands: Commands, displays: Query<Entity, Added<M8DisplaySprite>>) {
+    let left = -(WHITE_KEYS.len() as f32 - 1.0) / 2.0 * WHITE_KEY_SIZE.x;
+
+    for display in displays.iter() {
+        commands.entity(display).with_children(|parent| {
+            for (index, semitone) in WHITE_KEYS.into_iter().enumerate() {
+                let x = left + index as f32 * WHITE_KEY_SIZE.x;
+                parent.spawn((
+                    M8PianoKey(semitone),
+                    Sprite {
+                        color: WHITE_KEY_COLOUR,
+                        custom_size: Some(WHITE_KEY_SIZE - Vec2::X),
+                        ..default()
+                    },
+                    Transform::from_xyz(x, PIANO_OFFSET, 2.0),
+                ));
+            }
+
+            // The black keys sit between the white keys, on top of them.
+            let top = PIANO_OFFSET + (WHITE_KEY_SIZE.y - BLACK_KEY_SIZE.y) / 2.0;
+            for (semitone, after) in BLACK_KEYS {
+                let x = left + (after as f32 + 0.5) * WHITE_KEY_SIZE.x;
+                parent.spawn((
+                    M8PianoKey(semitone),
+                    Sprite {
+                        color: BLACK_KEY_COLOUR,
+                        custom_size: Some(BLACK_KEY_SIZE),
+                        ..default()
+                    },
+                    Transform::from_xyz(x, top, 2.1),
+                ));
+            }
+        });
+    }
+}
+
+fn toggle_piano_keyboard(keys: Res<ButtonInput<KeyCode>>, mut piano: ResMut<M8Piano>) {
+    if keys.just_pressed(PIANO_KEYBOARD_TOGGLE_KEY) {
+        piano.keyboard = !piano.keyboard;
+        info!(
+            "Piano keyboard {}",
+            if piano.keyboard { "on" } else { "off" }
+        );
+    }
+
+    if !piano.keyboard {
+        return;
+    }
+
+    if keys.just_pressed(OCTAVE_DOWN_KEY) {
+        piano.octave = piano.octave.saturating_sub(1);
+    }
+    if keys.just_pressed(OCTAVE_UP_KEY) {
+        piano.octave = (piano.octave + 1).min(MAX_OCTAVE);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_piano(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<M8DisplayCamera>>,
+    mut piano_keys: Query<(&M8PianoKey, &GlobalTransform, &mut Sprite)>,
+    piano: Res<M8Piano>,
+    mut held: ResMut<M8HeldNotes>,
+    mut live_input: MessageWriter<M8LiveInput>,
+) {
+    let mut notes: Vec<u8> = Vec::new();
+
+    if piano.keyboard {
+        notes.extend(
+            KEYBOARD_LAYOUT
+                .iter()
+                .filter(|(keycode, _)| keys.pressed(*keycode))
+                .filter_map(|&(_, semitone)| piano.note(semitone)),
+        );
+    }
+
+    let mut pointers: Vec<Vec2> = touches.iter().map(|touch| touch.position()).collect();
+    if mouse.pressed(MouseButton::Left)
+        && let Ok(window) = windows.single()
+        && let Some(cursor) = window.cursor_position()
+    {
+        pointers.push(cursor);
+    }
+
+    if let Ok((camera, camera_transform)) = cameras.single() {
+        for pointer in pointers {
+            let Ok(point) = camera.viewport_to_world_2d(camera_transform, pointer) else {
+                continue;
+            };
+
+            // Only the topmost key under a pointer is pressed, so a black key
+            // wins over the white keys beneath it.
+            let pressed = piano_keys
+                .iter()
+                .filter(|(_, transform, sprite)| {
+                    // Hit test in the key's own space, so rotating the display works.
+                    let local = transform
+                        .affine()
+                        .inverse()
+                        .transform_point3(point.extend(0.0))
+                        .truncate();
+                    let size = sprite.custom_size.unwrap_or_default();
+                    local.abs().cmple(size / 2.0).all()
+                })
+                .max_by(|(_, a, _), (_, b, _)| a.translation().z.total_cmp(&b.translation().z));
+            if let Some((&M8PianoKey(semitone), _, _)) = pressed {
+                notes.extend(piano.note(semitone));
+            }
+        }
+    }
+
+    // Keep the notes still held in the order they were pressed, and add the
+    // new ones after them.
+    let playing = held.0.last().copied();
+    held.0.retain(|note| notes.contains(note));
+    for note in notes {
+        if !held.0.contains(&note) {
+            held.0.push(note);
+        }
+    }
+
+    match held.0.last().copied() {
+        Some(note) if playing != Some(note) => {
+            live_input.write(M8LiveInput(keyjazz_message(note, piano.velocity)));
+        }
+        None if playing.is_some() => {
+            live_input.write(M8LiveInput(KEYJAZZ_STOP.to_vec()));
+        }
+        _ => (),
+    }
+
+    for (&M8PianoKey(semitone), _, mut sprite) in piano_keys.iter_mut() {
+        let is_held = piano
+            .note(semitone)
+            .is_some_and(|note| held.0.contains(&note));
+        sprite.color = match (is_held, WHITE_KEYS.contains(&semitone)) {
+            (true, _) => HELD_KEY_COLOUR,
+            (false, true) => WHITE_KEY_COLOUR,
+            (false, false) => BLACK_KEY_COLOUR,
+        };
+    }
+}
+
+/// This plugin shows an on-screen piano playing the M8's keyjazz. Its keys
+/// can be clicked or touched, and `F8` plays it from the computer keyboard
+/// with `-` and `=` changing the octave.
+pub struct M8PianoPlugin;
+
+impl Plugin for M8PianoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<M8Piano>();
+        app.init_resource::<M8HeldNotes>();
+        app.register_type::<M8Piano>();
+        app.add_systems(
+            Update,
+            (spawn_piano, toggle_piano_keyboard, play_piano).chain(),
+        );
+    }
+}
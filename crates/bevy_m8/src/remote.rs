@@ -81,11 +81,11 @@ fn flush_keyboard_event_queue(
 
 /// Default port with which bevy_m8 remote functionality
 /// runs on.
-const DEFAULT_PORT: u16 = 3030;
+pub(crate) const DEFAULT_PORT: u16 = 3030;
 
 /// Default address with which bevy_m8 remote functionality
 /// runs on.
-const DEFAULT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+pub(crate) const DEFAULT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
 /// This plugin provides remote capabilities.
 pub struct M8RemotePlugin {
@@ -102,8 +102,8 @@ impl Default for M8RemotePlugin {
     }
 }
 
-#[allow(unused)]
 impl M8RemotePlugin {
+    /// Listens on the given address instead of localhost.
     pub fn with_address(self, address: impl Into<IpAddr>) -> Self {
         Self {
             address: address.into(),
@@ -111,6 +111,7 @@ impl M8RemotePlugin {
         }
     }
 
+    /// Listens on the given port.
     pub fn with_port(self, port: u16) -> Self {
         Self { port, ..self }
     }
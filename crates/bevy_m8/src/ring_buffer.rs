@@ -0,0 +1,81 @@
+//! This file provides a lock-free single-producer single-consumer ring buffer
+//! used to pass audio samples between the input and output streams.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+
+/// The storage shared between the two halves of the ring buffer.
+struct Shared {
+    slots: Box<[AtomicU32]>,
+    /// The total number of samples popped so far.
+    head: AtomicUsize,
+    /// The total number of samples pushed so far.
+    tail: AtomicUsize,
+}
+
+impl Shared {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+/// The writing half of the ring buffer.
+pub struct RingProducer(Arc<Shared>);
+
+/// The reading half of the ring buffer.
+pub struct RingConsumer(Arc<Shared>);
+
+/// Creates a ring buffer able to hold `capacity` samples.
+pub fn ring_buffer(capacity: usize) -> (RingProducer, RingConsumer) {
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        slots: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (RingProducer(shared.clone()), RingConsumer(shared))
+}
+
+/// Returns the ring buffer capacity required to hold `latency_ms` worth of
+/// audio twice over, so the buffer can sit half full at the target latency.
+pub fn capacity_for_latency(sample_rate: u32, channels: u16, latency_ms: u32) -> usize {
+    let samples = sample_rate as u64 * channels as u64 * latency_ms as u64 / 1000;
+    (samples as usize).max(1) * 2
+}
+
+impl RingProducer {
+    /// Pushes a sample into the buffer, returning false if it is full.
+    pub fn push(&self, sample: f32) -> bool {
+        let tail = self.0.tail.load(Ordering::Relaxed);
+        let head = self.0.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == self.0.capacity() {
+            return false;
+        }
+
+        self.0.slots[tail % self.0.capacity()].store(sample.to_bits(), Ordering::Relaxed);
+        self.0.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Returns the maximum number of samples the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+}
+
+impl RingConsumer {
+    /// Pops the oldest sample from the buffer, if there is one.
+    pub fn pop(&self) -> Option<f32> {
+        let head = self.0.head.load(Ordering::Relaxed);
+        let tail = self.0.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let sample = f32::from_bits(self.0.slots[head % self.0.capacity()].load(Ordering::Relaxed));
+        self.0.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(sample)
+    }
+}
@@ -0,0 +1,123 @@
+//! This file provides rotation of the presented display, for monitors
+//! mounted in portrait.
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{camera::ScalingMode, prelude::*};
+
+use crate::{
+    M8UpdateSystems,
+    dimensions::M8DisplayDimensions,
+    display::{M8DisplayCamera, M8DisplaySprite},
+};
+
+/// Key which cycles through the rotations.
+const ROTATION_CYCLE_KEY: KeyCode = KeyCode::F4;
+
+/// How far the display is rotated clockwise. Only the presented sprite
+/// is rotated, the framebuffer and zoom regions are left untouched.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Resource)]
+pub enum M8DisplayRotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl M8DisplayRotation {
+    /// The number of clockwise quarter turns.
+    pub fn quarter_turns(self) -> u32 {
+        match self {
+            M8DisplayRotation::Deg0 => 0,
+            M8DisplayRotation::Deg90 => 1,
+            M8DisplayRotation::Deg180 => 2,
+            M8DisplayRotation::Deg270 => 3,
+        }
+    }
+
+    /// The next rotation, a quarter turn further clockwise.
+    pub fn next(self) -> Self {
+        match self {
+            M8DisplayRotation::Deg0 => M8DisplayRotation::Deg90,
+            M8DisplayRotation::Deg90 => M8DisplayRotation::Deg180,
+            M8DisplayRotation::Deg180 => M8DisplayRotation::Deg270,
+            M8DisplayRotation::Deg270 => M8DisplayRotation::Deg0,
+        }
+    }
+
+    /// Returns true if the width and height of the display are swapped.
+    pub fn swaps_axes(self) -> bool {
+        self.quarter_turns() % 2 == 1
+    }
+
+    /// The rotation applied to the display sprite.
+    pub fn quat(self) -> Quat {
+        // Positive angles turn counter-clockwise.
+        Quat::from_rotation_z(-FRAC_PI_2 * self.quarter_turns() as f32)
+    }
+
+    /// Returns the size the display takes up on screen once rotated.
+    pub fn rotate_size(self, size: Vec2) -> Vec2 {
+        if self.swaps_axes() { size.yx() } else { size }
+    }
+}
+
+fn rotation_input(keys: Res<ButtonInput<KeyCode>>, mut rotation: ResMut<M8DisplayRotation>) {
+    if keys.just_pressed(ROTATION_CYCLE_KEY) {
+        *rotation = rotation.next();
+    }
+}
+
+/// Turns the display sprite and fits the camera to the turned display. The
+/// zoom plugin refits the camera after it when a region is zoomed in on.
+pub(crate) fn apply_rotation(
+    rotation: Res<M8DisplayRotation>,
+    dimensions: Res<M8DisplayDimensions>,
+    mut sprites: Query<&mut Transform, With<M8DisplaySprite>>,
+    mut cameras: Query<&mut Projection, With<M8DisplayCamera>>,
+) {
+    for mut transform in sprites.iter_mut() {
+        transform.rotation = rotation.quat();
+    }
+
+    let size = rotation.rotate_size(dimensions.size().as_vec2());
+    for mut projection in cameras.iter_mut() {
+        if let Projection::Orthographic(orthographic) = &mut *projection {
+            orthographic.scaling_mode = ScalingMode::Fixed {
+                width: size.x,
+                height: size.y,
+            };
+        }
+    }
+}
+
+/// This plugin provides the display rotation. It works on its own, the
+/// zoom plugin keeping zoomed in regions turned too.
+#[derive(Default)]
+pub struct M8RotationPlugin {
+    pub rotation: M8DisplayRotation,
+}
+
+impl Plugin for M8RotationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.rotation);
+        app.register_type::<M8DisplayRotation>();
+        app.add_systems(
+            Update,
+            (
+                rotation_input,
+                // The display resizes the camera for a new model while
+                // rendering, so the turned size is reapplied after it.
+                apply_rotation.after(M8UpdateSystems::DisplayRender).run_if(
+                    resource_exists::<M8DisplayDimensions>.and(
+                        resource_changed::<M8DisplayRotation>
+                            .or(resource_changed::<M8DisplayDimensions>),
+                    ),
+                ),
+            )
+                .chain(),
+        );
+    }
+}
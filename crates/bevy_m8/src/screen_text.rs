@@ -0,0 +1,108 @@
+//! This file assembles the characters drawn by the M8 into lines of text,
+//! for screen readers or for scraping what the screen shows.
+
+use bevy::prelude::*;
+
+use crate::{
+    M8LoadingState, M8UpdateSystems,
+    decoder::{CELL_SIZE, M8Command, M8DeviceInfo},
+    dimensions::M8DisplayDimensions,
+    serial::{M8Commands, m8_screen_changed},
+};
+
+/// The text on the M8's screen, from the characters most recently drawn in
+/// each character cell. Cells without text hold spaces.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8ScreenText {
+    /// The rows of character cells, from the top.
+    pub lines: Vec<String>,
+}
+
+impl M8ScreenText {
+    /// The screen as a single string, without trailing spaces or empty
+    /// lines at the bottom.
+    pub fn text(&self) -> String {
+        let lines: Vec<&str> = self.lines.iter().map(|line| line.trim_end()).collect();
+        let len = lines
+            .iter()
+            .rposition(|line| !line.is_empty())
+            .map_or(0, |last| last + 1);
+        lines[..len].join("\n")
+    }
+}
+
+/// The character shown for a byte drawn by the M8.
+fn cell_char(c: u8) -> char {
+    if c.is_ascii_graphic() {
+        char::from(c)
+    } else {
+        ' '
+    }
+}
+
+/// Writes the characters drawn this frame into their cells, and clears the
+/// cells painted over by rectangles.
+fn track_screen_text(
+    commands: Res<M8Commands>,
+    dimensions: Option<Res<M8DisplayDimensions>>,
+    device_info: Option<Res<M8DeviceInfo>>,
+    mut text: ResMut<M8ScreenText>,
+) {
+    // Without the display the screen keeps the size of the original M8.
+    let dimensions = dimensions.map(|dimensions| *dimensions).unwrap_or_default();
+    let cell_size = device_info.map_or(CELL_SIZE, |info| info.cell_size());
+    let grid = dimensions.size() / cell_size;
+
+    let mut cells: Vec<Vec<char>> = text
+        .lines
+        .iter()
+        .map(|line| line.chars().collect())
+        .collect();
+    cells.resize(grid.y as usize, Vec::new());
+    for row in cells.iter_mut() {
+        row.resize(grid.x as usize, ' ');
+    }
+
+    for command in commands.0.iter() {
+        match *command {
+            M8Command::DrawCharacter { c, pos, .. } => {
+                let cell = pos.as_uvec2() / cell_size;
+                if cell.cmplt(grid).all() {
+                    cells[cell.y as usize][cell.x as usize] = cell_char(c);
+                }
+            }
+            M8Command::DrawRectangle { pos, size, .. } => {
+                // Only cells the rectangle covers entirely lose their text.
+                let min = (pos.as_uvec2() + cell_size - UVec2::ONE) / cell_size;
+                let max = ((pos.as_uvec2() + size.as_uvec2()) / cell_size).min(grid);
+                for y in min.y..max.y {
+                    for x in min.x..max.x {
+                        cells[y as usize][x as usize] = ' ';
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    text.set_if_neq(M8ScreenText {
+        lines: cells.into_iter().map(String::from_iter).collect(),
+    });
+}
+
+/// This plugin keeps the [M8ScreenText] up to date.
+pub struct M8ScreenTextPlugin;
+
+impl Plugin for M8ScreenTextPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<M8ScreenText>();
+        app.register_type::<M8ScreenText>();
+        app.add_systems(
+            Update,
+            track_screen_text
+                .after(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running).and(m8_screen_changed)),
+        );
+    }
+}
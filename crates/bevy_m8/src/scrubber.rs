@@ -0,0 +1,168 @@
+//! This file provides a scrubber which holds back the decoded commands and
+//! releases them one at a time, to watch the display build up while
+//! debugging a capture.
+
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::{
+    M8LoadingState, M8UpdateSystems,
+    decoder::M8Command,
+    serial::{M8Commands, M8ScreenState, m8_connected, m8_decode_paused, receive_commands},
+};
+
+/// Key which plays or pauses the scrubber.
+const SCRUBBER_PLAY_KEY: KeyCode = KeyCode::F9;
+
+/// Key which releases a single command and pauses the scrubber.
+const SCRUBBER_STEP_KEY: KeyCode = KeyCode::F10;
+
+/// How long each command is shown for while playing by default.
+const DEFAULT_SCRUBBER_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The state of the scrubber. It starts out paused, so nothing is drawn
+/// until it is stepped or played.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct M8Scrubber {
+    /// Releases a command every `interval` while set, otherwise only when
+    /// stepped.
+    pub playing: bool,
+    /// How long each command is shown for while playing.
+    pub interval: Duration,
+    #[reflect(ignore)]
+    pending: VecDeque<M8Command>,
+    /// Time since the last command was released while playing.
+    #[reflect(ignore)]
+    elapsed: Duration,
+}
+
+impl Default for M8Scrubber {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            interval: DEFAULT_SCRUBBER_INTERVAL,
+            pending: VecDeque::new(),
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl M8Scrubber {
+    /// The commands decoded but not released yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The next command to be released.
+    pub fn next(&self) -> Option<&M8Command> {
+        self.pending.front()
+    }
+}
+
+/// Controls the [M8Scrubber].
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum M8ScrubberCommand {
+    Play,
+    Pause,
+    /// Releases the next command and pauses the scrubber.
+    Step,
+}
+
+fn scrubber_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    scrubber: Res<M8Scrubber>,
+    mut commands: MessageWriter<M8ScrubberCommand>,
+) {
+    if keys.just_pressed(SCRUBBER_PLAY_KEY) {
+        commands.write(if scrubber.playing {
+            M8ScrubberCommand::Pause
+        } else {
+            M8ScrubberCommand::Play
+        });
+    }
+
+    if keys.just_pressed(SCRUBBER_STEP_KEY) {
+        commands.write(M8ScrubberCommand::Step);
+    }
+}
+
+/// Queues the commands decoded this frame and hands back the ones due, in
+/// their place.
+fn scrub_commands(
+    time: Res<Time>,
+    mut scrubber: ResMut<M8Scrubber>,
+    mut commands: ResMut<M8Commands>,
+    mut screen: ResMut<M8ScreenState>,
+    mut controls: MessageReader<M8ScrubberCommand>,
+) {
+    let mut steps = 0;
+    let mut stepped = false;
+    for control in controls.read() {
+        match control {
+            M8ScrubberCommand::Play => {
+                scrubber.playing = true;
+                scrubber.elapsed = Duration::ZERO;
+            }
+            M8ScrubberCommand::Pause => scrubber.playing = false,
+            M8ScrubberCommand::Step => {
+                scrubber.playing = false;
+                stepped = true;
+                steps += 1;
+            }
+        }
+    }
+
+    let scrubber = &mut *scrubber;
+    scrubber.pending.extend(commands.0.drain(..));
+    if scrubber.playing {
+        scrubber.elapsed += time.delta();
+        while scrubber.elapsed >= scrubber.interval && steps < scrubber.pending.len() {
+            scrubber.elapsed -= scrubber.interval;
+            steps += 1;
+        }
+        // Don't save up time while there is nothing to release, which would
+        // release a burst of commands once more arrive.
+        if steps >= scrubber.pending.len() {
+            scrubber.elapsed = scrubber.elapsed.min(scrubber.interval);
+        }
+    }
+
+    let steps = steps.min(scrubber.pending.len());
+    commands.0.extend(scrubber.pending.drain(..steps));
+    if stepped {
+        for command in commands.0.iter() {
+            info!("{} ({} pending)", command, scrubber.pending.len());
+        }
+    }
+
+    screen.set_if_neq(M8ScreenState::from_commands(&commands.0));
+}
+
+/// This plugin holds back the decoded commands so they can be stepped
+/// through one at a time, typically while playing back a capture. `F9`
+/// plays or pauses the scrubber and `F10` steps a single command.
+pub struct M8ScrubberPlugin;
+
+impl Plugin for M8ScrubberPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<M8Scrubber>();
+        app.register_type::<M8Scrubber>();
+        app.add_message::<M8ScrubberCommand>();
+        app.add_systems(
+            Update,
+            (
+                scrubber_input.before(scrub_commands),
+                scrub_commands
+                    .in_set(M8UpdateSystems::Decode)
+                    .after(receive_commands)
+                    .run_if(
+                        in_state(M8LoadingState::Running)
+                            .and(m8_connected)
+                            .and(not(m8_decode_paused)),
+                    ),
+            ),
+        );
+    }
+}
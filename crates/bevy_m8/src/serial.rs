@@ -1,122 +1,1385 @@
 //! The Dirtywave M8 serialport interaction API.
 
-use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*};
-use crossbeam_channel::{Receiver, Sender, unbounded};
+use bevy::{diagnostic::LogDiagnosticsPlugin, platform::time::Instant, prelude::*};
+use crossbeam_channel::{Receiver, Sender, bounded, unbounded};
+use std::{
+    collections::HashSet,
+    fmt, io,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{
+    M8HandshakeState, M8LoadingState, M8UpdateSystems,
+    capture::M8Recorder,
+    configure_update_systems,
+    decoder::{CommandDecoder, M8Command, M8DeviceInfo, M8DeviceInfoChanged, M8Ready, SlipDecoder},
+    dimensions::M8DisplayDimensions,
+    input::{M8Button, send_input},
+    keymap::M8KeyMap,
+    macros::{M8LiveInput, forward_live_input},
+    mock::M8MockConnection,
+    transport::M8Transport,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::capture::M8Playback;
+#[cfg(not(target_arch = "wasm32"))]
 use serialport::SerialPortType;
-use std::{thread, time::Duration};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+#[cfg(target_arch = "wasm32")]
+use crate::web_serial::M8WebSerial;
 
-use crate::decoder::{CommandDecoder, M8Command, SlipDecoder};
+/// Key which pauses or resumes decoding, unless it's bound to a button.
+const DECODE_PAUSE_KEY: KeyCode = KeyCode::F11;
 
 /// The maximum amount of bytes to read from the serial device in one pass.
 const SERIAL_READ_SIZE: usize = 1024;
 
 // M8 Constants
+#[cfg(not(target_arch = "wasm32"))]
 const M8_VID: u16 = 0x16C0;
+#[cfg(not(target_arch = "wasm32"))]
 const M8_PID: u16 = 0x048A;
-const BAUD_RATE: u32 = 115_200;
+const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+/// The delay before the first retry when the port fails to open.
+#[cfg(not(target_arch = "wasm32"))]
+const OPEN_RETRY_MIN_DELAY: Duration = Duration::from_millis(250);
+
+/// The longest delay between attempts to open the port.
+#[cfg(not(target_arch = "wasm32"))]
+const OPEN_RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// How long empty reads are counted before deciding whether the M8 is gone.
+const SILENT_READ_WINDOW: Duration = Duration::from_secs(1);
+
+/// The number of empty reads within [SILENT_READ_WINDOW] after which the
+/// M8 counts as unplugged. Waiting on a port which has no data yet times
+/// out far less often, while some platforms return empty reads immediately
+/// and forever once the device is gone.
+const SILENT_READ_LIMIT: u32 = 1000;
+
+/// How often a failed write is retried by default before it is reported.
+const DEFAULT_WRITE_RETRIES: u32 = 3;
+
+/// How long bytes may arrive without completing a packet before the SLIP
+/// decoder is reset, by default.
+const DEFAULT_RESYNC_AFTER: Duration = Duration::from_millis(500);
+
+/// The bytes which must arrive without completing a packet before the SLIP
+/// decoder is reset, more than the longest packet the M8 sends with every
+/// byte escaped. Counting bytes rather than time alone ties the reset to
+/// the throughput, so a slow stream isn't mistaken for a stuck one.
+const RESYNC_MIN_BYTES: usize = 1024;
 
-/// Represents the connection to the M8.
-#[derive(Resource)]
+/// The pause before retrying a failed write, short enough not to hold up
+/// the reads for long.
+#[cfg(not(target_arch = "wasm32"))]
+const WRITE_RETRY_DELAY: Duration = Duration::from_millis(2);
+
+/// The highest keyjazz velocity the M8 accepts.
+const MAX_KEYJAZZ_VELOCITY: u8 = 0x7F;
+
+/// Stops the note played through keyjazz.
+pub(crate) const KEYJAZZ_STOP: [u8; 2] = [b'K', 0xFF];
+
+/// Builds the keyjazz command playing `note` at `velocity`.
+pub(crate) fn keyjazz_message(note: u8, velocity: u8) -> Vec<u8> {
+    vec![b'K', note, velocity.min(MAX_KEYJAZZ_VELOCITY)]
+}
+
+/// How long the serial throughput is measured over.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Represents the connection to the M8. Any `M8Device`
+/// has one of its own as a component.
+#[derive(Resource, Component)]
 pub struct M8Connection {
-    pub rx: Receiver<M8Command>,
+    /// Read by the decode stage, use [M8Commands] to see the commands.
+    pub(crate) rx: Receiver<M8Command>,
+    /// Sends raw bytes to the M8.
     pub tx: Sender<Vec<u8>>,
+    errors: Receiver<M8ConnectionError>,
+    shared: Arc<M8LinkShared>,
+    /// The most commands kept in [M8Commands] each frame, unbounded if unset.
+    max_commands: Option<usize>,
+}
+
+/// The commands decoded from the M8 during the current frame. Any
+/// `M8Device` has its own as a component.
+#[derive(Resource, Component, Debug, Default, Clone, PartialEq, Reflect)]
+#[reflect(Resource, Component)]
+pub struct M8Commands(pub Vec<M8Command>);
+
+/// A complete frame sent by the M8, with the SLIP framing removed but not
+/// decoded into a command, for forwarding the stream elsewhere. Only sent
+/// when [M8SerialPlugin::raw_frames] is set.
+#[derive(Message, Debug, Clone, PartialEq, Eq)]
+pub struct M8RawFrame(pub Vec<u8>);
+
+/// Triggered for every command decoded from the M8, in the order received,
+/// for observers reacting to particular commands.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct M8CommandEvent(pub M8Command);
+
+/// Triggered once per frame with every command decoded in it, after their
+/// [M8CommandEvent]s, for observers applying them together. The commands
+/// are in the order the M8 sent them.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct M8FrameEvent {
+    pub commands: Vec<M8Command>,
+}
+
+/// The rate at which bytes are read from the M8, measured over the last second.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct M8SerialThroughput {
+    pub bytes_per_sec: f32,
+}
+
+/// A snapshot of the connection, refreshed every frame so it can
+/// be watched in inspectors or polled over the remote protocol.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8ConnectionStatus {
+    pub connected: bool,
+    /// The port the M8 was last opened on, if it has a name.
+    pub port: Option<String>,
+    /// The last problem reported with the connection, even if it has
+    /// since recovered.
+    pub last_error: Option<String>,
+    /// The total number of bytes read from the M8.
+    pub bytes_read: u64,
+    /// The total number of packets which couldn't be decoded.
+    pub decode_errors: u64,
+}
+
+/// Describes how the commands decoded this frame affect the screen.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8ScreenState {
+    /// Whether any draw command was decoded this frame.
+    pub drawn: bool,
+    /// Whether the only draw commands decoded this frame were waveforms.
+    pub waveform_only: bool,
+}
+
+impl M8ScreenState {
+    /// Describes how the given commands affect the screen.
+    pub fn from_commands(commands: &[M8Command]) -> Self {
+        let waveform = commands
+            .iter()
+            .any(|command| matches!(command, M8Command::DrawOscilloscopeWaveform { .. }));
+        let other = commands.iter().any(|command| {
+            matches!(
+                command,
+                M8Command::DrawRectangle { .. } | M8Command::DrawCharacter { .. }
+            )
+        });
+
+        Self {
+            drawn: waveform || other,
+            waveform_only: waveform && !other,
+        }
+    }
+}
+
+/// Run condition which is true when the screen changed this frame.
+pub fn m8_screen_changed(state: Option<Res<M8ScreenState>>) -> bool {
+    state.is_some_and(|state| state.drawn)
+}
+
+/// Holds back the commands decoded from the M8 while set, freezing the
+/// screen without disconnecting. The commands keep buffering and are
+/// handled once decoding resumes.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8DecodePaused(pub bool);
+
+/// Run condition which is true while decoding is paused.
+pub fn m8_decode_paused(paused: Option<Res<M8DecodePaused>>) -> bool {
+    paused.is_some_and(|paused| paused.0)
+}
+
+fn toggle_decode_pause(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_map: Option<Res<M8KeyMap>>,
+    mut paused: ResMut<M8DecodePaused>,
+) {
+    if keys.just_pressed(DECODE_PAUSE_KEY)
+        && !key_map.is_some_and(|key_map| key_map.is_bound(DECODE_PAUSE_KEY))
+    {
+        paused.0 = !paused.0;
+        info!("{} decoding", if paused.0 { "Paused" } else { "Resumed" });
+    }
+}
+
+/// Run condition which is true while a device is connected.
+pub fn m8_connected(connection: Option<Res<M8Connection>>) -> bool {
+    connection.is_some_and(|connection| connection.is_connected())
 }
 
 /// Errors that may occur when trying to find or connect
 /// to a M8 device.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum M8ConnectionError {
     NoDeviceFound,
+    /// The port is held by another program.
+    PortBusy(String),
+    /// The user lacks the permissions to open the port.
+    PermissionDenied(String),
     SerialPort(String),
+    /// The capture file to play back couldn't be opened.
+    Playback(String),
 }
-/// This plugin provides the capabilities required
-/// communicate with the M8 via it's serial port.
-#[derive(Debug, Default)]
-pub struct M8SerialPlugin {
-    pub preferred_device: Option<String>,
+
+impl M8ConnectionError {
+    /// Returns a suggestion for how the user can resolve the error.
+    pub fn remedy(&self) -> &'static str {
+        match self {
+            M8ConnectionError::NoDeviceFound => "Make sure the M8 is connected over USB.",
+            M8ConnectionError::PortBusy(_) => {
+                "Close any other program using the M8 (such as m8c), it will be picked up once released."
+            }
+            M8ConnectionError::PermissionDenied(_) => {
+                "Add your user to the group owning the port (usually dialout or uucp) and log in again."
+            }
+            M8ConnectionError::SerialPort(_) => "Try reconnecting the M8.",
+            M8ConnectionError::Playback(_) => "Check the path of the capture file.",
+        }
+    }
 }
 
-impl Plugin for M8SerialPlugin {
-    fn build(&self, app: &mut App) {
-        let (to_bevy, from_serial) = unbounded::<M8Command>();
-        let (to_serial, from_bevy) = unbounded::<Vec<u8>>();
+impl fmt::Display for M8ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            M8ConnectionError::NoDeviceFound => write!(f, "No M8 device found"),
+            M8ConnectionError::PortBusy(port) => write!(f, "Serial port {} is busy", port),
+            M8ConnectionError::PermissionDenied(port) => {
+                write!(f, "Permission denied opening serial port {}", port)
+            }
+            M8ConnectionError::SerialPort(s) => write!(f, "Serial port error: {}", s),
+            M8ConnectionError::Playback(s) => write!(f, "Failed to open capture file: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for M8ConnectionError {}
+
+/// Sent when connecting to the M8 fails, along with a suggested remedy.
+#[derive(Message, Debug, Clone)]
+pub struct M8ConnectionIssue {
+    pub error: M8ConnectionError,
+    pub remedy: &'static str,
+}
+
+/// Sent when the SLIP decoder was reset after bytes kept arriving without
+/// completing a packet, as happens after the stream was corrupted.
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct M8StreamResync {
+    /// The bytes received since the last packet, which were dropped.
+    pub discarded: usize,
+}
+
+/// Sent when the M8 connects or disconnects.
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct M8ConnectionChanged {
+    pub connected: bool,
+}
+
+/// Send this to drop the current connection and open the M8 again,
+/// optionally on another port. In the browser the enable handshake is
+/// run again instead, as the port is picked by the user.
+#[derive(Message, Debug, Clone, Default, PartialEq, Eq)]
+pub struct M8Reconnect {
+    /// The port to open, the previously preferred port if unset.
+    pub port: Option<String>,
+}
+
+/// Classifies an error from opening the serial port, singling out the
+/// port being held by another program and missing permissions.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn classify_open_error(port_name: &str, error: &serialport::Error) -> M8ConnectionError {
+    let description = error.description.to_lowercase();
+    match error.kind {
+        // Windows reports a port held by another program as access denied.
+        serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) if cfg!(unix) => {
+            M8ConnectionError::PermissionDenied(port_name.into())
+        }
+        serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied)
+        | serialport::ErrorKind::Io(io::ErrorKind::ResourceBusy)
+        | serialport::ErrorKind::NoDevice => M8ConnectionError::PortBusy(port_name.into()),
+        _ if description.contains("busy") || description.contains("access is denied") => {
+            M8ConnectionError::PortBusy(port_name.into())
+        }
+        _ => M8ConnectionError::SerialPort(error.to_string()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn open_port(
+    port_name: &str,
+    baud_rate: u32,
+) -> Result<Box<dyn serialport::SerialPort>, M8ConnectionError> {
+    serialport::new(port_name, baud_rate)
+        .timeout(Duration::from_millis(10))
+        .parity(serialport::Parity::None)
+        .stop_bits(serialport::StopBits::One)
+        .flow_control(serialport::FlowControl::None)
+        .data_bits(serialport::DataBits::Eight)
+        .open()
+        .map_err(|e| classify_open_error(port_name, &e))
+}
+
+/// Finds and opens the M8's port, doubling the delay after every failed
+/// attempt up to [OPEN_RETRY_MAX_DELAY]. Gives up after `attempts` tries,
+/// or never if there is no limit, passing each error to `on_error`. With
+/// `only_preferred` no other M8 is opened while the preferred port is gone.
+#[cfg(not(target_arch = "wasm32"))]
+fn retry_open(
+    preferred: Option<String>,
+    only_preferred: bool,
+    baud_rate: u32,
+    attempts: Option<u32>,
+    base_delay: Duration,
+    mut on_error: impl FnMut(&M8ConnectionError),
+) -> Result<Box<dyn serialport::SerialPort>, M8ConnectionError> {
+    let mut delay = base_delay;
+    let mut attempt = 1;
+    loop {
+        let error = match M8Connection::find_port_name(preferred.clone(), only_preferred)
+            .and_then(|port_name| open_port(&port_name, baud_rate))
+        {
+            Ok(port) => return Ok(port),
+            Err(e) => e,
+        };
+
+        on_error(&error);
+        if attempts.is_some_and(|attempts| attempt >= attempts) {
+            return Err(error);
+        }
 
-        let port_name = M8Connection::find_port_name(self.preferred_device.clone()).unwrap_or_else(
-            |e| match e {
-                M8ConnectionError::NoDeviceFound => panic!("No M8 device found!"),
-                M8ConnectionError::SerialPort(s) => panic!("Serial port error: {}", s),
-            },
+        info!(
+            "Opening the M8 failed on attempt {}: {}, retrying in {:?}",
+            attempt, error, delay
         );
+        thread::sleep(delay);
+        delay = (delay * 2).min(OPEN_RETRY_MAX_DELAY);
+        attempt += 1;
+    }
+}
 
-        thread::spawn(move || {
-            let mut port = serialport::new(port_name, BAUD_RATE)
-                .timeout(Duration::from_millis(10))
-                .parity(serialport::Parity::None)
-                .stop_bits(serialport::StopBits::One)
-                .flow_control(serialport::FlowControl::None)
-                .data_bits(serialport::DataBits::Eight)
-                .open()
-                .expect("Failed to open M8 port");
-
-            if let Err(e) = port.write_all(b"E") {
-                error!("Failed to send Enable command: {:?}", e);
-            } else {
-                info!("Sent Enable command ('E') to M8");
-            }
+/// The most drawn waveforms waiting to be handed back to the decoder.
+const RECYCLED_WAVEFORMS: usize = 8;
+
+/// State shared between the link and bevy.
+#[derive(Debug)]
+struct M8LinkShared {
+    connected: AtomicBool,
+    /// Set from bevy to run the enable handshake again.
+    reenable: AtomicBool,
+    /// The total number of bytes read from the M8.
+    bytes_read: AtomicU64,
+    /// The total number of packets which couldn't be decoded.
+    decode_errors: AtomicU64,
+    /// Drawn waveforms handed back so the decoder can reuse them.
+    recycle_tx: Sender<Vec<u8>>,
+    recycle_rx: Receiver<Vec<u8>>,
+    /// Reconnect requests from bevy, with the port to reconnect to.
+    reconnect_tx: Sender<Option<String>>,
+    reconnect_rx: Receiver<Option<String>>,
+    /// How often a failed write is retried before it is reported.
+    write_retries: AtomicU32,
+    /// Whether every SLIP frame is also passed on undecoded.
+    raw_frames: AtomicBool,
+    raw_tx: Sender<Vec<u8>>,
+    raw_rx: Receiver<Vec<u8>>,
+    /// How long, in milliseconds, bytes may arrive without completing a
+    /// packet before the SLIP decoder is reset.
+    resync_after_ms: AtomicU64,
+    /// The bytes dropped by each reset of the SLIP decoder.
+    resync_tx: Sender<usize>,
+    resync_rx: Receiver<usize>,
+    /// The command bytes decoded, all of them if unset.
+    enabled_commands: Option<HashSet<u8>>,
+    /// The name of the port the link is on.
+    port_name: Mutex<Option<String>>,
+    /// The buttons held through [M8Connection::send_buttons].
+    held_buttons: AtomicU8,
+}
 
-            thread::sleep(Duration::from_millis(60));
+impl M8LinkShared {
+    fn new() -> Self {
+        let (recycle_tx, recycle_rx) = bounded(RECYCLED_WAVEFORMS);
+        let (reconnect_tx, reconnect_rx) = unbounded();
+        let (raw_tx, raw_rx) = unbounded();
+        let (resync_tx, resync_rx) = unbounded();
+        Self {
+            connected: AtomicBool::new(false),
+            reenable: AtomicBool::new(false),
+            bytes_read: AtomicU64::new(0),
+            decode_errors: AtomicU64::new(0),
+            recycle_tx,
+            recycle_rx,
+            reconnect_tx,
+            reconnect_rx,
+            write_retries: AtomicU32::new(DEFAULT_WRITE_RETRIES),
+            raw_frames: AtomicBool::new(false),
+            raw_tx,
+            raw_rx,
+            resync_after_ms: AtomicU64::new(DEFAULT_RESYNC_AFTER.as_millis() as u64),
+            resync_tx,
+            resync_rx,
+            enabled_commands: None,
+            port_name: Mutex::new(None),
+            held_buttons: AtomicU8::new(0),
+        }
+    }
 
-            if let Err(e) = port.write_all(b"R") {
-                error!("Failed to send Reset/Refresh command: {:?}", e);
-            } else {
-                info!("Sent Reset/Refresh command ('R') to M8");
+    /// Locks the port name, recovering it if a thread panicked holding it.
+    fn lock_port_name(&self) -> MutexGuard<'_, Option<String>> {
+        self.port_name.lock().unwrap_or_else(|poisoned| {
+            warn!("The M8's port name was poisoned, recovering it");
+            self.port_name.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    fn set_port_name(&self, name: Option<String>) {
+        *self.lock_port_name() = name;
+    }
+}
+
+/// Shuttles bytes between the M8 and bevy, decoding what the M8 sends.
+struct M8Link<T> {
+    /// Only ever empty while [M8Link::reconnect] reopens the port.
+    port: Option<T>,
+    to_bevy: Sender<M8Command>,
+    from_bevy: Receiver<Vec<u8>>,
+    recorder: Option<M8Recorder>,
+    shared: Arc<M8LinkShared>,
+    slip_decoder: SlipDecoder,
+    command_decoder: CommandDecoder,
+    read_buffer: [u8; SERIAL_READ_SIZE],
+    /// Set when bevy asked to reconnect, to the port requested if any.
+    reconnect_to: Option<Option<String>>,
+    /// Consecutive empty reads since `empty_since`.
+    empty_reads: u32,
+    empty_since: Option<Instant>,
+    /// Bytes read without completing a packet since `unframed_since`.
+    unframed_bytes: usize,
+    unframed_since: Option<Instant>,
+}
+
+impl<T: M8Transport> M8Link<T> {
+    fn new(
+        port: T,
+        to_bevy: Sender<M8Command>,
+        from_bevy: Receiver<Vec<u8>>,
+        recorder: Option<M8Recorder>,
+        shared: Arc<M8LinkShared>,
+    ) -> Self {
+        shared.set_port_name(port.name());
+        let mut command_decoder = CommandDecoder::new();
+        command_decoder.set_enabled_commands(shared.enabled_commands.clone());
+        Self {
+            port: Some(port),
+            to_bevy,
+            from_bevy,
+            recorder,
+            shared,
+            slip_decoder: SlipDecoder::new(),
+            command_decoder,
+            read_buffer: [0u8; SERIAL_READ_SIZE],
+            reconnect_to: None,
+            empty_reads: 0,
+            empty_since: None,
+            unframed_bytes: 0,
+            unframed_since: None,
+        }
+    }
+
+    /// Continues over a freshly opened port, after the previous one went silent.
+    /// This is the only place the SLIP decoder is reset, since whatever part
+    /// of a packet the old port left in it won't be finished.
+    ///
+    /// The old port is closed before `open` runs, as most systems won't open
+    /// a serial port a second time while it's still held.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn reconnect(&mut self, open: impl FnOnce() -> T) {
+        self.port = None;
+        let port = open();
+        self.shared.set_port_name(port.name());
+        self.port = Some(port);
+        self.slip_decoder = SlipDecoder::new();
+        self.command_decoder.forget_unknown_commands();
+        self.empty_reads = 0;
+        self.empty_since = None;
+        self.unframed_bytes = 0;
+        self.unframed_since = None;
+    }
+
+    fn port(&mut self) -> &mut T {
+        self.port
+            .as_mut()
+            .expect("the port is only released while reopening it")
+    }
+
+    /// Counts bytes read without completing a packet, resetting the SLIP
+    /// decoder once too many arrived for too long, as its state must have
+    /// been thrown off by a corrupted stream.
+    fn check_framing(&mut self, count: usize, packets: usize) {
+        if packets > 0 {
+            self.unframed_bytes = 0;
+            self.unframed_since = None;
+            return;
+        }
+
+        let now = Instant::now();
+        let since = *self.unframed_since.get_or_insert(now);
+        self.unframed_bytes += count;
+        let resync_after =
+            Duration::from_millis(self.shared.resync_after_ms.load(Ordering::Relaxed));
+        if self.unframed_bytes < RESYNC_MIN_BYTES || now - since < resync_after {
+            return;
+        }
+
+        warn!(
+            "No packet from the M8 in {} bytes, resetting the SLIP decoder",
+            self.unframed_bytes
+        );
+        self.slip_decoder.reset();
+        self.shared.resync_tx.send(self.unframed_bytes).ok();
+        self.unframed_bytes = 0;
+        self.unframed_since = None;
+    }
+
+    /// Counts an empty read, returning true once so many arrived in a row
+    /// that the M8 must have been unplugged.
+    fn is_silent(&mut self) -> bool {
+        let now = Instant::now();
+        let since = *self.empty_since.get_or_insert(now);
+        self.empty_reads += 1;
+        if now - since < SILENT_READ_WINDOW {
+            return false;
+        }
+
+        let silent = self.empty_reads >= SILENT_READ_LIMIT;
+        self.empty_reads = 0;
+        self.empty_since = Some(now);
+        silent
+    }
+
+    /// Asks the M8 to start sending its display.
+    fn enable(&mut self) {
+        if let Err(e) = self.port().write_all(b"E") {
+            error!("Failed to send Enable command: {:?}", e);
+        } else {
+            info!("Sent Enable command ('E') to M8");
+        }
+
+        // The browser can't block, there the writes are queued in order anyway.
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::sleep(Duration::from_millis(60));
+
+        if let Err(e) = self.port().write_all(b"R") {
+            error!("Failed to send Reset/Refresh command: {:?}", e);
+        } else {
+            info!("Sent Reset/Refresh command ('R') to M8");
+        }
+    }
+
+    /// Performs a single pass of reading, decoding and writing. Returns false
+    /// if the M8 appears to have been unplugged or a reconnect was requested.
+    fn poll(&mut self) -> bool {
+        if let Ok(port) = self.shared.reconnect_rx.try_recv() {
+            info!("Reconnecting to the M8 as requested");
+            self.reconnect_to = Some(port);
+            return false;
+        }
+        if self.shared.reenable.swap(false, Ordering::SeqCst) {
+            self.enable();
+        }
+        for waveform in self.shared.recycle_rx.try_iter() {
+            self.command_decoder.recycle_waveform(waveform);
+        }
+
+        let Some(port) = self.port.as_mut() else {
+            return false;
+        };
+        match port.read(&mut self.read_buffer) {
+            Ok(0) => {
+                if self.is_silent() {
+                    return false;
+                }
             }
+            Ok(count) => {
+                self.empty_reads = 0;
+                self.empty_since = None;
+                self.shared
+                    .bytes_read
+                    .fetch_add(count as u64, Ordering::Relaxed);
+                if let Some(recorder) = &self.recorder
+                    && count > 0
+                {
+                    recorder.record(&self.read_buffer[..count]);
+                }
 
-            let mut slip_decoder = SlipDecoder::new();
-            let mut command_decoder = CommandDecoder::new();
-            let mut read_buffer = [0u8; SERIAL_READ_SIZE];
-
-            loop {
-                match port.read(&mut read_buffer) {
-                    Ok(count) if count > 0 => {
-                        for i in 0..count {
-                            if let Some(packet) = slip_decoder.process_byte(read_buffer[i]) {
-                                if let Some(cmd) = command_decoder.parse(&packet) {
-                                    to_bevy.send(cmd).ok();
-                                }
+                let mut packets = 0;
+                self.slip_decoder
+                    .process(&self.read_buffer[..count], |packet| {
+                        packets += 1;
+                        if self.shared.raw_frames.load(Ordering::Relaxed) {
+                            self.shared.raw_tx.send(packet.to_vec()).ok();
+                        }
+                        match self.command_decoder.parse(packet) {
+                            Some(cmd) => {
+                                self.to_bevy.send(cmd).ok();
+                            }
+                            None if self.command_decoder.skips(packet) => (),
+                            None => {
+                                self.shared.decode_errors.fetch_add(1, Ordering::Relaxed);
                             }
                         }
-                    }
-                    Ok(_) => {}
-                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => (),
-                    Err(e) => error!("Serial Read Error: {:?}", e),
+                    });
+                self.check_framing(count, packets);
+            }
+            Err(e) => error!("Serial Read Error: {:?}", e),
+        }
+        if let Ok(msg) = self.from_bevy.try_recv()
+            && let Err(e) = self.write_with_retries(&msg)
+        {
+            error!("Serial Write Error: {:?}", e);
+        }
+        true
+    }
+
+    /// Writes to the M8, retrying a few times since transient USB write
+    /// errors often succeed straight away on a second attempt.
+    fn write_with_retries(&mut self, data: &[u8]) -> io::Result<()> {
+        let retries = self.shared.write_retries.load(Ordering::Relaxed);
+        let mut attempt = 0;
+        loop {
+            match self.port().write_all(data) {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    debug!(
+                        "Serial write failed ({:?}), retry {} of {}",
+                        e, attempt, retries
+                    );
+                    #[cfg(not(target_arch = "wasm32"))]
+                    thread::sleep(WRITE_RETRY_DELAY);
                 }
-                if let Ok(msg) = from_bevy.try_recv() {
-                    if let Err(e) = port.write_all(&msg) {
-                        error!("Serial Write Error: {:?}", e);
-                    }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Enables the M8 and then shuttles bytes between it and bevy until it goes
+/// silent or a reconnect is requested, returning the request if there was one.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_connection(link: &mut M8Link<impl M8Transport>) -> Option<Option<String>> {
+    link.shared.connected.store(true, Ordering::SeqCst);
+    link.enable();
+    while link.poll() {}
+    link.shared.connected.store(false, Ordering::SeqCst);
+    link.reconnect_to.take()
+}
+
+/// Keeps running a connection which can't be reopened, such as a mock.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_forever(mut link: M8Link<impl M8Transport>) {
+    loop {
+        run_connection(&mut link);
+    }
+}
+
+/// Polls the Web Serial connection, as the browser has no threads to do it on.
+#[cfg(target_arch = "wasm32")]
+fn poll_web_serial(mut link: NonSendMut<M8Link<M8WebSerial>>) {
+    // Polled once a frame, far too rarely to mistake the M8 for unplugged.
+    if !link.poll() && link.reconnect_to.take().is_some() {
+        link.enable();
+    }
+}
+
+/// Sends an [M8StreamResync] for every reset of the SLIP decoder since the
+/// last update.
+fn report_stream_resyncs(
+    connection: Res<M8Connection>,
+    mut resyncs: MessageWriter<M8StreamResync>,
+) {
+    resyncs.write_batch(
+        connection
+            .shared
+            .resync_rx
+            .try_iter()
+            .map(|discarded| M8StreamResync { discarded }),
+    );
+}
+
+/// Sends the frames read since the last update as [M8RawFrame]s.
+fn receive_raw_frames(connection: Res<M8Connection>, mut frames: MessageWriter<M8RawFrame>) {
+    frames.write_batch(connection.shared.raw_rx.try_iter().map(M8RawFrame));
+}
+
+/// Whether any observer watches for the event. Events nothing observes
+/// aren't built, as that would copy every waveform for nothing.
+fn is_observed<E: Event>(world: &World) -> bool {
+    world
+        .event_key::<E>()
+        .and_then(|key| world.observers().try_get_observers(key))
+        .is_some_and(|observers| !observers.global_observers().is_empty())
+}
+
+/// Triggers an [M8CommandEvent] for every command of this frame, then an
+/// [M8FrameEvent] with all of them. The commands are copied once, each
+/// one taken back out of its event to build the frame.
+fn trigger_command_events(world: &mut World) {
+    let each = is_observed::<M8CommandEvent>(world);
+    let frame = is_observed::<M8FrameEvent>(world);
+    let m8_commands = world.resource::<M8Commands>();
+    if m8_commands.0.is_empty() || !(each || frame) {
+        return;
+    }
+
+    let mut commands = m8_commands.0.clone();
+    if each {
+        commands = commands
+            .into_iter()
+            .map(|command| {
+                let mut event = M8CommandEvent(command);
+                world.trigger_ref(&mut event);
+                event.0
+            })
+            .collect();
+    }
+    if frame {
+        world.trigger(M8FrameEvent { commands });
+    }
+}
+
+/// Logs every command of this frame, when [M8SerialPlugin::log_commands]
+/// is set.
+fn log_commands(m8_commands: Res<M8Commands>) {
+    for command in m8_commands.0.iter() {
+        trace!("{}", command);
+    }
+}
+
+/// Hands last frame's waveforms back so their allocations get reused.
+fn recycle_commands(connection: &M8Connection, commands: impl IntoIterator<Item = M8Command>) {
+    for command in commands {
+        if let M8Command::DrawOscilloscopeWaveform { waveform, .. } = command {
+            connection.shared.recycle_tx.try_send(waveform).ok();
+        }
+    }
+}
+
+/// Drops the last decoded commands while no device is connected or decoding
+/// is paused, so they aren't handled again every frame.
+fn clear_commands(
+    connection: Option<Res<M8Connection>>,
+    mut commands: ResMut<M8Commands>,
+    mut screen: ResMut<M8ScreenState>,
+) {
+    match connection {
+        Some(connection) if !commands.0.is_empty() => {
+            recycle_commands(&connection, commands.0.drain(..))
+        }
+        _ => commands.0.clear(),
+    }
+    screen.set_if_neq(M8ScreenState::default());
+}
+
+/// Drops the commands the app pushed last update when there is no
+/// connection to replace them.
+fn clear_pushed_commands(mut commands: ResMut<M8Commands>) {
+    if !commands.0.is_empty() {
+        commands.0.clear();
+    }
+}
+
+/// Whether commands are decoded this update: those of the connected M8,
+/// or the ones pushed by the app when there is no connection.
+fn m8_receiving(connection: Option<Res<M8Connection>>) -> bool {
+    connection.is_none_or(|connection| connection.is_connected())
+}
+
+/// Collects the commands decoded since the last frame, or takes the ones
+/// pushed by the app when there is no connection.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn receive_commands(
+    mut bevy_commands: Commands,
+    connection: Option<Res<M8Connection>>,
+    mut commands: ResMut<M8Commands>,
+    mut screen: ResMut<M8ScreenState>,
+    dimensions: Option<Res<M8DisplayDimensions>>,
+    mut device_info: Option<ResMut<M8DeviceInfo>>,
+    mut device_info_changed: MessageWriter<M8DeviceInfoChanged>,
+    mut ready: MessageWriter<M8Ready>,
+    mut handshake: ResMut<NextState<M8HandshakeState>>,
+) {
+    if let Some(connection) = connection {
+        connection.receive(&mut commands);
+    }
+
+    let mut latest = device_info.as_deref().copied();
+    for command in commands.0.iter() {
+        let &M8Command::SystemInfo {
+            hardware_type,
+            major,
+            minor,
+            patch,
+            font_mode,
+        } = command
+        else {
+            continue;
+        };
+        let new = M8DeviceInfo {
+            hardware_type,
+            major,
+            minor,
+            patch,
+            font_mode,
+        };
+        let old = latest;
+        if old == Some(new) {
+            continue;
+        }
+
+        info!(
+            "M8 firmware {}.{}.{} (hardware type {})",
+            major, minor, patch, hardware_type
+        );
+        latest = Some(new);
+        device_info_changed.write(M8DeviceInfoChanged { old, new });
+    }
+    if let Some(new) = latest {
+        match device_info.as_mut() {
+            Some(device_info) => {
+                device_info.set_if_neq(new);
+            }
+            None => {
+                bevy_commands.insert_resource(new);
+                ready.write(M8Ready { info: new });
+                handshake.set(M8HandshakeState::Ready);
+            }
+        }
+    }
+
+    // Drop draws which start off the framebuffer, they can't be shown. The
+    // display is resized to the reported model before these are drawn.
+    let bounds = match latest.and_then(|info| info.model()) {
+        Some(model) => Some(model.display_size()),
+        None => dimensions.map(|dimensions| dimensions.size()),
+    };
+    if let Some(bounds) = bounds {
+        commands.0.retain(|command| match command {
+            M8Command::DrawRectangle { pos, .. } | M8Command::DrawCharacter { pos, .. } => {
+                let visible = pos.as_uvec2().cmplt(bounds).all();
+                if !visible {
+                    debug!("Dropping draw outside the display: {:?}", command);
                 }
+                visible
             }
+            _ => true,
         });
+    }
+
+    screen.set_if_neq(M8ScreenState::from_commands(&commands.0));
+}
+
+/// The bytes read when the current throughput window started, and how long it has run.
+#[derive(Default)]
+struct ThroughputWindow {
+    bytes_read: u64,
+    elapsed: Duration,
+}
+
+fn measure_throughput(
+    time: Res<Time>,
+    connection: Res<M8Connection>,
+    mut throughput: ResMut<M8SerialThroughput>,
+    mut window: Local<ThroughputWindow>,
+) {
+    window.elapsed += time.delta();
+    if window.elapsed < THROUGHPUT_WINDOW {
+        return;
+    }
+
+    let bytes_read = connection.bytes_read();
+    throughput.bytes_per_sec =
+        (bytes_read - window.bytes_read) as f32 / window.elapsed.as_secs_f32();
+    *window = ThroughputWindow {
+        bytes_read,
+        elapsed: Duration::ZERO,
+    };
+}
+
+fn update_connection_status(
+    connection: Res<M8Connection>,
+    mut status: ResMut<M8ConnectionStatus>,
+    mut changed: MessageWriter<M8ConnectionChanged>,
+) {
+    let connected = connection.is_connected();
+    if connected != status.connected {
+        changed.write(M8ConnectionChanged { connected });
+    }
+    let last_error = status.last_error.clone();
+    status.set_if_neq(M8ConnectionStatus {
+        connected,
+        port: connection.port_name(),
+        last_error,
+        bytes_read: connection.bytes_read(),
+        decode_errors: connection.decode_errors(),
+    });
+}
+
+fn handle_reconnect_requests(
+    mut requests: MessageReader<M8Reconnect>,
+    connection: Res<M8Connection>,
+) {
+    for M8Reconnect { port } in requests.read() {
+        connection.reconnect(port.clone());
+    }
+}
+
+fn report_connection_errors(
+    connection: Res<M8Connection>,
+    mut status: ResMut<M8ConnectionStatus>,
+    mut issues: MessageWriter<M8ConnectionIssue>,
+) {
+    for error in connection.errors() {
+        let remedy = error.remedy();
+        warn!("{}. {}", error, remedy);
+        status.last_error = Some(error.to_string());
+        issues.write(M8ConnectionIssue { error, remedy });
+    }
+}
+
+/// This plugin provides the capabilities required
+/// communicate with the M8 via it's serial port.
+#[derive(Debug, Default)]
+pub struct M8SerialPlugin {
+    /// Unused in the browser, where the user picks the port.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub preferred_device: Option<String>,
+    /// Replays a scripted stream instead of connecting to a device.
+    pub mock: Option<M8MockConnection>,
+    /// Records the raw serial stream to this capture file.
+    pub record_path: Option<PathBuf>,
+    /// Plays back this capture file instead of connecting to a device.
+    pub playback_path: Option<PathBuf>,
+    /// The baud rate of the serial port, 115200 if unset.
+    pub baud_rate: Option<u32>,
+    /// How often a failed write is retried before it is reported, 3 times
+    /// if unset.
+    pub write_retries: Option<u32>,
+    /// How long bytes may keep arriving without completing a packet before
+    /// the SLIP decoder is reset and an [M8StreamResync] sent, half a
+    /// second if unset.
+    pub resync_after: Option<Duration>,
+    /// Only decodes the commands with these bytes, such as
+    /// [SYSTEM_INFO_COMMAND](crate::SYSTEM_INFO_COMMAND), skipping the
+    /// others without building them. Decodes every command if unset.
+    pub enabled_commands: Option<HashSet<u8>>,
+    /// Also sends every frame from the M8 undecoded as an [M8RawFrame].
+    pub raw_frames: bool,
+    /// Keeps at most this many commands each frame, dropping the oldest,
+    /// unbounded if unset.
+    pub max_commands: Option<usize>,
+    /// Logs every decoded command at the trace level, passed on to the
+    /// [M8DecoderPlugin] brought in.
+    pub log_commands: bool,
+}
+
+impl Plugin for M8SerialPlugin {
+    fn build(&self, app: &mut App) {
+        configure_update_systems(app);
+        #[cfg(not(target_arch = "wasm32"))]
+        let connection = self.connect(false);
+
+        #[cfg(target_arch = "wasm32")]
+        let connection = {
+            if self.mock.is_some() || self.playback_path.is_some() {
+                warn!("Mock and playback connections aren't supported in the browser");
+            }
+
+            let (connection, ends) = self.channels();
+            let mut link = M8Link::new(
+                M8WebSerial::connect(self.baud_rate.unwrap_or(DEFAULT_BAUD_RATE)),
+                ends.to_bevy,
+                ends.from_bevy,
+                ends.recorder,
+                ends.shared.clone(),
+            );
+            link.enable();
+            ends.shared.connected.store(true, Ordering::SeqCst);
+            app.insert_non_send_resource(link);
+            app.add_systems(PreUpdate, poll_web_serial);
+            connection
+        };
 
         app.add_plugins(LogDiagnosticsPlugin::default());
-        app.insert_resource(M8Connection {
+        if !app.is_plugin_added::<M8DecoderPlugin>() {
+            app.add_plugins(M8DecoderPlugin {
+                log_commands: self.log_commands,
+            });
+        }
+        app.add_message::<M8ConnectionIssue>();
+        app.add_message::<M8ConnectionChanged>();
+        app.add_message::<M8Reconnect>();
+        app.add_message::<M8RawFrame>();
+        app.add_message::<M8StreamResync>();
+        app.add_message::<M8LiveInput>();
+        app.init_resource::<M8SerialThroughput>();
+        app.init_resource::<M8ConnectionStatus>();
+        app.register_type::<M8SerialThroughput>();
+        app.register_type::<M8ConnectionStatus>();
+        app.add_systems(
+            Update,
+            (
+                report_connection_errors,
+                report_stream_resyncs,
+                handle_reconnect_requests,
+                measure_throughput,
+                update_connection_status,
+                // The buttons sent this update go out in the same update.
+                forward_live_input.after(send_input),
+            ),
+        );
+        if self.raw_frames {
+            app.add_systems(Update, receive_raw_frames.in_set(M8UpdateSystems::Decode));
+        }
+        app.insert_resource(connection);
+    }
+}
+
+/// This plugin decodes the commands received each update into
+/// [M8Commands], keeping track of the [M8DeviceInfo] and
+/// [M8HandshakeState] of the M8 sending them. [M8SerialPlugin] brings it
+/// in unless it was added first and receives the commands from the M8.
+/// Without a connection the commands the app pushes into [M8Commands]
+/// during [M8UpdateSystems::Input], such as ones decoded from a capture
+/// with [M8StreamDecoder](crate::M8StreamDecoder), are decoded instead and
+/// cleared once the update is over.
+#[derive(Debug, Default)]
+pub struct M8DecoderPlugin {
+    /// Logs every decoded command at the trace level.
+    pub log_commands: bool,
+}
+
+impl Plugin for M8DecoderPlugin {
+    fn build(&self, app: &mut App) {
+        configure_update_systems(app);
+        app.add_message::<M8DeviceInfoChanged>();
+        app.add_message::<M8Ready>();
+        app.init_resource::<M8Commands>();
+        app.init_resource::<M8ScreenState>();
+        app.init_resource::<M8DecodePaused>();
+        app.init_state::<M8HandshakeState>();
+        app.register_type::<M8Commands>();
+        app.register_type::<M8ScreenState>();
+        app.register_type::<M8DeviceInfo>();
+        app.register_type::<M8DecodePaused>();
+        app.add_systems(
+            First,
+            clear_pushed_commands.run_if(not(resource_exists::<M8Connection>)),
+        );
+        app.add_systems(
+            Update,
+            toggle_decode_pause
+                .before(M8UpdateSystems::Decode)
+                .run_if(resource_exists::<ButtonInput<KeyCode>>),
+        );
+        app.add_systems(
+            Update,
+            (
+                receive_commands.run_if(m8_receiving.and(not(m8_decode_paused))),
+                clear_commands.run_if(not(m8_receiving).or(m8_decode_paused)),
+            )
+                .in_set(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running)),
+        );
+        app.add_systems(
+            Update,
+            trigger_command_events
+                .after(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running).and(m8_receiving)),
+        );
+        if self.log_commands {
+            app.add_systems(
+                Update,
+                log_commands
+                    .after(M8UpdateSystems::Decode)
+                    .run_if(in_state(M8LoadingState::Running).and(m8_receiving)),
+            );
+        }
+    }
+}
+
+/// The link's ends of the channels of an [M8Connection].
+struct M8LinkEnds {
+    to_bevy: Sender<M8Command>,
+    from_bevy: Receiver<Vec<u8>>,
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    errors: Sender<M8ConnectionError>,
+    shared: Arc<M8LinkShared>,
+    recorder: Option<M8Recorder>,
+}
+
+impl M8SerialPlugin {
+    /// Creates a connection with the plugin's settings, along with the ends
+    /// of its channels for the link to the M8.
+    fn channels(&self) -> (M8Connection, M8LinkEnds) {
+        let (to_bevy, from_serial) = unbounded::<M8Command>();
+        let (to_serial, from_bevy) = unbounded::<Vec<u8>>();
+        let (to_bevy_errors, from_serial_errors) = unbounded::<M8ConnectionError>();
+        let shared = Arc::new(M8LinkShared {
+            enabled_commands: self.enabled_commands.clone(),
+            ..M8LinkShared::new()
+        });
+        shared.raw_frames.store(self.raw_frames, Ordering::Relaxed);
+        if let Some(write_retries) = self.write_retries {
+            shared.write_retries.store(write_retries, Ordering::Relaxed);
+        }
+        if let Some(resync_after) = self.resync_after {
+            shared
+                .resync_after_ms
+                .store(resync_after.as_millis() as u64, Ordering::Relaxed);
+        }
+        let recorder = self.record_path.as_ref().and_then(|path| {
+            M8Recorder::create(path)
+                .inspect(|_| info!("Recording the serial stream to {}", path.display()))
+                .inspect_err(|e| {
+                    error!("Failed to create capture file {}: {:?}", path.display(), e)
+                })
+                .ok()
+        });
+
+        let connection = M8Connection {
             rx: from_serial,
             tx: to_serial,
-        });
+            errors: from_serial_errors,
+            shared: shared.clone(),
+            max_commands: self.max_commands,
+        };
+        let ends = M8LinkEnds {
+            to_bevy,
+            from_bevy,
+            errors: to_bevy_errors,
+            shared,
+            recorder,
+        };
+        (connection, ends)
+    }
+
+    /// Opens the link to the M8 on a thread of its own. With
+    /// `only_preferred` the link waits for the preferred port rather than
+    /// opening any M8 it finds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn connect(&self, only_preferred: bool) -> M8Connection {
+        let (connection, ends) = self.channels();
+        let M8LinkEnds {
+            to_bevy,
+            from_bevy,
+            errors: to_bevy_errors,
+            shared,
+            recorder,
+        } = ends;
+        let baud_rate = self.baud_rate.unwrap_or(DEFAULT_BAUD_RATE);
+
+        if let Some(mock) = self.mock.clone() {
+            info!("Using a mock M8 connection");
+            let link = M8Link::new(mock, to_bevy, from_bevy, recorder, shared);
+            thread::spawn(move || run_forever(link));
+        } else if let Some(path) = &self.playback_path {
+            // A missing capture is reported like a missing device, leaving
+            // the connection closed rather than failing to start.
+            match M8Playback::open(path) {
+                Ok(playback) => {
+                    info!("Playing back the capture {}", path.display());
+                    let link = M8Link::new(playback, to_bevy, from_bevy, recorder, shared);
+                    thread::spawn(move || run_forever(link));
+                }
+                Err(e) => {
+                    let error = M8ConnectionError::Playback(format!("{}: {}", path.display(), e));
+                    to_bevy_errors.send(error).ok();
+                }
+            }
+        } else {
+            let mut preferred = self.preferred_device.clone();
+            thread::spawn(move || {
+                // Keep retrying so the port is grabbed as soon as it becomes available.
+                let open = |preferred: &Option<String>| {
+                    let mut last_error = None;
+                    retry_open(
+                        preferred.clone(),
+                        only_preferred,
+                        baud_rate,
+                        None,
+                        OPEN_RETRY_MIN_DELAY,
+                        |e| {
+                            if last_error.as_ref() != Some(e) {
+                                to_bevy_errors.send(e.clone()).ok();
+                                last_error = Some(e.clone());
+                            }
+                        },
+                    )
+                    .expect("retrying without an attempt limit only returns once opened")
+                };
+
+                let mut link = M8Link::new(open(&preferred), to_bevy, from_bevy, recorder, shared);
+                loop {
+                    match run_connection(&mut link) {
+                        Some(port) => preferred = port.or(preferred),
+                        None => warn!("The M8 stopped responding, reconnecting"),
+                    }
+                    link.reconnect(|| open(&preferred));
+                }
+            });
+        }
+
+        connection
     }
 }
 
 impl M8Connection {
-    fn find_port_name(preferred: Option<String>) -> Result<String, M8ConnectionError> {
+    /// Replaces last frame's commands with the ones decoded since.
+    pub(crate) fn receive(&self, commands: &mut M8Commands) {
+        recycle_commands(self, commands.0.drain(..));
+        commands.0.extend(self.rx.try_iter());
+        if let Some(max_commands) = self.max_commands
+            && commands.0.len() > max_commands
+        {
+            let excess = commands.0.len() - max_commands;
+            warn_once!("Dropping the oldest M8 commands over the limit of {max_commands}");
+            recycle_commands(self, commands.0.drain(..excess));
+        }
+    }
+
+    /// The problems reported by the link since last asked.
+    pub(crate) fn errors(&self) -> impl Iterator<Item = M8ConnectionError> + '_ {
+        self.errors.try_iter()
+    }
+
+    /// Returns whether the connection to the device has been established.
+    pub fn is_connected(&self) -> bool {
+        self.shared.connected.load(Ordering::SeqCst)
+    }
+
+    /// The name of the port the M8 was last opened on, if it has one.
+    pub fn port_name(&self) -> Option<String> {
+        self.shared.lock_port_name().clone()
+    }
+
+    /// The total number of bytes read from the M8.
+    pub fn bytes_read(&self) -> u64 {
+        self.shared.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// The total number of packets from the M8 which couldn't be decoded.
+    pub fn decode_errors(&self) -> u64 {
+        self.shared.decode_errors.load(Ordering::Relaxed)
+    }
+
+    /// Drops the connection and opens the M8 again, on the given port if set.
+    /// Only the port is replaced: this resource and its counters are kept,
+    /// as are settings such as [M8KeyMap](crate::M8KeyMap) and
+    /// [M8Theme](crate::M8Theme), and the M8 is enabled again once opened.
+    pub fn reconnect(&self, port: Option<String>) {
+        let _ = self.shared.reconnect_tx.send(port);
+    }
+
+    /// Asks the M8 to start sending its display again, as done when connecting.
+    pub fn request_enable(&self) {
+        self.shared.reenable.store(true, Ordering::SeqCst);
+    }
+
+    /// Plays a note through keyjazz. Notes range from 0 to 127 like MIDI note
+    /// numbers, velocities from 0 to 127 with higher values clamped.
+    pub fn send_keyjazz(&self, note: u8, velocity: u8) {
+        let _ = self.tx.send(keyjazz_message(note, velocity));
+    }
+
+    /// Stops the note played through keyjazz.
+    pub fn stop_keyjazz(&self) {
+        let _ = self.tx.send(KEYJAZZ_STOP.to_vec());
+    }
+
+    /// Holds down the given buttons until called again, releasing the ones
+    /// held by the last call. They join the other input sources in the
+    /// [M8InputState](crate::M8InputState) sent by
+    /// [M8KeyMapPlugin](crate::M8KeyMapPlugin), so the control byte
+    /// combines them with the keyboard's rather than overriding it.
+    pub fn send_buttons(&self, buttons: &[M8Button]) {
+        self.shared
+            .held_buttons
+            .store(M8Button::mask(buttons), Ordering::Relaxed);
+    }
+
+    /// The mask of the buttons held through [M8Connection::send_buttons].
+    pub(crate) fn held_buttons(&self) -> u8 {
+        self.shared.held_buttons.load(Ordering::Relaxed)
+    }
+
+    /// Lists the names of the serial ports, with the M8s first.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn available_ports() -> Vec<String> {
+        let mut ports = serialport::available_ports().unwrap_or_default();
+        ports.sort_by_key(|port| {
+            !matches!(&port.port_type, SerialPortType::UsbPort(info)
+                if info.vid == M8_VID && info.pid == M8_PID)
+        });
+        ports.into_iter().map(|port| port.port_name).collect()
+    }
+
+    /// Finds and opens the M8's port, preferring the given port name. Failed
+    /// attempts are retried up to `attempts` times in total, waiting
+    /// `base_delay` at first and doubling the wait after every failure.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_with_retry(
+        preferred: Option<String>,
+        attempts: u32,
+        base_delay: Duration,
+    ) -> Result<Box<dyn serialport::SerialPort>, M8ConnectionError> {
+        retry_open(
+            preferred,
+            false,
+            DEFAULT_BAUD_RATE,
+            Some(attempts),
+            base_delay,
+            |_| (),
+        )
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn find_port_name(
+        preferred: Option<String>,
+        only_preferred: bool,
+    ) -> Result<String, M8ConnectionError> {
         let ports = serialport::available_ports()
             .map_err(|e| M8ConnectionError::SerialPort(e.to_string()))?;
 
-        if let Some(pref) = preferred
-            && ports.iter().any(|p| p.port_name == pref)
-        {
-            println!("Returned here!");
-            return Ok(pref.to_string());
+        if let Some(pref) = preferred {
+            // Device files which aren't enumerated, such as the links under
+            // /dev/serial/by-id, can still be opened by their path.
+            if ports.iter().any(|p| p.port_name == pref) || std::path::Path::new(&pref).exists() {
+                return Ok(pref);
+            }
+            if only_preferred {
+                return Err(M8ConnectionError::NoDeviceFound);
+            }
         }
 
         for port in ports {
@@ -124,12 +1387,10 @@ impl M8Connection {
                 && info.vid == M8_VID
                 && info.pid == M8_PID
             {
-                println!("Returned somewhere here!");
                 return Ok(port.port_name);
             }
         }
 
-        println!("Fuck!");
         Err(M8ConnectionError::NoDeviceFound)
     }
 }
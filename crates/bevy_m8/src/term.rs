@@ -0,0 +1,244 @@
+//! This file provides a renderer which draws a downsampled M8 screen to
+//! the terminal, for checking on the M8 without a window.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    M8LoadingState, M8UpdateSystems,
+    color::rgb_from_color,
+    decoder::{CELL_SIZE, M8Command, M8DeviceInfo, Position, Size, waveform_points},
+    dimensions::{DISPLAY_HEIGHT, DISPLAY_WIDTH, M8DisplayDimensions},
+    serial::M8Commands,
+};
+
+/// The longest the terminal goes without an update while the M8 draws.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The display pixels covered by one terminal column.
+const PIXELS_PER_COLUMN: u32 = 4;
+
+/// The display pixels covered by each half of a terminal row.
+const PIXELS_PER_HALF_ROW: u32 = 4;
+
+/// The height of the oscilloscope at the top of the screen.
+const WAVEFORM_HEIGHT: u32 = 16;
+
+/// The upper half block, its foreground is the top pixel and its
+/// background the bottom one.
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// The copy of the M8's screen drawn to the terminal.
+#[derive(Resource)]
+struct M8TermFramebuffer {
+    size: UVec2,
+    pixels: Vec<[u8; 3]>,
+    background: [u8; 3],
+    /// The character cell of the M8's font, from its latest SystemInfo.
+    cell_size: UVec2,
+    dirty: bool,
+}
+
+impl M8TermFramebuffer {
+    fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            pixels: vec![[0; 3]; (size.x * size.y) as usize],
+            background: [0; 3],
+            cell_size: CELL_SIZE,
+            dirty: true,
+        }
+    }
+
+    fn fill(&mut self, min: UVec2, max: UVec2, colour: [u8; 3]) {
+        let max = max.min(self.size);
+        for y in min.y..max.y {
+            let row = (y * self.size.x) as usize;
+            for x in min.x..max.x {
+                self.pixels[row + x as usize] = colour;
+            }
+        }
+    }
+
+    fn draw(&mut self, command: &M8Command) {
+        match *command {
+            M8Command::DrawRectangle { pos, size, colour } => {
+                let colour = rgb_from_color(colour);
+                if pos == Position::ZERO && size.x as u32 >= self.size.x {
+                    self.background = colour;
+                }
+                let (min, max) = bounds(pos, size);
+                self.fill(min, max, colour);
+            }
+            M8Command::DrawCharacter {
+                c,
+                pos,
+                foreground,
+                background,
+            } => {
+                // Glyphs are too small to make out once downsampled, so text
+                // is drawn as a block in its colour inside the cell.
+                let min = pos.as_uvec2();
+                self.fill(min, min + self.cell_size, rgb_from_color(background));
+                if c != b' ' {
+                    self.fill(
+                        min + UVec2::new(1, 2),
+                        min + self.cell_size - UVec2::new(1, 2),
+                        rgb_from_color(foreground),
+                    );
+                }
+            }
+            M8Command::DrawOscilloscopeWaveform {
+                colour,
+                ref waveform,
+            } => {
+                let background = self.background;
+                self.fill(
+                    UVec2::ZERO,
+                    UVec2::new(self.size.x, WAVEFORM_HEIGHT),
+                    background,
+                );
+                let colour = rgb_from_color(colour);
+                for (x, y) in waveform_points(waveform, WAVEFORM_HEIGHT) {
+                    let point = UVec2::new(x, y);
+                    self.fill(point, point + UVec2::ONE, colour);
+                }
+            }
+            M8Command::SystemInfo {
+                hardware_type,
+                major,
+                minor,
+                patch,
+                font_mode,
+            } => {
+                let info = M8DeviceInfo {
+                    hardware_type,
+                    major,
+                    minor,
+                    patch,
+                    font_mode,
+                };
+                self.cell_size = info.cell_size();
+                return;
+            }
+            M8Command::KeyPressState { .. } => return,
+        }
+        self.dirty = true;
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        self.pixels[(y.min(self.size.y - 1) * self.size.x + x.min(self.size.x - 1)) as usize]
+    }
+
+    /// Writes the screen as ANSI coloured half blocks, sampling the middle
+    /// of the pixels each half covers.
+    fn to_ansi(&self) -> String {
+        let columns = self.size.x / PIXELS_PER_COLUMN;
+        let rows = self.size.y / (PIXELS_PER_HALF_ROW * 2);
+        let mut out = String::from("\x1b[H");
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = column * PIXELS_PER_COLUMN + PIXELS_PER_COLUMN / 2;
+                let y = row * PIXELS_PER_HALF_ROW * 2 + PIXELS_PER_HALF_ROW / 2;
+                let [tr, tg, tb] = self.pixel(x, y);
+                let [br, bg, bb] = self.pixel(x, y + PIXELS_PER_HALF_ROW);
+                let _ = write!(
+                    out,
+                    "\x1b[38;2;{tr};{tg};{tb}m\x1b[48;2;{br};{bg};{bb}m{UPPER_HALF_BLOCK}"
+                );
+            }
+            out.push_str("\x1b[0m\r\n");
+        }
+        out
+    }
+}
+
+/// The pixels covered by a rectangle, as its minimum and maximum corners.
+fn bounds(pos: Position, size: Size) -> (UVec2, UVec2) {
+    let min = pos.as_uvec2();
+    (min, min + size.as_uvec2())
+}
+
+fn draw_commands(
+    commands: Res<M8Commands>,
+    dimensions: Option<Res<M8DisplayDimensions>>,
+    mut framebuffer: ResMut<M8TermFramebuffer>,
+) {
+    let size = dimensions.map_or(UVec2::new(DISPLAY_WIDTH, DISPLAY_HEIGHT), |d| d.size());
+    if framebuffer.size != size {
+        *framebuffer = M8TermFramebuffer::new(size);
+    }
+
+    for command in commands.0.iter() {
+        framebuffer.draw(command);
+    }
+}
+
+fn present(
+    time: Res<Time>,
+    mut timer: ResMut<M8TermTimer>,
+    mut framebuffer: ResMut<M8TermFramebuffer>,
+) {
+    if !timer.0.tick(time.delta()).is_finished() || !framebuffer.dirty {
+        return;
+    }
+
+    let mut stdout = io::stdout().lock();
+    if let Err(e) = stdout
+        .write_all(framebuffer.to_ansi().as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        warn!("Failed to draw to the terminal: {:?}", e);
+    }
+    framebuffer.dirty = false;
+}
+
+/// Paces how often the terminal is redrawn.
+#[derive(Resource)]
+struct M8TermTimer(Timer);
+
+/// This plugin draws a downsampled M8 screen to the terminal using ANSI
+/// colours, in half blocks of 4x4 pixels. It reads the decoded commands
+/// and doesn't need a renderer, so it suits headless apps. Logs written
+/// to the terminal will garble the picture.
+pub struct M8TermRenderer {
+    /// The shortest time between two redraws of the terminal.
+    pub interval: Duration,
+}
+
+impl Default for M8TermRenderer {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+impl M8TermRenderer {
+    /// Redraws the terminal at most once every interval.
+    pub fn with_interval(self, interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+impl Plugin for M8TermRenderer {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(M8TermFramebuffer::new(UVec2::new(
+            DISPLAY_WIDTH,
+            DISPLAY_HEIGHT,
+        )));
+        app.insert_resource(M8TermTimer(Timer::new(self.interval, TimerMode::Repeating)));
+        app.add_systems(
+            Update,
+            (draw_commands, present)
+                .chain()
+                .after(M8UpdateSystems::Decode)
+                .run_if(in_state(M8LoadingState::Running)),
+        );
+    }
+}
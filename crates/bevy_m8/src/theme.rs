@@ -0,0 +1,61 @@
+//! This file provides a colour theme which remaps the colours the M8
+//! draws with, without the firmware knowing.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{color::rgb_from_color, serial::M8Connection};
+
+/// Maps colours drawn by the M8 to the colours presented instead. Colours
+/// missing from the palette are drawn unchanged. Rectangles drawn with a
+/// translucent replacement are blended over what is already on screen.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct M8Theme {
+    /// The replacements, keyed by the RGB colour the M8 sent.
+    pub palette: HashMap<[u8; 3], Color>,
+}
+
+impl M8Theme {
+    /// Replaces the given RGB colour sent by the M8.
+    pub fn with_colour(mut self, from: [u8; 3], to: Color) -> Self {
+        self.palette.insert(from, to);
+        self
+    }
+
+    /// Returns the colour to draw in place of the one sent by the M8.
+    pub fn remap(&self, colour: Color) -> Color {
+        self.replacement(colour).unwrap_or(colour)
+    }
+
+    /// Returns the replacement of the colour sent by the M8, if it has one.
+    pub fn replacement(&self, colour: Color) -> Option<Color> {
+        if self.palette.is_empty() {
+            return None;
+        }
+        self.palette.get(&rgb_from_color(colour)).copied()
+    }
+}
+
+/// Asks the M8 to redraw the screen, so a changed theme applies everywhere.
+fn redraw_on_theme_change(connection: Option<Res<M8Connection>>) {
+    if let Some(connection) = connection {
+        let _ = connection.tx.send(vec![b'R']);
+    }
+}
+
+/// This plugin provides the colour theme.
+pub struct M8ThemePlugin;
+
+impl Plugin for M8ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<M8Theme>();
+        app.register_type::<M8Theme>();
+        app.add_systems(
+            Update,
+            redraw_on_theme_change
+                .run_if(resource_changed::<M8Theme>.and(not(resource_added::<M8Theme>))),
+        );
+    }
+}
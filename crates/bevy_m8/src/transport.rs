@@ -0,0 +1,36 @@
+//! This file provides the byte transport the M8 is reached through.
+
+use std::io;
+
+/// A bidirectional byte stream to an M8 device.
+pub trait M8Transport: Send + 'static {
+    /// Reads the bytes currently available into `buf`, returning how many
+    /// were read. Returns `Ok(0)` when no data arrived in time.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Writes all of `data` to the device.
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// The name of the port the device is on, if it has one.
+    fn name(&self) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl M8Transport for Box<dyn serialport::SerialPort> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match io::Read::read(self, buf) {
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Ok(0),
+            result => result,
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        io::Write::write_all(self, data)
+    }
+
+    fn name(&self) -> Option<String> {
+        serialport::SerialPort::name(&**self)
+    }
+}
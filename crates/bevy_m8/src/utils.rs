@@ -8,34 +8,18 @@ use bevy::{
 };
 
 use crate::{
-    display::{
+    input::{
         M8_DOWN, M8_EDIT, M8_KEY_COUNT, M8_LEFT, M8_OPTION, M8_RIGHT, M8_SELECT, M8_START, M8_UP,
+        M8Button,
     },
     keymap::M8KeyMap,
 };
 
 pub fn keycode_to_mask(keycodes: Vec<KeyCode>, key_map: &Res<M8KeyMap>) -> u8 {
-    let mut mask = 0;
-    for &keycode in keycodes.iter() {
-        if keycode == key_map.edit_keycode() {
-            mask |= M8_EDIT;
-        } else if keycode == key_map.option_keycode() {
-            mask |= M8_OPTION;
-        } else if keycode == key_map.right_keycode() {
-            mask |= M8_RIGHT;
-        } else if keycode == key_map.left_keycode() {
-            mask |= M8_LEFT;
-        } else if keycode == key_map.down_keycode() {
-            mask |= M8_DOWN;
-        } else if keycode == key_map.up_keycode() {
-            mask |= M8_UP;
-        } else if keycode == key_map.select_keycode() {
-            mask |= M8_SELECT;
-        } else if keycode == key_map.start_keycode() {
-            mask |= M8_START;
-        }
-    }
-    mask
+    M8Button::ALL
+        .into_iter()
+        .filter(|&button| keycodes.contains(&key_map.keycode(button)))
+        .fold(0, |mask, button| mask | button.bit())
 }
 
 pub fn mask_to_keyboard_input(mask: u8, key_map: &Res<M8KeyMap>) -> Vec<KeyboardInput> {
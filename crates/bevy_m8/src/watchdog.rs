@@ -0,0 +1,163 @@
+//! This file provides a watchdog which revives the display when the M8
+//! stops sending draw commands while still connected.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    M8LoadingState, M8UpdateSystems,
+    decoder::M8Command,
+    scrubber::M8Scrubber,
+    serial::{M8Commands, M8Connection, M8Reconnect, m8_decode_paused},
+};
+
+/// How long the display may go without draw commands before it is reset.
+const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(2);
+
+/// The longest wait between two resets.
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The number of resets tried before the M8 is reconnected.
+const DEFAULT_MAX_RESETS: u32 = 3;
+
+/// Configuration of the display watchdog.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8WatchdogConfig {
+    /// Whether the watchdog is active.
+    pub enabled: bool,
+    /// How long the display may go without draw commands before it is reset.
+    pub stale_after: Duration,
+    /// The longest wait between two resets, which double while they fail.
+    pub max_backoff: Duration,
+    /// The number of resets tried before falling back to an
+    /// [M8Reconnect], if the M8 has gone completely silent.
+    pub max_resets: u32,
+}
+
+impl Default for M8WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stale_after: DEFAULT_STALE_AFTER,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            max_resets: DEFAULT_MAX_RESETS,
+        }
+    }
+}
+
+/// Counts what the watchdog had to do.
+#[derive(Resource, Debug, Default, Clone, Copy, Reflect)]
+#[reflect(Resource)]
+pub struct M8WatchdogStats {
+    /// Reset commands sent because the display went stale.
+    pub resets: u64,
+    /// Times the M8 was reconnected after the resets failed.
+    pub reconnects: u64,
+}
+
+/// Tracks when the M8 was last heard from.
+#[derive(Resource, Debug, Default)]
+struct M8Watchdog {
+    /// When any packet was last decoded.
+    last_packet: Option<Duration>,
+    /// When a draw command was last decoded.
+    last_draw: Option<Duration>,
+    /// The earliest time the next reset may be sent.
+    next_reset: Duration,
+    /// Resets sent since the last draw command.
+    attempts: u32,
+}
+
+/// True while the scrubber holds back every command, which leaves the
+/// display still however well the M8 is drawing.
+fn scrubber_paused(scrubber: Option<Res<M8Scrubber>>) -> bool {
+    scrubber.is_some_and(|scrubber| !scrubber.playing)
+}
+
+fn watch_stream(
+    time: Res<Time>,
+    config: Res<M8WatchdogConfig>,
+    commands: Res<M8Commands>,
+    connection: Res<M8Connection>,
+    mut watchdog: ResMut<M8Watchdog>,
+    mut stats: ResMut<M8WatchdogStats>,
+    mut reconnect: MessageWriter<M8Reconnect>,
+) {
+    let now = time.elapsed();
+    if !connection.is_connected() {
+        *watchdog = M8Watchdog::default();
+        return;
+    }
+
+    // Waveforms count as drawing, so an idle screen with a running
+    // oscilloscope is left alone. Key states only show the M8 is alive.
+    for command in commands.0.iter() {
+        watchdog.last_packet = Some(now);
+        if !matches!(
+            command,
+            M8Command::KeyPressState { .. } | M8Command::SystemInfo { .. }
+        ) {
+            watchdog.last_draw = Some(now);
+            watchdog.next_reset = now;
+            watchdog.attempts = 0;
+        }
+    }
+
+    let last_draw = *watchdog.last_draw.get_or_insert(now);
+    let last_packet = *watchdog.last_packet.get_or_insert(now);
+    if !config.enabled || now - last_draw < config.stale_after || now < watchdog.next_reset {
+        return;
+    }
+
+    if watchdog.attempts >= config.max_resets && now - last_packet >= config.stale_after {
+        // Reopening the port also runs the enable handshake again.
+        warn!("M8 stopped responding, reconnecting");
+        reconnect.write(M8Reconnect::default());
+        stats.reconnects += 1;
+        watchdog.attempts = 0;
+    } else {
+        warn!(
+            "No draw commands from the M8 for {:?}, resetting the display",
+            now - last_draw
+        );
+        let _ = connection.tx.send(vec![b'R']);
+        stats.resets += 1;
+        watchdog.attempts += 1;
+    }
+
+    let backoff = config
+        .stale_after
+        .saturating_mul(1 << watchdog.attempts.min(16))
+        .min(config.max_backoff);
+    watchdog.next_reset = now + backoff;
+}
+
+/// This plugin resets the display when the M8 stops drawing.
+pub struct M8WatchdogPlugin;
+
+impl Plugin for M8WatchdogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<M8WatchdogConfig>();
+        app.init_resource::<M8WatchdogStats>();
+        app.init_resource::<M8Watchdog>();
+        app.register_type::<M8WatchdogConfig>();
+        app.register_type::<M8WatchdogStats>();
+        app.add_message::<M8Reconnect>();
+        app.add_systems(
+            Update,
+            watch_stream
+                // Sees the commands left once the frame is drawn, after
+                // anything such as the scrubber has held some back.
+                .after(M8UpdateSystems::DisplayRender)
+                // Nothing is decoded or let through while paused, which
+                // isn't the M8's fault.
+                .run_if(
+                    in_state(M8LoadingState::Running)
+                        .and(not(m8_decode_paused))
+                        .and(not(scrubber_paused)),
+                ),
+        );
+    }
+}
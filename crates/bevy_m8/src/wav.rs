@@ -0,0 +1,101 @@
+//! This file provides recordings of the M8's audio as WAV files.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    thread::{self, JoinHandle},
+};
+
+use bevy::log::error;
+use crossbeam_channel::{Sender, unbounded};
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+/// Records interleaved audio samples to a WAV file.
+///
+/// Samples are handed to a background thread which does the writing, so
+/// recording doesn't block the audio callback. The header is brought up to
+/// date whenever the thread catches up, so the file stays playable if the
+/// app dies mid-recording. Dropping the recorder writes the queued samples
+/// and finalizes the file.
+pub struct M8WavRecorder {
+    tx: Option<Sender<Vec<f32>>>,
+    writer: Option<JoinHandle<()>>,
+    spec: WavSpec,
+}
+
+impl M8WavRecorder {
+    /// Creates the WAV file and starts the thread writing to it. The samples
+    /// recorded must hold `channels` interleaved channels at `sample_rate`.
+    pub fn create(path: impl AsRef<Path>, channels: u16, sample_rate: u32) -> hound::Result<Self> {
+        let spec = WavSpec {
+            channels: channels.max(1),
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(path, spec)?;
+        let (tx, rx) = unbounded::<Vec<f32>>();
+
+        let writer = thread::spawn(move || {
+            for samples in rx.iter() {
+                let mut result = write_samples(&mut writer, &samples);
+                if result.is_ok() && rx.is_empty() {
+                    result = writer.flush();
+                }
+
+                if let Err(e) = result {
+                    error!("Failed to write to the WAV file: {:?}", e);
+                    return;
+                }
+            }
+
+            if let Err(e) = writer.finalize() {
+                error!("Failed to finalize the WAV file: {:?}", e);
+            }
+        });
+
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+            spec,
+        })
+    }
+
+    /// The format of the WAV file.
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Queues interleaved samples to be written.
+    pub fn record(&self, samples: &[f32]) {
+        if let Some(tx) = &self.tx {
+            tx.send(samples.to_vec()).ok();
+        }
+    }
+
+    /// A sender queueing interleaved samples to be written, for the audio
+    /// callback. The file is only finalized once every sender is dropped.
+    pub fn sender(&self) -> Sender<Vec<f32>> {
+        self.tx.clone().expect("the recorder is still running")
+    }
+}
+
+impl Drop for M8WavRecorder {
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+    }
+}
+
+/// Writes the samples, leaving off a trailing partial frame.
+fn write_samples(writer: &mut WavWriter<BufWriter<File>>, samples: &[f32]) -> hound::Result<()> {
+    let channels = usize::from(writer.spec().channels);
+    let whole = samples.len() - samples.len() % channels;
+    for &sample in &samples[..whole] {
+        writer.write_sample(sample)?;
+    }
+    Ok(())
+}
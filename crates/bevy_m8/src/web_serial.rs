@@ -0,0 +1,164 @@
+//! This file provides a Web Serial backend for running in the browser.
+//!
+//! The Web Serial bindings of `web-sys` are unstable, so building for
+//! `wasm32` requires `RUSTFLAGS=--cfg=web_sys_unstable_apis`.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use bevy::log::{error, info, warn};
+use js_sys::{Array, Reflect, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{JsFuture, spawn_local};
+use web_sys::{
+    ReadableStreamDefaultReader, SerialOptions, SerialPort, WritableStreamDefaultWriter,
+};
+
+use crate::transport::M8Transport;
+
+thread_local! {
+    /// The writer of the open port. JavaScript objects can't be sent between
+    /// threads, but the browser runs everything on this one.
+    static WRITER: RefCell<Option<WritableStreamDefaultWriter>> = const { RefCell::new(None) };
+}
+
+/// Bytes waiting to be handed to the decoder or written to the M8.
+#[derive(Default)]
+struct Queues {
+    incoming: VecDeque<u8>,
+    outgoing: Vec<Vec<u8>>,
+    flushing: bool,
+}
+
+/// Locks the queues. A panic while they were locked leaves them poisoned,
+/// but they stay usable, so the connection carries on with them.
+fn lock(queues: &Mutex<Queues>) -> MutexGuard<'_, Queues> {
+    queues.lock().unwrap_or_else(|poisoned| {
+        warn!("The Web Serial queues were poisoned, recovering them");
+        queues.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// A connection to the M8 through the browser's Web Serial API.
+///
+/// The port is streamed from on the browser's event loop, so reads only
+/// return what has already arrived and never block.
+#[derive(Clone, Default)]
+pub struct M8WebSerial {
+    queues: Arc<Mutex<Queues>>,
+}
+
+impl M8WebSerial {
+    /// Opens a serial port granted by the user and starts streaming from it.
+    /// If no port has been granted yet, the browser prompts for one.
+    pub fn connect(baud_rate: u32) -> Self {
+        let web_serial = Self::default();
+        let queues = web_serial.queues.clone();
+        spawn_local(async move {
+            if let Err(e) = stream(queues, baud_rate).await {
+                error!("Web Serial Error: {:?}", e);
+            }
+        });
+        web_serial
+    }
+}
+
+async fn open_port(baud_rate: u32) -> Result<SerialPort, JsValue> {
+    let serial = web_sys::window()
+        .ok_or_else(|| JsValue::from_str("No window available"))?
+        .navigator()
+        .serial();
+
+    // Reuse a port the user has already granted access to.
+    let ports: Array = JsFuture::from(serial.get_ports()).await?.dyn_into()?;
+    let port: SerialPort = if ports.length() > 0 {
+        ports.get(0).dyn_into()?
+    } else {
+        JsFuture::from(serial.request_port()).await?.dyn_into()?
+    };
+
+    JsFuture::from(port.open(&SerialOptions::new(baud_rate))).await?;
+    Ok(port)
+}
+
+async fn stream(queues: Arc<Mutex<Queues>>, baud_rate: u32) -> Result<(), JsValue> {
+    let port = open_port(baud_rate).await?;
+    info!("Opened the M8 over Web Serial");
+
+    let reader: ReadableStreamDefaultReader = port.readable().get_reader().dyn_into()?;
+    WRITER.set(Some(port.writable().get_writer()?));
+    flush(queues.clone());
+
+    loop {
+        let result = JsFuture::from(reader.read()).await?;
+        if Reflect::get(&result, &JsValue::from_str("done"))?
+            .as_bool()
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+
+        let chunk: Uint8Array = Reflect::get(&result, &JsValue::from_str("value"))?.dyn_into()?;
+        lock(&queues).incoming.extend(chunk.to_vec());
+    }
+}
+
+/// Writes the queued bytes to the port in order, unless a flush is
+/// already underway or the port isn't open yet.
+fn flush(queues: Arc<Mutex<Queues>>) {
+    let Some(writer) = WRITER.with_borrow(|writer| writer.clone()) else {
+        return;
+    };
+
+    {
+        let mut queues = lock(&queues);
+        if queues.flushing {
+            return;
+        }
+        queues.flushing = true;
+    }
+
+    spawn_local(async move {
+        loop {
+            let outgoing = {
+                let mut queues = lock(&queues);
+                let outgoing = std::mem::take(&mut queues.outgoing);
+                queues.flushing = !outgoing.is_empty();
+                outgoing
+            };
+
+            if outgoing.is_empty() {
+                return;
+            }
+
+            for data in outgoing {
+                let chunk = Uint8Array::from(data.as_slice());
+                if let Err(e) = JsFuture::from(writer.write_with_chunk(&chunk)).await {
+                    error!("Web Serial Write Error: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+impl M8Transport for M8WebSerial {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut queues = lock(&self.queues);
+        let count = buf.len().min(queues.incoming.len());
+        for (slot, byte) in buf.iter_mut().zip(queues.incoming.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        lock(&self.queues).outgoing.push(data.to_vec());
+        flush(self.queues.clone());
+        Ok(())
+    }
+}
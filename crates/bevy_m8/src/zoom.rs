@@ -0,0 +1,216 @@
+//! This file provides a zoom mode which shows a sub-region of the display.
+
+use bevy::{camera::ScalingMode, prelude::*, window::PrimaryWindow};
+
+use crate::{
+    M8UpdateSystems,
+    dimensions::M8DisplayDimensions,
+    display::{M8DisplayCamera, M8DisplaySprite},
+    keymap::M8KeyMap,
+    rotation::{M8DisplayRotation, apply_rotation},
+};
+
+/// Key which toggles the zoom on and off.
+const ZOOM_TOGGLE_KEY: KeyCode = KeyCode::F2;
+
+/// Key which cycles through the zoom presets.
+const ZOOM_CYCLE_KEY: KeyCode = KeyCode::F3;
+
+/// Key which scales the view up by one step.
+const VIEW_ZOOM_IN_KEY: KeyCode = KeyCode::Equal;
+
+/// Key which scales the view down by one step.
+const VIEW_ZOOM_OUT_KEY: KeyCode = KeyCode::Minus;
+
+/// The largest scale the view keys step up to.
+const MAX_VIEW_SCALE: f32 = 8.0;
+
+/// Controls which region of the display is shown in the window.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub struct M8Zoom {
+    /// Whether only the region is shown.
+    pub enabled: bool,
+    /// The region of the display to show, in display pixels. An empty
+    /// region shows the whole display.
+    pub region: URect,
+}
+
+impl M8Zoom {
+    /// The regions cycled through by the zoom cycle key on a display of
+    /// the given size: the oscilloscope strip along the top, the whole
+    /// screen and the information rows along the bottom.
+    pub fn presets(dimensions: M8DisplayDimensions) -> [URect; 3] {
+        let M8DisplayDimensions { width, height } = dimensions;
+        [
+            URect::new(0, 0, width, height / 10),
+            dimensions.rect(),
+            URect::new(0, height - height / 6, width, height),
+        ]
+    }
+
+    /// Returns the region clamped to a display of the given size, falling
+    /// back to the whole display if nothing of the region is left.
+    pub fn clamped_region(&self, width: u32, height: u32) -> URect {
+        let region = self.region.intersect(URect::new(0, 0, width, height));
+        if region.is_empty() {
+            URect::new(0, 0, width, height)
+        } else {
+            region
+        }
+    }
+}
+
+/// Scales and pans the camera over the shown display, for a close-up of
+/// part of it. Applied every frame on top of the [M8Zoom] region.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct M8DisplayView {
+    /// How far the view is scaled in, 1 showing the whole region.
+    pub scale: f32,
+    /// The point the view is centred on, in display pixels from the centre
+    /// of the shown region.
+    pub offset: Vec2,
+    /// Whether each display pixel covers a whole number of window pixels,
+    /// the scale being rounded down to keep them sharp.
+    pub integer_scaling: bool,
+}
+
+impl Default for M8DisplayView {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: Vec2::ZERO,
+            integer_scaling: false,
+        }
+    }
+}
+
+impl M8DisplayView {
+    /// Returns the offset clamped so that the view stays within a region
+    /// of the given size.
+    pub fn clamped_offset(&self, size: Vec2) -> Vec2 {
+        let slack = size / 2.0 * (1.0 - 1.0 / self.scale.max(1.0));
+        self.offset.clamp(-slack, slack)
+    }
+}
+
+fn zoom_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    dimensions: Res<M8DisplayDimensions>,
+    mut zoom: ResMut<M8Zoom>,
+    mut preset: Local<usize>,
+) {
+    if keys.just_pressed(ZOOM_TOGGLE_KEY) {
+        zoom.enabled = !zoom.enabled;
+    }
+
+    if keys.just_pressed(ZOOM_CYCLE_KEY) {
+        let presets = M8Zoom::presets(*dimensions);
+        *preset = (*preset + 1) % presets.len();
+        zoom.region = presets[*preset];
+        zoom.enabled = true;
+    }
+}
+
+fn view_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_map: Option<Res<M8KeyMap>>,
+    mut view: ResMut<M8DisplayView>,
+) {
+    let bound = |key| {
+        key_map
+            .as_ref()
+            .is_some_and(|key_map| key_map.is_bound(key))
+    };
+    if keys.just_pressed(VIEW_ZOOM_IN_KEY) && !bound(VIEW_ZOOM_IN_KEY) {
+        view.scale = (view.scale.floor() + 1.0).min(MAX_VIEW_SCALE);
+    }
+
+    if keys.just_pressed(VIEW_ZOOM_OUT_KEY) && !bound(VIEW_ZOOM_OUT_KEY) {
+        view.scale = (view.scale.ceil() - 1.0).max(1.0);
+    }
+}
+
+fn apply_zoom(
+    zoom: Res<M8Zoom>,
+    view: Res<M8DisplayView>,
+    rotation: Option<Res<M8DisplayRotation>>,
+    dimensions: Res<M8DisplayDimensions>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut sprites: Query<&mut Sprite, With<M8DisplaySprite>>,
+    mut cameras: Query<(&mut Projection, &mut Transform), With<M8DisplayCamera>>,
+) {
+    let rotation = rotation.as_deref().copied().unwrap_or_default();
+    // The region is in framebuffer space, only its size on screen is rotated
+    // as the rotation plugin turns the sprite.
+    let region = zoom.clamped_region(dimensions.width, dimensions.height);
+    let shown = if zoom.enabled {
+        region
+    } else {
+        dimensions.rect()
+    };
+    let size = rotation.rotate_size(shown.size().as_vec2());
+    let rect = zoom.enabled.then(|| region.as_rect());
+
+    for mut sprite in sprites.iter_mut() {
+        if sprite.rect != rect {
+            sprite.rect = rect;
+        }
+    }
+
+    let mut translation = view.clamped_offset(size);
+    let (scaling_mode, scale) = match windows.single() {
+        Ok(window) if view.integer_scaling => {
+            let physical = window.physical_size().as_vec2();
+            let fit = (physical / size).min_element();
+            let pixels = (fit * view.scale.max(1.0)).floor().max(1.0);
+            // Snaps the corner of the display onto a window pixel, the rest
+            // following as every display pixel is a whole number of them.
+            let corner = physical / 2.0 - (size / 2.0 + translation) * pixels;
+            translation = (physical / 2.0 - corner.round()) / pixels - size / 2.0;
+            (ScalingMode::WindowSize, window.scale_factor() / pixels)
+        }
+        _ if zoom.enabled => (
+            ScalingMode::AutoMin {
+                min_width: size.x,
+                min_height: size.y,
+            },
+            1.0 / view.scale.max(1.0),
+        ),
+        _ => (
+            ScalingMode::Fixed {
+                width: size.x,
+                height: size.y,
+            },
+            1.0 / view.scale.max(1.0),
+        ),
+    };
+
+    for (mut projection, mut transform) in cameras.iter_mut() {
+        if let Projection::Orthographic(orthographic) = &mut *projection {
+            orthographic.scaling_mode = scaling_mode;
+            orthographic.scale = scale;
+        }
+        transform.translation = translation.extend(transform.translation.z);
+    }
+}
+
+/// This plugin provides the display zoom mode and the view over it.
+pub struct M8ZoomPlugin;
+
+impl Plugin for M8ZoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<M8Zoom>();
+        app.init_resource::<M8DisplayView>();
+        app.register_type::<M8Zoom>();
+        app.register_type::<M8DisplayView>();
+        app.add_systems(
+            Update,
+            (zoom_input, view_input, apply_zoom)
+                .chain()
+                .after(M8UpdateSystems::DisplayRender)
+                .after(apply_rotation),
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Checks the ring buffer between the audio streams and the underrun and
+//! overrun counters, without opening any audio device.
+
+#![cfg(feature = "bevy")]
+
+use bevy_m8::{M8AudioStats, capacity_for_latency, ring_buffer};
+
+#[test]
+fn capacity_holds_the_latency_twice_over() {
+    // 20ms of 48kHz stereo is 1920 samples.
+    assert_eq!(capacity_for_latency(48_000, 2, 20), 3840);
+    assert_eq!(capacity_for_latency(44_100, 1, 100), 8820);
+    // Never zero, even without any latency.
+    assert_eq!(capacity_for_latency(48_000, 2, 0), 2);
+}
+
+#[test]
+fn empty_buffer_pops_nothing() {
+    let (producer, consumer) = ring_buffer(4);
+    assert_eq!(producer.capacity(), 4);
+    assert_eq!(consumer.pop(), None);
+
+    producer.push(1.0);
+    assert_eq!(consumer.pop(), Some(1.0));
+    assert_eq!(consumer.pop(), None);
+}
+
+#[test]
+fn full_buffer_rejects_pushes() {
+    let (producer, consumer) = ring_buffer(3);
+    assert!(producer.push(1.0));
+    assert!(producer.push(2.0));
+    assert!(producer.push(3.0));
+    assert!(!producer.push(4.0));
+
+    // The rejected sample didn't overwrite the oldest.
+    assert_eq!(consumer.pop(), Some(1.0));
+    assert!(producer.push(5.0));
+    assert_eq!(consumer.pop(), Some(2.0));
+    assert_eq!(consumer.pop(), Some(3.0));
+    assert_eq!(consumer.pop(), Some(5.0));
+    assert_eq!(consumer.pop(), None);
+}
+
+#[test]
+fn samples_keep_their_order_across_the_wraparound() {
+    let (producer, consumer) = ring_buffer(3);
+    let mut next = 0.0;
+    let mut expected = 0.0;
+    // Every round wraps the indices around the slots again.
+    for _ in 0..10 {
+        for _ in 0..2 {
+            assert!(producer.push(next));
+            next += 1.0;
+        }
+        for _ in 0..2 {
+            assert_eq!(consumer.pop(), Some(expected));
+            expected += 1.0;
+        }
+    }
+    assert_eq!(consumer.pop(), None);
+}
+
+#[test]
+fn capacity_is_at_least_one() {
+    let (producer, consumer) = ring_buffer(0);
+    assert_eq!(producer.capacity(), 1);
+    assert!(producer.push(1.0));
+    assert!(!producer.push(2.0));
+    assert_eq!(consumer.pop(), Some(1.0));
+}
+
+#[test]
+fn overruns_count_blocks_which_did_not_fit() {
+    let stats = M8AudioStats::default();
+    let (producer, consumer) = ring_buffer(4);
+
+    stats.push_input(&producer, &[1.0, 2.0, 3.0]);
+    assert_eq!(stats.overruns(), 0);
+
+    // Only one of these fits, the block counts as one overrun.
+    stats.push_input(&producer, &[4.0, 5.0, 6.0]);
+    assert_eq!(stats.overruns(), 1);
+
+    let mut output = [0.0; 4];
+    stats.pop_output(&consumer, &mut output);
+    assert_eq!(output, [1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(stats.underruns(), 0);
+}
+
+#[test]
+fn underruns_fill_the_rest_with_silence() {
+    let stats = M8AudioStats::default();
+    let (producer, consumer) = ring_buffer(8);
+
+    stats.push_input(&producer, &[0.5, -0.5]);
+    let mut output = [1.0; 4];
+    stats.pop_output(&consumer, &mut output);
+    assert_eq!(output, [0.5, -0.5, 0.0, 0.0]);
+    assert_eq!(stats.underruns(), 1);
+
+    // An empty buffer plays silence and counts again.
+    stats.pop_output(&consumer, &mut output);
+    assert_eq!(output, [0.0; 4]);
+    assert_eq!(stats.underruns(), 2);
+    assert_eq!(stats.overruns(), 0);
+}
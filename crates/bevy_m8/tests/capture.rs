@@ -0,0 +1,126 @@
+//! Replays a capture fixture, both straight through the transport and
+//! through the plugins in place of a device.
+//!
+//! `fixtures/capture.bin` holds the commands below in three frames, 20ms
+//! apart, with the frame boundaries splitting packets.
+
+#![cfg(feature = "bevy")]
+
+use std::{
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_m8::{M8StreamDecoder, M8Transport, prelude::*};
+
+/// When the last frame of the fixture was recorded.
+const LAST_FRAME: Duration = Duration::from_millis(40);
+
+/// How long the app may take to play back the whole fixture.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/capture.bin")
+}
+
+fn expected() -> Vec<M8Command> {
+    let black = Color::srgb_u8(0x00, 0x00, 0x00);
+    let white = Color::srgb_u8(0xFF, 0xFF, 0xFF);
+    vec![
+        M8Command::SystemInfo {
+            hardware_type: 3,
+            major: 6,
+            minor: 0,
+            patch: 0,
+            font_mode: 0,
+        },
+        M8Command::DrawRectangle {
+            pos: Position::new(0, 0),
+            size: Size::new(320, 240),
+            colour: black,
+        },
+        M8Command::DrawCharacter {
+            c: b'M',
+            pos: Position::new(8, 12),
+            foreground: white,
+            background: black,
+        },
+        M8Command::DrawCharacter {
+            c: b'8',
+            pos: Position::new(16, 12),
+            foreground: white,
+            background: black,
+        },
+        // Both bytes of this colour are sent escaped.
+        M8Command::DrawRectangle {
+            pos: Position::new(40, 20),
+            size: Size::new(10, 10),
+            colour: Color::srgb_u8(0xC0, 0xDB, 0x20),
+        },
+        M8Command::DrawOscilloscopeWaveform {
+            colour: Color::srgb_u8(0x00, 0xFF, 0x00),
+            waveform: (0..16).chain((0..16).rev()).collect(),
+        },
+    ]
+}
+
+#[test]
+fn playback_replays_the_fixture_with_its_timing() {
+    let mut playback = M8Playback::open(fixture()).unwrap();
+    let mut decoder = M8StreamDecoder::new();
+    let mut commands = Vec::new();
+    let mut errors = 0;
+    let mut buffer = [0u8; 16];
+
+    let started = Instant::now();
+    loop {
+        let count = playback.read(&mut buffer).unwrap();
+        if count == 0 {
+            break;
+        }
+        errors += decoder.decode(&buffer[..count], |command| commands.push(command.clone()));
+    }
+
+    assert!(started.elapsed() >= LAST_FRAME);
+    assert_eq!(errors, 0);
+    assert_eq!(commands, expected());
+
+    // Once finished, the playback stays idle rather than failing.
+    assert_eq!(playback.read(&mut buffer).unwrap(), 0);
+}
+
+#[test]
+fn playback_is_decoded_in_place_of_a_device() {
+    let plugin = M8Plugin::default().with_playback(fixture());
+    #[cfg(feature = "render")]
+    let plugin = plugin.with_headless(true);
+
+    let mut app = App::new();
+    app.add_plugins(plugin);
+    app.finish();
+    app.cleanup();
+
+    let expected = expected();
+    let mut commands = Vec::new();
+    let started = Instant::now();
+    while commands.len() < expected.len() {
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "only {} of the commands were played back",
+            commands.len()
+        );
+        app.update();
+        commands.extend(app.world().resource::<M8Commands>().0.iter().cloned());
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    assert_eq!(commands, expected);
+    // The state set on the SystemInfo packet is applied on the next update.
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<M8HandshakeState>>(),
+        M8HandshakeState::Ready
+    );
+}
@@ -0,0 +1,53 @@
+//! Checks rectangles are clipped to the framebuffer before being drawn.
+
+#![cfg(feature = "bevy")]
+
+use bevy::prelude::*;
+use bevy_m8::prelude::*;
+
+#[test]
+fn zero_size_rectangles_are_skipped() {
+    let dimensions = M8DisplayDimensions::default();
+    assert_eq!(
+        dimensions.clip(Position::new(10, 10), Size::new(0, 5)),
+        None
+    );
+    assert_eq!(
+        dimensions.clip(Position::new(10, 10), Size::new(5, 0)),
+        None
+    );
+    assert_eq!(dimensions.clip(Position::ZERO, Size::ZERO), None);
+}
+
+#[test]
+fn rectangles_are_cut_off_at_the_display_edge() {
+    let dimensions = M8DisplayDimensions::default();
+    let clipped = dimensions.clip(Position::new(310, 230), Size::new(20, 20));
+    assert_eq!(
+        clipped,
+        Some(URect::new(310, 230, dimensions.width, dimensions.height))
+    );
+}
+
+#[test]
+fn rectangles_overflowing_u16_do_not_wrap() {
+    let dimensions = M8DisplayDimensions {
+        width: u16::MAX as u32 + 1,
+        height: u16::MAX as u32 + 1,
+    };
+    let pos = Position::new(u16::MAX - 4, u16::MAX - 4);
+    let clipped = dimensions.clip(pos, Size::new(100, 100));
+    assert_eq!(
+        clipped,
+        Some(URect::new(
+            u16::MAX as u32 - 4,
+            u16::MAX as u32 - 4,
+            u16::MAX as u32,
+            u16::MAX as u32
+        ))
+    );
+
+    // Off the default display entirely, however large the rectangle.
+    let off_screen = M8DisplayDimensions::default().clip(pos, Size::new(u16::MAX, u16::MAX));
+    assert_eq!(off_screen, None);
+}
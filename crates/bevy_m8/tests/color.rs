@@ -0,0 +1,35 @@
+//! Checks colours survive the trip to the M8's RGB bytes and back, the
+//! conversion both the decoder and encoder use.
+
+use bevy_m8::{color_from_rgb, rgb_from_color};
+
+#[test]
+fn every_channel_value_round_trips() {
+    for value in 0..=u8::MAX {
+        for rgb in [
+            [value, 0, 0],
+            [0, value, 0],
+            [0, 0, value],
+            [value, value, value],
+            [value, u8::MAX - value, value / 2],
+        ] {
+            assert_eq!(rgb_from_color(color_from_rgb(&rgb)), rgb);
+        }
+    }
+}
+
+#[cfg(feature = "bevy")]
+#[test]
+fn other_colour_spaces_convert_to_srgb_bytes() {
+    use bevy::color::{Color, LinearRgba};
+
+    assert_eq!(rgb_from_color(Color::BLACK), [0, 0, 0]);
+    assert_eq!(rgb_from_color(Color::WHITE), [255, 255, 255]);
+    // Linear 0.5 is sRGB 0xBC.
+    assert_eq!(
+        rgb_from_color(LinearRgba::rgb(0.5, 0.5, 0.5).into()),
+        [0xBC; 3]
+    );
+    // Out of range channels are clamped rather than wrapped.
+    assert_eq!(rgb_from_color(Color::srgb(1.5, -0.5, 0.0)), [255, 0, 0]);
+}
@@ -0,0 +1,89 @@
+//! Checks the errors from opening the serial port, or a capture to play
+//! back, are told apart, as each calls for a different remedy.
+
+#![cfg(all(feature = "bevy", not(target_arch = "wasm32")))]
+
+use std::io;
+
+use bevy::{diagnostic::DiagnosticsPlugin, prelude::*, state::app::StatesPlugin};
+use bevy_m8::{M8ConnectionError, classify_open_error, prelude::*};
+use serialport::{Error, ErrorKind};
+
+const PORT: &str = "/dev/ttyACM0";
+
+fn classify(kind: ErrorKind, description: &str) -> M8ConnectionError {
+    classify_open_error(PORT, &Error::new(kind, description))
+}
+
+#[test]
+fn permission_denied_is_missing_permissions() {
+    let error = classify(
+        ErrorKind::Io(io::ErrorKind::PermissionDenied),
+        "Permission denied",
+    );
+    // Windows reports a port held by another program as access denied.
+    if cfg!(unix) {
+        assert_eq!(error, M8ConnectionError::PermissionDenied(PORT.into()));
+    } else {
+        assert_eq!(error, M8ConnectionError::PortBusy(PORT.into()));
+    }
+}
+
+#[test]
+fn resource_busy_is_a_busy_port() {
+    let error = classify(
+        ErrorKind::Io(io::ErrorKind::ResourceBusy),
+        "Device or resource busy",
+    );
+    assert_eq!(error, M8ConnectionError::PortBusy(PORT.into()));
+}
+
+#[test]
+fn no_device_is_a_busy_port() {
+    let error = classify(ErrorKind::NoDevice, "Device or resource busy");
+    assert_eq!(error, M8ConnectionError::PortBusy(PORT.into()));
+}
+
+#[test]
+fn busy_messages_are_a_busy_port() {
+    let error = classify(ErrorKind::Unknown, "Port is busy");
+    assert_eq!(error, M8ConnectionError::PortBusy(PORT.into()));
+    let error = classify(ErrorKind::Unknown, "Access is denied.");
+    assert_eq!(error, M8ConnectionError::PortBusy(PORT.into()));
+}
+
+#[test]
+fn not_found_is_a_plain_serial_port_error() {
+    let error = classify(
+        ErrorKind::Io(io::ErrorKind::NotFound),
+        "No such file or directory",
+    );
+    assert!(
+        matches!(error, M8ConnectionError::SerialPort(_)),
+        "{:?}",
+        error
+    );
+    assert_eq!(error.remedy(), "Try reconnecting the M8.");
+}
+
+#[test]
+fn missing_capture_is_reported_rather_than_panicking() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        DiagnosticsPlugin,
+        StatesPlugin,
+        M8SerialPlugin {
+            playback_path: Some("missing.bin".into()),
+            ..default()
+        },
+    ));
+    app.update();
+
+    let status = app.world().resource::<M8ConnectionStatus>();
+    assert!(!status.connected);
+    let last_error = status.last_error.as_deref().unwrap_or_default();
+    assert!(last_error.contains("missing.bin"), "{:?}", last_error);
+    let error = M8ConnectionError::Playback("missing.bin".into());
+    assert_eq!(error.remedy(), "Check the path of the capture file.");
+}
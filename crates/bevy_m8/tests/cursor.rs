@@ -0,0 +1,182 @@
+//! Replays the rectangles the M8 draws while the cursor moves around, and
+//! checks the estimate follows it, as does the signature the M8's font.
+
+#![cfg(feature = "render")]
+
+use std::any::TypeId;
+
+use bevy::{ecs::reflect::ReflectResource, prelude::*, state::app::StatesPlugin};
+use bevy_m8::{DISPLAY_HEIGHT, DISPLAY_WIDTH, M8DisplayDimensions, prelude::*};
+
+const BACKGROUND: Color = Color::srgb_u8(0x00, 0x00, 0x00);
+const SELECTION: Color = Color::srgb_u8(0x00, 0x80, 0xFF);
+const TEXT: Color = Color::srgb_u8(0xFF, 0xFF, 0xFF);
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin, M8CursorPlugin));
+    app.insert_state(M8LoadingState::Running);
+    app.init_resource::<M8Commands>();
+    app.init_resource::<M8DisplayDimensions>();
+    app
+}
+
+fn rectangle(x: u16, y: u16, width: u16, height: u16, colour: Color) -> M8Command {
+    M8Command::DrawRectangle {
+        pos: Position::new(x, y),
+        size: Size::new(width, height),
+        colour,
+    }
+}
+
+/// The cursor as the firmware draws it, a selection coloured cell.
+fn cell_cursor(column: u16, row: u16) -> M8Command {
+    rectangle(column * 8, row * 10, 8, 10, SELECTION)
+}
+
+fn clear_screen() -> M8Command {
+    rectangle(
+        0,
+        0,
+        DISPLAY_WIDTH as u16,
+        DISPLAY_HEIGHT as u16,
+        BACKGROUND,
+    )
+}
+
+/// Draws every frame in turn, returning the estimate after each.
+fn replay(app: &mut App, frames: Vec<Vec<M8Command>>) -> Vec<(Option<UVec2>, bool)> {
+    frames
+        .into_iter()
+        .map(|frame| {
+            app.world_mut().resource_mut::<M8Commands>().0 = frame;
+            app.update();
+            let estimate = app.world().resource::<M8CursorEstimate>();
+            (estimate.cell, estimate.stale)
+        })
+        .collect()
+}
+
+#[test]
+fn estimate_follows_the_cursor_path() {
+    let mut app = app();
+    let character = |x, y| M8Command::DrawCharacter {
+        c: b'A',
+        pos: Position::new(x, y),
+        foreground: TEXT,
+        background: BACKGROUND,
+    };
+
+    let path = replay(
+        &mut app,
+        vec![
+            // A view is drawn with the cursor on its third row.
+            vec![clear_screen(), character(0, 20), cell_cursor(1, 2)],
+            // The cursor moves right, the old cell is drawn over in the
+            // background colour first.
+            vec![rectangle(8, 20, 8, 10, BACKGROUND), cell_cursor(2, 2)],
+            // A frame without rectangles leaves the estimate alone.
+            vec![character(24, 40)],
+            // Rectangles of other shapes, such as meters, don't count.
+            vec![rectangle(200, 0, 3, 40, TEXT), cell_cursor(2, 3)],
+            // A whole row is selected.
+            vec![rectangle(0, 50, 160, 10, SELECTION)],
+            // Switching views clears the screen before the new cursor.
+            vec![clear_screen()],
+            vec![cell_cursor(0, 1)],
+        ],
+    );
+
+    assert_eq!(
+        path,
+        [
+            (Some(UVec2::new(1, 2)), false),
+            (Some(UVec2::new(2, 2)), false),
+            (Some(UVec2::new(2, 2)), false),
+            (Some(UVec2::new(2, 3)), false),
+            (Some(UVec2::new(0, 5)), false),
+            (Some(UVec2::new(0, 5)), true),
+            (Some(UVec2::new(0, 1)), false),
+        ]
+    );
+}
+
+#[test]
+fn selection_colour_filters_the_rectangles() {
+    let mut app = app();
+    app.insert_resource(M8CursorSignature {
+        colour: Some(SELECTION),
+        ..default()
+    });
+
+    let path = replay(
+        &mut app,
+        vec![
+            vec![cell_cursor(3, 4)],
+            // The old cell drawn over in the background colour isn't the
+            // cursor once the colour is known.
+            vec![rectangle(24, 40, 8, 10, BACKGROUND)],
+        ],
+    );
+    assert_eq!(
+        path,
+        [
+            (Some(UVec2::new(3, 4)), false),
+            (Some(UVec2::new(3, 4)), false)
+        ]
+    );
+
+    let estimate = app.world().resource::<M8CursorEstimate>();
+    assert_eq!(estimate.rect.map(|rect| rect.colour), Some(SELECTION));
+    assert_eq!(estimate.recent_rectangles().count(), 2);
+}
+
+#[test]
+fn cursor_resources_are_reflected() {
+    let app = app();
+    let registry = app.world().resource::<AppTypeRegistry>().read();
+    for type_id in [
+        TypeId::of::<M8CursorSignature>(),
+        TypeId::of::<M8CursorEstimate>(),
+    ] {
+        assert!(registry.get_type_data::<ReflectResource>(type_id).is_some());
+    }
+}
+
+#[test]
+fn signature_follows_the_font_unless_changed() {
+    let mut app = app();
+    let large_font = M8DeviceInfo {
+        hardware_type: 2,
+        major: 6,
+        minor: 0,
+        patch: 0,
+        font_mode: 1,
+    };
+    app.world_mut().write_message(M8DeviceInfoChanged {
+        old: None,
+        new: large_font,
+    });
+    app.update();
+    let signature = *app.world().resource::<M8CursorSignature>();
+    assert_eq!(signature.cell_size.as_uvec2(), large_font.cell_size());
+    assert_eq!(signature.min_row_width as u32, large_font.cell_size().x * 4);
+
+    // A signature set to match a theme is left alone.
+    let custom = Size::new(6, 9);
+    app.world_mut()
+        .resource_mut::<M8CursorSignature>()
+        .cell_size = custom;
+    app.world_mut().write_message(M8DeviceInfoChanged {
+        old: Some(large_font),
+        new: M8DeviceInfo {
+            font_mode: 0,
+            ..large_font
+        },
+    });
+    app.update();
+    assert_eq!(
+        app.world().resource::<M8CursorSignature>().cell_size,
+        custom
+    );
+}
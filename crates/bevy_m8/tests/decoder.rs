@@ -0,0 +1,204 @@
+//! Checks how the command decoder handles the packet variants the
+//! firmwares send, packets cut short, and a device which changes mid-stream.
+
+#![cfg(feature = "bevy")]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_m8::{
+    DRAW_CHARACTER_COMMAND, DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND, DRAW_RECTANGLE_COMMAND,
+    KEY_PRESS_STATE_COMMAND, M8DeviceInfo, M8DeviceInfoChanged, M8StreamDecoder,
+    SYSTEM_INFO_COMMAND, prelude::*,
+};
+
+/// How long the app may take to see the firmware change.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// SLIP frames each packet with a trailing `SLIP_END`.
+const SLIP_END: u8 = 0xC0;
+
+fn decode_packets(packets: &[&[u8]]) -> (Vec<M8Command>, usize) {
+    let mut decoder = M8StreamDecoder::new();
+    let mut commands = Vec::new();
+    let mut errors = 0;
+    for packet in packets {
+        let mut bytes = packet.to_vec();
+        bytes.push(SLIP_END);
+        errors += decoder.decode(&bytes, |command| commands.push(command.clone()));
+    }
+    (commands, errors)
+}
+
+#[test]
+fn extended_key_state_is_rejected_from_old_firmwares() {
+    let system_info = |major| [SYSTEM_INFO_COMMAND, 3, major, 0, 0, 0];
+    let (commands, errors) = decode_packets(&[
+        // Nothing is known about the firmware yet.
+        &[KEY_PRESS_STATE_COMMAND, 0x01, 0x00],
+        &system_info(3),
+        &[KEY_PRESS_STATE_COMMAND, 0x02, 0x00],
+        &[KEY_PRESS_STATE_COMMAND, 0x04],
+    ]);
+    assert_eq!(errors, 2);
+    assert_eq!(commands[1..], [M8Command::KeyPressState { keys: 0x04 }]);
+}
+
+#[test]
+fn cell_size_follows_the_model_and_font() {
+    let cell_size = |hardware_type, font_mode| {
+        M8DeviceInfo {
+            hardware_type,
+            major: 6,
+            minor: 0,
+            patch: 0,
+            font_mode,
+        }
+        .cell_size()
+    };
+    let model_01 = cell_size(2, 0);
+    assert_eq!(model_01, UVec2::new(8, 10));
+    // The large font and the Model:02's larger screen both grow the cells.
+    assert!(cell_size(2, 1).cmpgt(model_01).all());
+    let model_02 = cell_size(3, 0);
+    assert!(model_02.cmpgt(model_01).all());
+    assert!(cell_size(3, 1).cmpgt(model_02).all());
+}
+
+#[test]
+fn firmware_change_mid_stream_switches_the_key_state_layout() {
+    let system_info = |major| [SYSTEM_INFO_COMMAND, 3, major, 0, 0, 0];
+    let (commands, errors) = decode_packets(&[
+        &system_info(4),
+        &[KEY_PRESS_STATE_COMMAND, 0x01, 0x00],
+        &[KEY_PRESS_STATE_COMMAND, 0x02],
+        &system_info(3),
+        &[KEY_PRESS_STATE_COMMAND, 0x04, 0x00],
+        &[KEY_PRESS_STATE_COMMAND, 0x08],
+    ]);
+    assert_eq!(errors, 1);
+    let keys: Vec<_> = commands
+        .iter()
+        .filter_map(|command| match command {
+            M8Command::KeyPressState { keys } => Some(*keys),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(keys, [0x01, 0x02, 0x08]);
+}
+
+#[test]
+fn key_state_of_any_other_length_is_an_error() {
+    let (commands, errors) = decode_packets(&[
+        &[KEY_PRESS_STATE_COMMAND],
+        &[KEY_PRESS_STATE_COMMAND, 0x01, 0x00, 0x00],
+    ]);
+    assert!(commands.is_empty());
+    assert_eq!(errors, 2);
+}
+
+#[test]
+fn every_command_one_byte_short_is_an_error() {
+    // A sized rectangle without a colour is left out, as one byte short it
+    // is exactly a rectangle with a colour and no size.
+    let packets: [&[u8]; 7] = [
+        &[DRAW_CHARACTER_COMMAND, b'A', 1, 0, 2, 0, 9, 9, 9, 0, 0, 0],
+        &[DRAW_RECTANGLE_COMMAND, 1, 0, 2, 0],
+        &[DRAW_RECTANGLE_COMMAND, 1, 0, 2, 0, 0xFF, 0, 0],
+        &[DRAW_RECTANGLE_COMMAND, 1, 0, 2, 0, 3, 0, 4, 0, 0xFF, 0, 0],
+        &[DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND, 0xFF, 0xFF, 0xFF],
+        &[SYSTEM_INFO_COMMAND, 3, 6, 0, 0, 0],
+        &[KEY_PRESS_STATE_COMMAND, 0x01],
+    ];
+    for packet in packets {
+        let (commands, errors) = decode_packets(&[packet]);
+        assert_eq!((commands.len(), errors), (1, 0), "{:02X?}", packet);
+
+        let (commands, errors) = decode_packets(&[&packet[..packet.len() - 1]]);
+        assert!(
+            commands.is_empty(),
+            "{:02X?} decoded as {:?}",
+            packet,
+            commands
+        );
+        assert_eq!(errors, 1, "{:02X?}", packet);
+    }
+}
+
+#[test]
+fn model_change_mid_stream_switches_the_waveform_limit() {
+    let system_info = |hardware_type, major| [SYSTEM_INFO_COMMAND, hardware_type, major, 0, 0, 0];
+    // Longer than the original models' screens, within the Model:02's.
+    let mut waveform = vec![DRAW_OSCILLOSCOPE_WAVEFORM_COMMAND, 0xFF, 0xFF, 0xFF];
+    waveform.extend([8; 400]);
+
+    let (commands, errors) = decode_packets(&[
+        &system_info(2, 5),
+        &waveform,
+        &system_info(3, 6),
+        &waveform,
+        &system_info(2, 5),
+        &waveform,
+    ]);
+    assert_eq!(errors, 2);
+    let waveforms: Vec<_> = commands
+        .iter()
+        .filter_map(|command| match command {
+            M8Command::DrawOscilloscopeWaveform { waveform, .. } => Some(waveform.len()),
+            _ => None,
+        })
+        .collect();
+    // Only the waveform sent while the Model:02 was connected.
+    assert_eq!(waveforms, [400]);
+    assert!(matches!(
+        commands[2],
+        M8Command::DrawOscilloscopeWaveform { .. }
+    ));
+}
+
+#[test]
+fn firmware_change_mid_stream_is_reported() {
+    let system_info = |major| M8Command::SystemInfo {
+        hardware_type: 3,
+        major,
+        minor: 0,
+        patch: 0,
+        font_mode: 0,
+    };
+    let mock = M8MockConnection::from_commands(&[system_info(5), system_info(6)])
+        .with_read_interval(Duration::from_millis(1));
+    let plugin = M8Plugin::default().with_mock(mock);
+    #[cfg(feature = "render")]
+    let plugin = plugin.with_headless(true);
+
+    let mut app = App::new();
+    app.add_plugins(plugin);
+    app.finish();
+    app.cleanup();
+
+    let mut cursor = app
+        .world()
+        .resource::<Messages<M8DeviceInfoChanged>>()
+        .get_cursor();
+    let mut changes = Vec::new();
+    let started = Instant::now();
+    while changes.len() < 2 {
+        assert!(started.elapsed() < TIMEOUT, "saw only {:?}", changes);
+        app.update();
+        let messages = app.world().resource::<Messages<M8DeviceInfoChanged>>();
+        changes.extend(
+            cursor
+                .read(messages)
+                .map(|changed| (changed.old.map(|old| old.major), changed.new.major)),
+        );
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    // The first SystemInfo has nothing to change from.
+    assert_eq!(changes[..2], [(None, 5), (Some(5), 6)]);
+    let (_, latest) = changes[changes.len() - 1];
+    assert_eq!(app.world().resource::<M8DeviceInfo>().major, latest);
+}
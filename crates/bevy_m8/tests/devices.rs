@@ -0,0 +1,66 @@
+//! Checks M8s spawned as entities each decode their own stream.
+
+#![cfg(all(feature = "bevy", not(target_arch = "wasm32")))]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{prelude::*, state::app::StatesPlugin};
+use bevy_m8::prelude::*;
+
+/// How long the devices may take to send their first frame.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn rectangle(x: u16, colour: Color) -> M8Command {
+    M8Command::DrawRectangle {
+        pos: Position::new(x, 50),
+        size: Size::new(10, 10),
+        colour,
+    }
+}
+
+fn device(port: &str, command: M8Command) -> M8Device {
+    let mock =
+        M8MockConnection::from_commands(&[command]).with_read_interval(Duration::from_millis(1));
+    M8Device::new(port).with_mock(mock)
+}
+
+#[test]
+fn devices_decode_their_own_streams() {
+    let first = rectangle(10, Color::srgb_u8(0xF0, 0x80, 0x10));
+    let second = rectangle(200, Color::srgb_u8(0x10, 0x80, 0xF0));
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin, M8DevicesPlugin));
+    #[cfg(feature = "render")]
+    app.add_plugins(AssetPlugin::default())
+        .init_asset::<Image>();
+    app.insert_state(M8LoadingState::Running);
+
+    let entities = [
+        app.world_mut().spawn(device("first", first.clone())).id(),
+        app.world_mut().spawn(device("second", second.clone())).id(),
+    ];
+    let expected = [first, second];
+
+    let mut seen = [false; 2];
+    let started = Instant::now();
+    while seen.contains(&false) {
+        app.update();
+        for (i, entity) in entities.into_iter().enumerate() {
+            let commands = app.world().get::<M8Commands>(entity).unwrap();
+            for command in commands.0.iter() {
+                assert_eq!(command, &expected[i], "a device decoded another's stream");
+                seen[i] = true;
+            }
+        }
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the devices didn't send their streams, seen {:?}",
+            seen
+        );
+        thread::sleep(Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,84 @@
+//! Drives the whole pipeline, from the serial stream through the decoder
+//! to the display image, with a mock M8 in place of the hardware.
+
+#![cfg(feature = "render")]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_m8::{DISPLAY_HEIGHT, DISPLAY_WIDTH, prelude::*};
+
+/// How long the app may take to load its assets and draw the screen.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+const BACKGROUND: Color = Color::srgb_u8(0x20, 0x40, 0x60);
+const HIGHLIGHT: Color = Color::srgb_u8(0xF0, 0x80, 0x10);
+
+fn pixel(app: &App, x: u32, y: u32) -> Option<[u8; 4]> {
+    let display = app.world().get_resource::<M8Display>()?;
+    let images = app.world().resource::<Assets<Image>>();
+    let colour = images.get(display.image())?.get_color_at(x, y).ok()?;
+    Some(colour.to_srgba().to_u8_array())
+}
+
+#[test]
+fn mock_stream_is_drawn_to_the_display() {
+    let commands = [
+        M8Command::DrawRectangle {
+            pos: Position::new(0, 0),
+            size: Size::new(DISPLAY_WIDTH as u16, DISPLAY_HEIGHT as u16),
+            colour: BACKGROUND,
+        },
+        M8Command::DrawRectangle {
+            pos: Position::new(100, 100),
+            size: Size::new(20, 10),
+            colour: HIGHLIGHT,
+        },
+    ];
+    // An odd chunk size splits the packets across reads.
+    let mock = M8MockConnection::from_commands(&commands)
+        .with_chunk_size(7)
+        .with_read_interval(Duration::from_millis(1));
+
+    let mut app = App::new();
+    app.add_plugins((
+        M8DisplayPlugin {
+            headless: true,
+            ..default()
+        },
+        M8AssetsPlugin,
+        M8FontsPlugin::default(),
+        M8KeyMapPlugin::default(),
+        M8MacroPlugin,
+        M8SerialPlugin {
+            mock: Some(mock),
+            ..default()
+        },
+    ));
+    app.finish();
+    app.cleanup();
+
+    let expected = |colour: Color| Some(colour.to_srgba().to_u8_array());
+    let started = Instant::now();
+    loop {
+        app.update();
+        if pixel(&app, 0, 0) == expected(BACKGROUND) && pixel(&app, 110, 105) == expected(HIGHLIGHT)
+        {
+            break;
+        }
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the mock stream wasn't drawn, found {:?} and {:?}",
+            pixel(&app, 0, 0),
+            pixel(&app, 110, 105),
+        );
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    assert_eq!(pixel(&app, 99, 105), expected(BACKGROUND));
+    assert_eq!(pixel(&app, 120, 105), expected(BACKGROUND));
+    assert!(app.world().resource::<M8DisplayStats>().frames_rendered > 0);
+}
@@ -0,0 +1,94 @@
+//! Checks the decoded commands are triggered as events for the observers
+//! watching for them.
+
+#![cfg(feature = "bevy")]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{diagnostic::DiagnosticsPlugin, prelude::*, state::app::StatesPlugin};
+use bevy_m8::prelude::*;
+
+/// How long the mock may take to send its commands.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Resource, Default)]
+struct Observed {
+    commands: Vec<M8Command>,
+    frames: Vec<Vec<M8Command>>,
+}
+
+fn rectangle(x: u16) -> M8Command {
+    M8Command::DrawRectangle {
+        pos: Position::new(x, 0),
+        size: Size::new(1, 1),
+        colour: Color::srgb_u8(0xFF, 0xFF, 0xFF),
+    }
+}
+
+fn app(sent: &[M8Command]) -> App {
+    let mock = M8MockConnection::from_commands(sent).with_read_interval(Duration::from_millis(1));
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        DiagnosticsPlugin,
+        M8SerialPlugin {
+            mock: Some(mock),
+            ..default()
+        },
+    ));
+    app.init_resource::<Observed>();
+    app.add_observer(|event: On<M8FrameEvent>, mut observed: ResMut<Observed>| {
+        observed.frames.push(event.commands.clone());
+    });
+    app.insert_state(M8LoadingState::Running);
+    app
+}
+
+fn update_until(app: &mut App, done: impl Fn(&Observed) -> bool) {
+    app.finish();
+    app.cleanup();
+    let started = Instant::now();
+    while !done(app.world().resource::<Observed>()) {
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the mock's commands never arrived"
+        );
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn observers_see_every_command_and_frame() {
+    let sent: Vec<_> = (0..3).map(rectangle).collect();
+    let mut app = app(&sent);
+    app.add_observer(
+        |event: On<M8CommandEvent>, mut observed: ResMut<Observed>| {
+            observed.commands.push(event.0.clone());
+        },
+    );
+    update_until(&mut app, |observed| observed.commands.len() >= sent.len());
+
+    // The mock repeats its commands, only the first of them are compared.
+    let observed = app.world().resource::<Observed>();
+    assert_eq!(observed.commands[..sent.len()], sent);
+    let framed: Vec<_> = observed.frames.concat();
+    assert_eq!(framed, observed.commands);
+}
+
+#[test]
+fn frames_are_triggered_without_command_observers() {
+    let sent: Vec<_> = (0..3).map(rectangle).collect();
+    let mut app = app(&sent);
+    update_until(&mut app, |observed| {
+        observed.frames.concat().len() >= sent.len()
+    });
+
+    let framed: Vec<_> = app.world().resource::<Observed>().frames.concat();
+    assert_eq!(framed[..sent.len()], sent);
+}
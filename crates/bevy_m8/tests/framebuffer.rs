@@ -0,0 +1,112 @@
+//! Checks the line and span rasterizers the oscilloscope is drawn with.
+
+#![cfg(feature = "render")]
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use bevy_m8::{draw_line, draw_vertical_span};
+
+const SIZE: u32 = 8;
+
+fn blank() -> Image {
+    Image::new_fill(
+        Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD,
+    )
+}
+
+fn full() -> URect {
+    URect::new(0, 0, SIZE, SIZE)
+}
+
+/// The pixels drawn over the black background, in rows from the top.
+fn lit(image: &Image) -> Vec<(u32, u32)> {
+    let mut pixels = Vec::new();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if image.pixel_bytes(UVec3::new(x, y, 0)) != Some(&[0, 0, 0, 255]) {
+                pixels.push((x, y));
+            }
+        }
+    }
+    pixels
+}
+
+#[test]
+fn horizontal_line_covers_both_endpoints() {
+    let mut image = blank();
+    draw_line(
+        &mut image,
+        IVec2::new(5, 2),
+        IVec2::new(1, 2),
+        full(),
+        Color::WHITE,
+    );
+    assert_eq!(lit(&image), [(1, 2), (2, 2), (3, 2), (4, 2), (5, 2)]);
+}
+
+#[test]
+fn vertical_line_covers_both_endpoints() {
+    let mut image = blank();
+    draw_line(
+        &mut image,
+        IVec2::new(3, 1),
+        IVec2::new(3, 4),
+        full(),
+        Color::WHITE,
+    );
+    assert_eq!(lit(&image), [(3, 1), (3, 2), (3, 3), (3, 4)]);
+}
+
+#[test]
+fn steep_line_has_one_pixel_per_row() {
+    let mut image = blank();
+    draw_line(
+        &mut image,
+        IVec2::new(1, 0),
+        IVec2::new(3, 6),
+        full(),
+        Color::WHITE,
+    );
+    let pixels = lit(&image);
+    assert_eq!(pixels.len(), 7);
+    for (row, &(x, y)) in pixels.iter().enumerate() {
+        assert_eq!(y, row as u32);
+        assert!((1..=3).contains(&x), "{:?} strays off the line", (x, y));
+    }
+    assert_eq!(pixels.first(), Some(&(1, 0)));
+    assert_eq!(pixels.last(), Some(&(3, 6)));
+}
+
+#[test]
+fn clipped_line_only_draws_inside_the_bounds() {
+    let mut image = blank();
+    // Both ends lie outside the bounds, and the second off the image.
+    draw_line(
+        &mut image,
+        IVec2::new(-3, 4),
+        IVec2::new(12, 4),
+        URect::new(2, 0, 6, SIZE),
+        Color::WHITE,
+    );
+    assert_eq!(lit(&image), [(2, 4), (3, 4), (4, 4), (5, 4)]);
+}
+
+#[test]
+fn vertical_span_is_clipped_in_either_direction() {
+    let mut image = blank();
+    let bounds = URect::new(0, 2, SIZE, 5);
+    draw_vertical_span(&mut image, 1, 6, 0, bounds, Color::WHITE);
+    draw_vertical_span(&mut image, 4, 3, 3, bounds, Color::WHITE);
+    assert_eq!(lit(&image), [(1, 2), (1, 3), (4, 3), (1, 4)]);
+}
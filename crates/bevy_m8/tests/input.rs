@@ -0,0 +1,180 @@
+//! Checks keys held together reach the M8 as a single control byte, joined
+//! by the buttons of the other sources, that repeats never send two bytes
+//! in one update and that the pause key gives way to a button bound to it,
+//! with a mock M8 which reports what it was sent. The macro plugin is left
+//! out, the serial plugin forwards the input on its own.
+
+#![cfg(feature = "bevy")]
+
+use std::time::Duration;
+
+use bevy::{diagnostic::DiagnosticsPlugin, prelude::*, state::app::StatesPlugin};
+use bevy_m8::{M8_EDIT, M8_LEFT, M8_SELECT, M8_UP, prelude::*};
+use crossbeam_channel::{Receiver, unbounded};
+
+/// How long a write may take to reach the mock.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for writes which shouldn't arrive.
+const QUIET_TIME: Duration = Duration::from_millis(30);
+
+fn app() -> (App, Receiver<Vec<u8>>) {
+    app_with(
+        M8KeyMap::default()
+            .with_up_keycode(KeyCode::ArrowUp)
+            .with_left_keycode(KeyCode::ArrowLeft)
+            .with_edit_keycode(KeyCode::KeyZ),
+    )
+}
+
+fn app_with(key_map: M8KeyMap) -> (App, Receiver<Vec<u8>>) {
+    let (written, writes) = unbounded();
+    let mock = M8MockConnection::from_bytes(Vec::new())
+        .with_read_interval(Duration::from_millis(1))
+        .with_written(written);
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        DiagnosticsPlugin,
+        M8SerialPlugin {
+            mock: Some(mock),
+            ..default()
+        },
+        M8KeyMapPlugin { key_map },
+    ));
+    app.init_resource::<ButtonInput<KeyCode>>();
+    app.insert_state(M8LoadingState::Running);
+    app.finish();
+    app.cleanup();
+
+    // The enable handshake is sent as the mock connects.
+    assert_eq!(next_write(&writes), b"E");
+    assert_eq!(next_write(&writes), b"R");
+    (app, writes)
+}
+
+fn next_write(writes: &Receiver<Vec<u8>>) -> Vec<u8> {
+    writes
+        .recv_timeout(WRITE_TIMEOUT)
+        .expect("nothing was written to the M8")
+}
+
+fn assert_quiet(writes: &Receiver<Vec<u8>>) {
+    if let Ok(bytes) = writes.recv_timeout(QUIET_TIME) {
+        panic!("unexpected write {:?}", bytes);
+    }
+}
+
+fn update_with_keys(app: &mut App, press: &[KeyCode], release: &[KeyCode]) {
+    let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    for &key in press {
+        keys.press(key);
+    }
+    for &key in release {
+        keys.release(key);
+    }
+    app.update();
+}
+
+#[test]
+fn keys_pressed_together_are_sent_as_one_byte() {
+    let (mut app, writes) = app();
+    update_with_keys(&mut app, &[KeyCode::ArrowUp, KeyCode::ArrowLeft], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_UP | M8_LEFT]);
+    assert_quiet(&writes);
+
+    // Holding them sends nothing more, releasing one sends the other.
+    update_with_keys(&mut app, &[], &[]);
+    assert_quiet(&writes);
+    update_with_keys(&mut app, &[], &[KeyCode::ArrowLeft]);
+    assert_eq!(next_write(&writes), [b'C', M8_UP]);
+    assert_eq!(app.world().resource::<M8KeyboardState>().last_mask, M8_UP);
+}
+
+#[test]
+fn a_key_added_to_a_held_one_sends_the_combined_byte() {
+    let (mut app, writes) = app();
+    update_with_keys(&mut app, &[KeyCode::KeyZ], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_EDIT]);
+    update_with_keys(&mut app, &[KeyCode::ArrowUp], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_EDIT | M8_UP]);
+    assert_quiet(&writes);
+}
+
+#[test]
+fn other_input_sources_join_the_keyboard_byte() {
+    let (mut app, writes) = app();
+    // Stands in for a source such as the on-screen keypad.
+    app.add_systems(
+        Update,
+        (|mut input: ResMut<M8InputState>| input.mask |= M8_SELECT).in_set(M8UpdateSystems::Input),
+    );
+    update_with_keys(&mut app, &[KeyCode::KeyZ], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_EDIT | M8_SELECT]);
+    assert_quiet(&writes);
+}
+
+#[test]
+fn buttons_sent_through_the_connection_join_the_keyboard_byte() {
+    let (mut app, writes) = app();
+    update_with_keys(&mut app, &[KeyCode::KeyZ], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_EDIT]);
+
+    app.world()
+        .resource::<M8Connection>()
+        .send_buttons(&[M8Button::Select]);
+    update_with_keys(&mut app, &[], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_EDIT | M8_SELECT]);
+    assert_eq!(
+        app.world().resource::<M8KeyboardState>().last_mask,
+        M8_EDIT | M8_SELECT
+    );
+    // They stay held until sent again.
+    update_with_keys(&mut app, &[], &[]);
+    assert_quiet(&writes);
+
+    app.world().resource::<M8Connection>().send_buttons(&[]);
+    update_with_keys(&mut app, &[], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_EDIT]);
+    assert_quiet(&writes);
+}
+
+#[test]
+fn repeats_release_and_press_in_separate_updates() {
+    let (mut app, writes) = app();
+    app.insert_resource(M8KeyRepeat {
+        enabled: true,
+        delay: Duration::ZERO,
+        interval: Duration::ZERO,
+    });
+    update_with_keys(&mut app, &[KeyCode::ArrowUp], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_UP]);
+
+    update_with_keys(&mut app, &[], &[]);
+    assert_eq!(next_write(&writes), [b'C', 0]);
+    assert_quiet(&writes);
+    assert_eq!(app.world().resource::<M8KeyboardState>().last_mask, 0);
+
+    update_with_keys(&mut app, &[], &[]);
+    assert_eq!(next_write(&writes), [b'C', M8_UP]);
+    assert_quiet(&writes);
+}
+
+#[test]
+fn f11_pauses_decoding_unless_it_is_bound() {
+    let paused = |app: &App| app.world().resource::<M8DecodePaused>().0;
+
+    let (mut app, writes) = app();
+    update_with_keys(&mut app, &[KeyCode::F11], &[]);
+    assert!(paused(&app));
+    assert_quiet(&writes);
+
+    // Bound to a button, the key is sent to the M8 instead.
+    let (mut app, writes) = app_with(M8KeyMap::default().with_edit_keycode(KeyCode::F11));
+    update_with_keys(&mut app, &[KeyCode::F11], &[]);
+    assert!(!paused(&app));
+    assert_eq!(next_write(&writes), [b'C', M8_EDIT]);
+}
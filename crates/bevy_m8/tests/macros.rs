@@ -0,0 +1,191 @@
+//! Checks the timing of recorded and played macros against a mocked clock,
+//! with a mock M8 which reports what it was sent.
+
+#![cfg(feature = "bevy")]
+
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::DiagnosticsPlugin, prelude::*, state::app::StatesPlugin, time::TimeUpdateStrategy,
+};
+use bevy_m8::prelude::*;
+use crossbeam_channel::{Receiver, unbounded};
+
+/// How far the mocked clock advances every update.
+const FRAME_TIME: Duration = Duration::from_millis(10);
+
+/// How long a write may take to reach the mock.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for writes which shouldn't arrive.
+const QUIET_TIME: Duration = Duration::from_millis(30);
+
+fn app() -> (App, Receiver<Vec<u8>>) {
+    let (written, writes) = unbounded();
+    let mock = M8MockConnection::from_commands(&[M8Command::SystemInfo {
+        hardware_type: 3,
+        major: 6,
+        minor: 0,
+        patch: 0,
+        font_mode: 0,
+    }])
+    .with_read_interval(Duration::from_millis(1))
+    .with_written(written);
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        DiagnosticsPlugin,
+        M8SerialPlugin {
+            mock: Some(mock),
+            ..default()
+        },
+        M8MacroPlugin,
+    ));
+    app.init_resource::<ButtonInput<KeyCode>>();
+    app.init_resource::<M8KeyboardState>();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(FRAME_TIME));
+    app.finish();
+    app.cleanup();
+
+    // The enable handshake is sent as the mock connects.
+    assert_eq!(next_write(&writes), b"E");
+    assert_eq!(next_write(&writes), b"R");
+    (app, writes)
+}
+
+fn next_write(writes: &Receiver<Vec<u8>>) -> Vec<u8> {
+    writes
+        .recv_timeout(WRITE_TIMEOUT)
+        .expect("nothing was written to the M8")
+}
+
+fn assert_quiet(writes: &Receiver<Vec<u8>>) {
+    if let Ok(bytes) = writes.recv_timeout(QUIET_TIME) {
+        panic!("unexpected write {:?}", bytes);
+    }
+}
+
+fn press(app: &mut App, key: KeyCode) {
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(key);
+    app.update();
+    let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    keys.release(key);
+    keys.clear();
+}
+
+fn live_input(app: &mut App, bytes: &[u8]) {
+    app.world_mut().write_message(M8LiveInput(bytes.to_vec()));
+}
+
+fn step(at_ms: u64, bytes: &[u8]) -> M8MacroStep {
+    M8MacroStep {
+        at_ms,
+        bytes: bytes.to_vec(),
+    }
+}
+
+#[test]
+fn recording_keeps_the_timing_of_live_input() {
+    let (mut app, writes) = app();
+    press(&mut app, KeyCode::F5);
+    assert!(app.world().resource::<M8Macros>().is_recording());
+
+    app.update();
+    app.update();
+    live_input(&mut app, b"C\x01");
+    app.update();
+    for _ in 0..3 {
+        app.update();
+    }
+    live_input(&mut app, b"C\x00");
+    app.update();
+    press(&mut app, KeyCode::F5);
+
+    let macros = app.world().resource::<M8Macros>();
+    assert!(!macros.is_recording());
+    assert_eq!(
+        macros.last().unwrap().steps,
+        [step(30, b"C\x01"), step(70, b"C\x00")]
+    );
+    // Recorded input still reaches the M8.
+    assert_eq!(next_write(&writes), b"C\x01");
+    assert_eq!(next_write(&writes), b"C\x00");
+    assert_quiet(&writes);
+}
+
+#[test]
+fn playback_sends_every_step_when_it_is_due() {
+    let (mut app, writes) = app();
+    let steps = [step(0, b"C\x01"), step(25, b"C\x00"), step(50, b"C\x02")];
+    app.world_mut().write_message(M8MacroCommand::Play(M8Macro {
+        steps: steps.to_vec(),
+    }));
+
+    // The frames each step is due in, counted from the one it starts in.
+    let mut due = [0, 3, 5].into_iter().zip(&steps).peekable();
+    for frame in 0..8 {
+        app.update();
+        match due.peek() {
+            Some((at, step)) if *at == frame => {
+                assert_eq!(next_write(&writes), step.bytes, "frame {}", frame);
+                due.next();
+            }
+            _ => assert_quiet(&writes),
+        }
+    }
+    assert!(due.next().is_none());
+    assert!(!app.world().resource::<M8Macros>().is_playing());
+}
+
+#[test]
+fn live_input_is_dropped_while_a_macro_plays() {
+    let (mut app, writes) = app();
+    app.world_mut().write_message(M8MacroCommand::Play(M8Macro {
+        steps: vec![step(0, b"C\x01"), step(100, b"C\x00")],
+    }));
+    app.update();
+    assert_eq!(next_write(&writes), b"C\x01");
+
+    live_input(&mut app, b"C\x40");
+    app.update();
+    assert!(app.world().resource::<M8Macros>().is_playing());
+    assert_quiet(&writes);
+
+    // Cancelling releases the keys and keyjazz, and live input flows again.
+    press(&mut app, KeyCode::F7);
+    assert_eq!(next_write(&writes), b"C\x00");
+    assert_eq!(next_write(&writes), b"K\xFF");
+    live_input(&mut app, b"C\x40");
+    app.update();
+    assert_eq!(next_write(&writes), b"C\x40");
+}
+
+#[test]
+fn trigger_keys_can_be_changed() {
+    let (mut app, writes) = app();
+    app.insert_resource(
+        M8Macros::default()
+            .with_record_key(KeyCode::F1)
+            .with_play_key(KeyCode::F2),
+    );
+
+    press(&mut app, KeyCode::F5);
+    assert!(!app.world().resource::<M8Macros>().is_recording());
+    press(&mut app, KeyCode::F1);
+    assert!(app.world().resource::<M8Macros>().is_recording());
+    live_input(&mut app, b"C\x01");
+    app.update();
+    press(&mut app, KeyCode::F1);
+    assert_eq!(next_write(&writes), b"C\x01");
+
+    press(&mut app, KeyCode::F6);
+    assert_quiet(&writes);
+    press(&mut app, KeyCode::F2);
+    // The input was recorded a frame after the recording started.
+    app.update();
+    assert_eq!(next_write(&writes), b"C\x01");
+}
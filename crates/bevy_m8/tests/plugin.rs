@@ -0,0 +1,22 @@
+//! Checks [M8Plugin] builds and runs on its own, with its plugins added in
+//! an order bevy accepts.
+
+#![cfg(feature = "bevy")]
+
+use bevy::prelude::*;
+use bevy_m8::prelude::*;
+
+#[test]
+fn plugin_runs_on_its_own() {
+    let plugin = M8Plugin::default().with_demo();
+    #[cfg(feature = "render")]
+    let plugin = plugin.with_headless(true);
+
+    let mut app = App::new();
+    app.add_plugins(plugin);
+    app.finish();
+    app.cleanup();
+    for _ in 0..3 {
+        app.update();
+    }
+}
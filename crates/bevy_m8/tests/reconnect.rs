@@ -0,0 +1,149 @@
+//! Checks reconnecting only replaces the port, leaving the settings chosen
+//! by the user in place, and that the same port can be opened again.
+
+#![cfg(feature = "bevy")]
+
+use std::{
+    io::Read,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{diagnostic::DiagnosticsPlugin, prelude::*, state::app::StatesPlugin};
+use bevy_m8::prelude::*;
+use crossbeam_channel::{Receiver, unbounded};
+use serialport::{SerialPort, TTYPort};
+
+/// How long the app may take to load its assets and connect.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long to keep updating once the reconnect was requested, longer than
+/// the enable handshake takes.
+#[cfg(feature = "render")]
+const RECONNECT_TIME: Duration = Duration::from_millis(300);
+
+#[cfg(feature = "render")]
+fn connected(app: &App) -> bool {
+    app.world().resource::<M8Connection>().is_connected()
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn reconnecting_keeps_the_key_map_and_theme() {
+    let key_map = M8KeyMap::default()
+        .with_edit_keycode(KeyCode::KeyA)
+        .with_up_keycode(KeyCode::ArrowUp);
+    let theme = M8Theme::default().with_colour([0x20, 0x40, 0x60], Color::BLACK);
+
+    let mock = M8MockConnection::from_commands(&[M8Command::DrawRectangle {
+        pos: Position::new(0, 0),
+        size: Size::new(10, 10),
+        colour: Color::WHITE,
+    }])
+    .with_read_interval(Duration::from_millis(1));
+
+    let mut app = App::new();
+    app.add_plugins((
+        M8DisplayPlugin {
+            headless: true,
+            ..default()
+        },
+        M8AssetsPlugin,
+        M8FontsPlugin::default(),
+        M8KeyMapPlugin { key_map },
+        M8MacroPlugin,
+        M8SerialPlugin {
+            mock: Some(mock),
+            ..default()
+        },
+    ));
+    app.insert_resource(theme.clone());
+    app.finish();
+    app.cleanup();
+
+    let started = Instant::now();
+    while !connected(&app) {
+        assert!(started.elapsed() < TIMEOUT, "the mock never connected");
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    app.world_mut().write_message(M8Reconnect::default());
+    let reconnected = Instant::now();
+    while reconnected.elapsed() < RECONNECT_TIME || !connected(&app) {
+        assert!(started.elapsed() < TIMEOUT, "the mock never reconnected");
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    assert_eq!(*app.world().resource::<M8KeyMap>(), key_map);
+    assert_eq!(*app.world().resource::<M8Theme>(), theme);
+}
+
+/// Collects what the plugin writes to the other end of a pseudo terminal.
+#[cfg(unix)]
+fn read_pty(mut master: TTYPort) -> Receiver<u8> {
+    let (tx, rx) = unbounded();
+    thread::spawn(move || {
+        let mut buffer = [0u8; 64];
+        loop {
+            match master.read(&mut buffer) {
+                Ok(count) if buffer[..count].iter().all(|&byte| tx.send(byte).is_ok()) => (),
+                Ok(_) => return,
+                // Reads time out, and fail while nothing holds the other end.
+                Err(_) => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(unix)]
+fn assert_enabled(app: &mut App, written: &Receiver<u8>, started: Instant) {
+    for expected in [b'E', b'R'] {
+        loop {
+            assert!(started.elapsed() < TIMEOUT, "the port was never enabled");
+            app.update();
+            if let Ok(byte) = written.recv_timeout(Duration::from_millis(5)) {
+                assert_eq!(byte, expected);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn reconnecting_reopens_the_same_port() {
+    let (master, slave) = TTYPort::pair().expect("failed to open a pseudo terminal");
+    let port_name = slave.name().expect("the pseudo terminal has no name");
+    // The plugin opens the port itself.
+    drop(slave);
+    let written = read_pty(master);
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        DiagnosticsPlugin,
+        M8SerialPlugin {
+            preferred_device: Some(port_name.clone()),
+            ..default()
+        },
+    ));
+    app.insert_state(M8LoadingState::Running);
+    app.finish();
+    app.cleanup();
+
+    let started = Instant::now();
+    assert_enabled(&mut app, &written, started);
+    assert_eq!(
+        app.world().resource::<M8Connection>().port_name(),
+        Some(port_name)
+    );
+
+    // The old port is still open while asking, and has to be closed before
+    // the same one can be opened again.
+    app.world().resource::<M8Connection>().reconnect(None);
+    assert_enabled(&mut app, &written, started);
+}
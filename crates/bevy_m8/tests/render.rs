@@ -0,0 +1,291 @@
+//! Checks the display renders the commands given to it, by injecting a
+//! command between decoding and rendering.
+
+#![cfg(feature = "render")]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_m8::prelude::*;
+
+/// How long the app may take to load its assets and draw the rectangle.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+const COLOUR: Color = Color::srgb_u8(0xC0, 0x30, 0x50);
+const POSITION: UVec2 = UVec2::new(40, 60);
+const SIZE: UVec2 = UVec2::new(16, 8);
+
+fn inject_rectangle(mut commands: ResMut<M8Commands>, mut screen: ResMut<M8ScreenState>) {
+    commands.0.push(M8Command::DrawRectangle {
+        pos: POSITION.as_u16vec2(),
+        size: SIZE.as_u16vec2(),
+        colour: COLOUR,
+    });
+    screen.drawn = true;
+}
+
+/// The samples of the waveform injected every frame.
+#[derive(Resource)]
+struct Trace(Vec<u8>);
+
+fn inject_trace(
+    trace: Res<Trace>,
+    mut commands: ResMut<M8Commands>,
+    mut screen: ResMut<M8ScreenState>,
+) {
+    commands.0.push(M8Command::DrawOscilloscopeWaveform {
+        colour: COLOUR,
+        waveform: trace.0.clone(),
+    });
+    screen.drawn = true;
+}
+
+/// A headless display fed by a mock which sends nothing, so everything
+/// drawn comes from the injected commands.
+fn headless_app() -> App {
+    app_with(M8MockConnection::from_bytes(Vec::new()), false)
+}
+
+fn app_with(mock: M8MockConnection, wait_for_handshake: bool) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        M8DisplayPlugin {
+            headless: true,
+            wait_for_handshake,
+            ..default()
+        },
+        M8AssetsPlugin,
+        M8FontsPlugin::default(),
+        M8KeyMapPlugin::default(),
+        M8MacroPlugin,
+        M8SerialPlugin {
+            mock: Some(mock),
+            ..default()
+        },
+    ));
+    app
+}
+
+fn pixel(app: &App, pos: UVec2) -> Option<[u8; 4]> {
+    let display = app.world().get_resource::<M8Display>()?;
+    let images = app.world().resource::<Assets<Image>>();
+    let colour = images
+        .get(display.image())?
+        .get_color_at(pos.x, pos.y)
+        .ok()?;
+    Some(colour.to_srgba().to_u8_array())
+}
+
+#[test]
+fn draw_rectangle_paints_the_display() {
+    let mut app = headless_app();
+    app.add_systems(
+        Update,
+        inject_rectangle
+            .after(M8UpdateSystems::Decode)
+            .before(M8UpdateSystems::DisplayRender)
+            .run_if(in_state(M8LoadingState::Running)),
+    );
+    app.finish();
+    app.cleanup();
+
+    let expected = Some(COLOUR.to_srgba().to_u8_array());
+    let started = Instant::now();
+    while pixel(&app, POSITION) != expected {
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the rectangle wasn't drawn, found {:?}",
+            pixel(&app, POSITION),
+        );
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    let last = POSITION + SIZE - UVec2::ONE;
+    assert_eq!(pixel(&app, last), expected);
+    assert_ne!(pixel(&app, POSITION - UVec2::ONE), expected);
+    assert_ne!(pixel(&app, last + UVec2::X), expected);
+    assert_ne!(pixel(&app, last + UVec2::Y), expected);
+}
+
+/// Pushes the rectangle for the decoder to pick up, as an app playing
+/// commands back without a connection does.
+fn push_rectangle(mut commands: ResMut<M8Commands>) {
+    commands.0.push(M8Command::DrawRectangle {
+        pos: POSITION.as_u16vec2(),
+        size: SIZE.as_u16vec2(),
+        colour: COLOUR,
+    });
+}
+
+#[test]
+fn pushed_commands_are_drawn_without_a_connection() {
+    let mut app = App::new();
+    app.add_plugins((
+        M8DisplayPlugin {
+            headless: true,
+            ..default()
+        },
+        M8AssetsPlugin,
+        M8FontsPlugin::default(),
+        M8DecoderPlugin::default(),
+    ));
+    app.add_systems(Update, push_rectangle.in_set(M8UpdateSystems::Input));
+    app.finish();
+    app.cleanup();
+
+    let expected = Some(COLOUR.to_srgba().to_u8_array());
+    let started = Instant::now();
+    while pixel(&app, POSITION) != expected {
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the pushed rectangle wasn't drawn, found {:?}",
+            pixel(&app, POSITION),
+        );
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert!(!app.world().contains_resource::<M8Connection>());
+
+    // The pushed commands only last the update they were pushed in.
+    app.world_mut().run_schedule(First);
+    assert!(app.world().resource::<M8Commands>().0.is_empty());
+}
+
+fn sprite_colour(app: &mut App) -> Color {
+    app.world_mut()
+        .query_filtered::<&Sprite, With<M8DisplaySprite>>()
+        .single(app.world())
+        .unwrap()
+        .color
+}
+
+fn running_and_connected(app: &App) -> bool {
+    app.world()
+        .get_resource::<State<M8LoadingState>>()
+        .is_some_and(|state| *state.get() == M8LoadingState::Running)
+        && app.world().resource::<M8Connection>().is_connected()
+}
+
+#[test]
+fn paused_decoding_isnt_shown_as_a_stall() {
+    const STALL_TIMEOUT: Duration = Duration::from_millis(50);
+
+    let mut app = headless_app();
+    app.insert_resource(M8DisconnectedDisplay {
+        stall_timeout: Some(STALL_TIMEOUT),
+        ..default()
+    });
+    app.insert_resource(M8DecodePaused(true));
+    app.finish();
+    app.cleanup();
+
+    let started = Instant::now();
+    while !running_and_connected(&app) {
+        assert!(started.elapsed() < TIMEOUT, "the mock never connected");
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    // The mock sends nothing, which would be a stall if it weren't paused.
+    let paused = Instant::now();
+    while paused.elapsed() < STALL_TIMEOUT * 4 {
+        app.update();
+        assert_eq!(sprite_colour(&mut app), Color::WHITE);
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    app.insert_resource(M8DecodePaused(false));
+    while sprite_colour(&mut app) == Color::WHITE {
+        assert!(started.elapsed() < TIMEOUT, "the stall was never shown");
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn short_waveform_clears_a_taller_one() {
+    // Samples of 0 reach the top of the oscilloscope, 12 stays low in it.
+    const TOP: UVec2 = UVec2::new(10, 0);
+    const LOW: UVec2 = UVec2::new(10, 12);
+    // The short trace also covers fewer columns than the tall one.
+    const SHORT_SAMPLES: u32 = 32;
+
+    let mut app = headless_app();
+    app.insert_resource(Trace(vec![0; 64]));
+    app.add_systems(
+        Update,
+        inject_trace
+            .after(M8UpdateSystems::Decode)
+            .before(M8UpdateSystems::DisplayRender)
+            .run_if(in_state(M8LoadingState::Running)),
+    );
+    app.finish();
+    app.cleanup();
+
+    let expected = Some(COLOUR.to_srgba().to_u8_array());
+    let started = Instant::now();
+    while pixel(&app, TOP) != expected {
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the tall waveform wasn't drawn, found {:?}",
+            pixel(&app, TOP),
+        );
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    app.insert_resource(Trace(vec![LOW.y as u8; SHORT_SAMPLES as usize]));
+    app.update();
+
+    // Only the short trace is left anywhere in the oscilloscope.
+    let scope = app.world().resource::<M8ScopeRegion>().0;
+    for y in scope.min.y..scope.max.y {
+        for x in scope.min.x..scope.max.x {
+            let on_trace = y == LOW.y && x < SHORT_SAMPLES;
+            assert_eq!(
+                pixel(&app, UVec2::new(x, y)) == expected,
+                on_trace,
+                "pixel ({x}, {y}) is wrong after the short waveform",
+            );
+        }
+    }
+}
+
+#[test]
+fn handshake_frame_is_drawn_when_waiting_for_it() {
+    // The M8 redraws its screen right after identifying itself.
+    let mock = M8MockConnection::from_commands(&[
+        M8Command::SystemInfo {
+            hardware_type: 1,
+            major: 6,
+            minor: 0,
+            patch: 0,
+            font_mode: 0,
+        },
+        M8Command::DrawRectangle {
+            pos: POSITION.as_u16vec2(),
+            size: SIZE.as_u16vec2(),
+            colour: COLOUR,
+        },
+    ])
+    .with_read_interval(Duration::from_millis(1));
+    let mut app = app_with(mock, true);
+    app.finish();
+    app.cleanup();
+
+    let started = Instant::now();
+    while !app.world().contains_resource::<M8DeviceInfo>() {
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the M8 never identified itself"
+        );
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    assert_eq!(pixel(&app, POSITION), Some(COLOUR.to_srgba().to_u8_array()));
+}
@@ -0,0 +1,136 @@
+//! Checks each rotation turns the corners of the display where a monitor
+//! mounted that way needs them, with or without the zoom plugin.
+
+#![cfg(feature = "render")]
+
+use bevy::{camera::ScalingMode, prelude::*};
+use bevy_m8::prelude::*;
+
+const DISPLAY: Vec2 = Vec2::new(320.0, 240.0);
+
+/// The corners of a box centred on the origin, y pointing up as in the
+/// sprite's space: top left, top right, bottom right and bottom left.
+fn corners(size: Vec2) -> [Vec2; 4] {
+    let half = size / 2.0;
+    [
+        Vec2::new(-half.x, half.y),
+        Vec2::new(half.x, half.y),
+        Vec2::new(half.x, -half.y),
+        Vec2::new(-half.x, -half.y),
+    ]
+}
+
+/// Where the display's corners end up on screen, in the order of
+/// [corners].
+fn rotated_corners(rotation: M8DisplayRotation) -> [Vec2; 4] {
+    corners(DISPLAY).map(|corner| (rotation.quat() * corner.extend(0.0)).truncate())
+}
+
+fn assert_corners(actual: [Vec2; 4], expected: [Vec2; 4]) {
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-3),
+            "{:?} isn't {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn corners_turn_clockwise() {
+    let [top_left, top_right, bottom_right, bottom_left] = corners(DISPLAY);
+    assert_corners(
+        rotated_corners(M8DisplayRotation::Deg0),
+        [top_left, top_right, bottom_right, bottom_left],
+    );
+    // Upside down, every corner swaps with the opposite one.
+    assert_corners(
+        rotated_corners(M8DisplayRotation::Deg180),
+        [bottom_right, bottom_left, top_left, top_right],
+    );
+
+    // A quarter turn lands in the portrait box, the top left corner of the
+    // display in its top right for a clockwise turn.
+    let [top_left, top_right, bottom_right, bottom_left] = corners(DISPLAY.yx());
+    assert_corners(
+        rotated_corners(M8DisplayRotation::Deg90),
+        [top_right, bottom_right, bottom_left, top_left],
+    );
+    assert_corners(
+        rotated_corners(M8DisplayRotation::Deg270),
+        [bottom_left, top_left, top_right, bottom_right],
+    );
+}
+
+#[test]
+fn rotated_size_bounds_the_corners() {
+    for rotation in [
+        M8DisplayRotation::Deg0,
+        M8DisplayRotation::Deg90,
+        M8DisplayRotation::Deg180,
+        M8DisplayRotation::Deg270,
+    ] {
+        let size = rotation.rotate_size(DISPLAY);
+        assert_eq!(size == DISPLAY.yx(), rotation.swaps_axes());
+        let max = rotated_corners(rotation)
+            .into_iter()
+            .fold(Vec2::ZERO, |max, corner| max.max(corner.abs()));
+        assert!(
+            max.abs_diff_eq(size / 2.0, 1e-3),
+            "{:?} spans {:?}, not {:?}",
+            rotation,
+            max * 2.0,
+            size
+        );
+    }
+}
+
+#[test]
+fn next_cycles_back_to_the_start() {
+    let mut rotation = M8DisplayRotation::default();
+    let mut turns = Vec::new();
+    for _ in 0..5 {
+        turns.push(rotation.quarter_turns());
+        rotation = rotation.next();
+    }
+    assert_eq!(turns, [0, 1, 2, 3, 0]);
+}
+
+#[test]
+fn rotation_plugin_turns_the_display_without_zoom() {
+    let mut app = App::new();
+    app.add_plugins(M8RotationPlugin {
+        rotation: M8DisplayRotation::Deg90,
+    });
+    app.init_resource::<ButtonInput<KeyCode>>();
+    app.insert_resource(M8DisplayDimensions {
+        width: 320,
+        height: 240,
+    });
+    let sprite = app
+        .world_mut()
+        .spawn((M8DisplaySprite, Transform::default()))
+        .id();
+    let camera = app
+        .world_mut()
+        .spawn((
+            M8DisplayCamera,
+            Projection::Orthographic(OrthographicProjection::default_2d()),
+        ))
+        .id();
+    app.update();
+
+    let transform = app.world().get::<Transform>(sprite).unwrap();
+    assert_eq!(transform.rotation, M8DisplayRotation::Deg90.quat());
+    let Some(Projection::Orthographic(orthographic)) = app.world().get::<Projection>(camera) else {
+        panic!("the display camera isn't orthographic");
+    };
+    assert!(matches!(
+        orthographic.scaling_mode,
+        ScalingMode::Fixed {
+            width: 240.0,
+            height: 320.0
+        }
+    ));
+}
@@ -0,0 +1,118 @@
+//! Checks the screen counts as changed only in frames with draw commands,
+//! so render work is skipped while the M8 is idle.
+
+#![cfg(feature = "bevy")]
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_m8::prelude::*;
+
+/// How long the app may take to load and see frames with and without
+/// commands.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How many frames of each kind to wait for.
+const FRAMES: usize = 3;
+
+const SYSTEM_INFO: M8Command = M8Command::SystemInfo {
+    hardware_type: 3,
+    major: 6,
+    minor: 0,
+    patch: 0,
+    font_mode: 0,
+};
+
+fn waveform() -> M8Command {
+    M8Command::DrawOscilloscopeWaveform {
+        colour: Color::WHITE,
+        waveform: vec![8; 16],
+    }
+}
+
+fn rectangle() -> M8Command {
+    M8Command::DrawRectangle {
+        pos: Position::new(0, 0),
+        size: Size::new(4, 4),
+        colour: Color::WHITE,
+    }
+}
+
+#[test]
+fn system_info_and_empty_frames_leave_the_screen_unchanged() {
+    assert_eq!(
+        M8ScreenState::from_commands(&[]),
+        M8ScreenState {
+            drawn: false,
+            waveform_only: false,
+        }
+    );
+    assert_eq!(
+        M8ScreenState::from_commands(&[SYSTEM_INFO]),
+        M8ScreenState {
+            drawn: false,
+            waveform_only: false,
+        }
+    );
+}
+
+#[test]
+fn waveforms_alone_are_flagged() {
+    assert_eq!(
+        M8ScreenState::from_commands(&[SYSTEM_INFO, waveform()]),
+        M8ScreenState {
+            drawn: true,
+            waveform_only: true,
+        }
+    );
+    assert_eq!(
+        M8ScreenState::from_commands(&[waveform(), rectangle()]),
+        M8ScreenState {
+            drawn: true,
+            waveform_only: false,
+        }
+    );
+}
+
+#[test]
+fn decoding_system_info_only_skips_the_frame() {
+    // Slower than the updates, so some frames decode nothing at all.
+    let mock = M8MockConnection::from_commands(&[SYSTEM_INFO])
+        .with_read_interval(Duration::from_millis(20));
+    let plugin = M8Plugin::default().with_mock(mock);
+    #[cfg(feature = "render")]
+    let plugin = plugin.with_headless(true);
+
+    let mut app = App::new();
+    app.add_plugins(plugin);
+    app.finish();
+    app.cleanup();
+
+    let mut system_info_frames = 0;
+    let mut empty_frames = 0;
+    let started = Instant::now();
+    while system_info_frames < FRAMES || empty_frames < FRAMES {
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "saw {} frames with the system info and {} without",
+            system_info_frames,
+            empty_frames
+        );
+        app.update();
+        let commands = &app.world().resource::<M8Commands>().0;
+        if commands.is_empty() {
+            empty_frames += 1;
+        } else {
+            assert!(commands.iter().all(|command| *command == SYSTEM_INFO));
+            system_info_frames += 1;
+        }
+        assert_eq!(
+            *app.world().resource::<M8ScreenState>(),
+            M8ScreenState::default()
+        );
+        thread::sleep(Duration::from_millis(5));
+    }
+}
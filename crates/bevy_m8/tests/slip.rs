@@ -0,0 +1,63 @@
+//! Checks the stream decoder keeps packets split across chunks, in
+//! particular a chunk ending right after a SLIP escape.
+
+#![cfg(feature = "bevy")]
+
+use bevy::prelude::*;
+use bevy_m8::{M8StreamDecoder, encode_stream, prelude::*};
+
+/// The SLIP escape, which the colours below force into the stream.
+const SLIP_ESC: u8 = 0xDB;
+
+fn decode_chunks(chunks: &[&[u8]]) -> (Vec<M8Command>, usize) {
+    let mut decoder = M8StreamDecoder::new();
+    let mut commands = Vec::new();
+    let mut errors = 0;
+    for chunk in chunks {
+        errors += decoder.decode(chunk, |command| commands.push(command.clone()));
+    }
+    (commands, errors)
+}
+
+fn escaped_commands() -> [M8Command; 2] {
+    [
+        // 0xC0 is SLIP_END and 0xDB is SLIP_ESC, both sent escaped.
+        M8Command::DrawRectangle {
+            pos: Position::new(40, 0),
+            size: Size::new(10, 10),
+            colour: Color::srgb_u8(0xC0, 0x10, 0x20),
+        },
+        M8Command::DrawCharacter {
+            c: b'A',
+            pos: Position::new(8, 16),
+            foreground: Color::srgb_u8(0xDB, 0xC0, 0xDB),
+            background: Color::srgb_u8(0, 0, 0),
+        },
+    ]
+}
+
+#[test]
+fn trailing_escape_is_kept_for_the_next_chunk() {
+    let commands = escaped_commands();
+    let stream = encode_stream(&commands[..1]);
+    let escape = stream.iter().position(|&byte| byte == SLIP_ESC).unwrap();
+
+    let (decoded, errors) = decode_chunks(&[&stream[..=escape], &stream[escape + 1..]]);
+    assert_eq!(errors, 0);
+    assert_eq!(decoded, commands[..1]);
+}
+
+#[test]
+fn every_chunk_boundary_decodes_the_same() {
+    let commands = escaped_commands();
+    let stream = encode_stream(&commands);
+
+    for split in 0..=stream.len() {
+        let (decoded, errors) = decode_chunks(&[&stream[..split], &stream[split..]]);
+        assert_eq!(errors, 0, "split at {split}");
+        assert_eq!(decoded, commands, "split at {split}");
+    }
+
+    let bytes: Vec<&[u8]> = stream.chunks(1).collect();
+    assert_eq!(decode_chunks(&bytes), (commands.to_vec(), 0));
+}
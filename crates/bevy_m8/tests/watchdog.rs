@@ -0,0 +1,163 @@
+//! Drives the display watchdog with synthetic commands under a manually
+//! advanced clock, with a mock M8 which reports what it was sent.
+
+#![cfg(feature = "bevy")]
+
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::DiagnosticsPlugin, prelude::*, state::app::StatesPlugin, time::TimeUpdateStrategy,
+};
+use bevy_m8::prelude::*;
+use crossbeam_channel::{Receiver, unbounded};
+
+/// How far the mocked clock advances every update, the most virtual time
+/// advances in one.
+const FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// How long a write may take to reach the mock.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The commands the next update decodes, in place of the M8's.
+#[derive(Resource, Default)]
+struct Script(Vec<M8Command>);
+
+fn inject_commands(mut script: ResMut<Script>, mut commands: ResMut<M8Commands>) {
+    commands.0.append(&mut script.0);
+}
+
+fn app() -> (App, Receiver<Vec<u8>>) {
+    app_with(false)
+}
+
+fn app_with(scrubber: bool) -> (App, Receiver<Vec<u8>>) {
+    let (written, writes) = unbounded();
+    let mock = M8MockConnection::from_bytes(Vec::new()).with_written(written);
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        DiagnosticsPlugin,
+        M8SerialPlugin {
+            mock: Some(mock),
+            ..default()
+        },
+        M8WatchdogPlugin,
+    ));
+    if scrubber {
+        app.add_plugins(M8ScrubberPlugin);
+        app.init_resource::<ButtonInput<KeyCode>>();
+    }
+    app.insert_state(M8LoadingState::Running);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(FRAME_TIME));
+    app.init_resource::<Script>();
+    app.add_systems(
+        Update,
+        inject_commands
+            .after(M8UpdateSystems::Decode)
+            .before(M8UpdateSystems::DisplayRender),
+    );
+    app.finish();
+    app.cleanup();
+
+    // The enable handshake is sent as the mock connects.
+    assert_eq!(next_write(&writes), b"E");
+    assert_eq!(next_write(&writes), b"R");
+    (app, writes)
+}
+
+fn next_write(writes: &Receiver<Vec<u8>>) -> Vec<u8> {
+    writes
+        .recv_timeout(WRITE_TIMEOUT)
+        .expect("nothing was written to the M8")
+}
+
+fn waveform() -> M8Command {
+    M8Command::DrawOscilloscopeWaveform {
+        colour: Color::WHITE,
+        waveform: vec![8; 16],
+    }
+}
+
+/// Runs `frames` updates, each decoding `command` if set, and returns the
+/// frames the watchdog reset or reconnected in.
+fn run(app: &mut App, frames: u32, command: Option<M8Command>) -> (Vec<u32>, Vec<u32>) {
+    let mut resets = Vec::new();
+    let mut reconnects = Vec::new();
+    for frame in 0..frames {
+        app.world_mut()
+            .resource_mut::<Script>()
+            .0
+            .extend(command.clone());
+        let before = *app.world().resource::<M8WatchdogStats>();
+        app.update();
+        let after = *app.world().resource::<M8WatchdogStats>();
+        if after.resets > before.resets {
+            resets.push(frame);
+        }
+        if after.reconnects > before.reconnects {
+            reconnects.push(frame);
+        }
+    }
+    (resets, reconnects)
+}
+
+#[test]
+fn drawing_keeps_the_watchdog_quiet() {
+    let (mut app, _writes) = app();
+    let (resets, reconnects) = run(&mut app, 80, Some(waveform()));
+    assert!(resets.is_empty(), "reset in frames {:?}", resets);
+    assert!(reconnects.is_empty());
+}
+
+#[test]
+fn stale_display_is_reset_with_a_growing_backoff() {
+    let (mut app, writes) = app();
+    run(&mut app, 1, Some(waveform()));
+
+    // Key states show the M8 is alive, so it is never reconnected.
+    let key_state = M8Command::KeyPressState { keys: 0 };
+    let (resets, reconnects) = run(&mut app, 130, Some(key_state));
+    // Two seconds without drawing, then waits of four, eight and sixteen
+    // seconds, capped at thirty.
+    assert_eq!(resets, [7, 23, 55, 119]);
+    assert!(reconnects.is_empty());
+    for _ in &resets {
+        assert_eq!(next_write(&writes), b"R");
+    }
+
+    // Drawing again starts the backoff over.
+    run(&mut app, 1, Some(waveform()));
+    let (resets, _) = run(&mut app, 10, None);
+    assert_eq!(resets, [7]);
+}
+
+#[test]
+fn silent_m8_is_reconnected_once_the_resets_fail() {
+    let (mut app, writes) = app();
+    let (resets, reconnects) = run(&mut app, 124, None);
+    assert_eq!(resets, [8, 24, 56]);
+    assert_eq!(reconnects, [120]);
+
+    for _ in &resets {
+        assert_eq!(next_write(&writes), b"R");
+    }
+    // The mock is opened again, and enabled like any new connection.
+    assert_eq!(next_write(&writes), b"E");
+    assert_eq!(next_write(&writes), b"R");
+}
+
+#[test]
+fn paused_scrubber_keeps_the_watchdog_quiet() {
+    let (mut app, _writes) = app_with(true);
+    // Nothing is let through to be drawn, nor decoded at all.
+    let (resets, reconnects) = run(&mut app, 130, None);
+    assert!(resets.is_empty(), "reset in frames {:?}", resets);
+    assert!(reconnects.is_empty());
+
+    // Once playing, a silent M8 is reset again.
+    app.world_mut().resource_mut::<M8Scrubber>().playing = true;
+    let (resets, _) = run(&mut app, 10, None);
+    assert!(!resets.is_empty());
+}
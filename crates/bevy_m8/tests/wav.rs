@@ -0,0 +1,37 @@
+//! Checks audio recordings are written as valid multi-channel WAV files.
+
+#![cfg(feature = "bevy")]
+
+use bevy_m8::prelude::*;
+
+#[test]
+fn stereo_recording_has_a_matching_header() {
+    let path = std::env::temp_dir().join(format!("bevy_m8_wav_{}.wav", std::process::id()));
+
+    // Left and right differ in sign, to catch swapped or dropped channels.
+    let frames: Vec<f32> = (0..64)
+        .flat_map(|i| {
+            let sample = i as f32 / 64.0;
+            [sample, -sample]
+        })
+        .collect();
+
+    let recorder = M8WavRecorder::create(&path, 2, 44_100).unwrap();
+    for chunk in frames.chunks(16) {
+        recorder.record(chunk);
+    }
+    drop(recorder);
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    let spec = reader.spec();
+    assert_eq!(spec.channels, 2);
+    assert_eq!(spec.sample_rate, 44_100);
+    assert_eq!(spec.bits_per_sample, 32);
+    assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+    assert_eq!(reader.duration(), 64);
+
+    let samples: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+    assert_eq!(samples, frames);
+
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,32 @@
+//! Checks the mapping of oscilloscope samples to display pixels.
+
+#![cfg(feature = "bevy")]
+
+use bevy::prelude::*;
+use bevy_m8::prelude::*;
+
+#[test]
+fn waveform_points_follow_the_samples() {
+    let command = M8Command::DrawOscilloscopeWaveform {
+        colour: Color::WHITE,
+        waveform: vec![0, 4, 8, 16, 200],
+    };
+
+    // At the M8's own height every sample is its row, and samples below
+    // the oscilloscope stay on its bottom row.
+    let points: Vec<_> = command.waveform_points(17).collect();
+    assert_eq!(points, [(0, 0), (1, 4), (2, 8), (3, 16), (4, 16)]);
+
+    // No point falls below the oscilloscope's last row.
+    let points: Vec<_> = command.waveform_points(33).collect();
+    assert_eq!(points, [(0, 0), (1, 8), (2, 16), (3, 32), (4, 32)]);
+
+    let points: Vec<_> = command.waveform_points(0).collect();
+    assert_eq!(points, [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]);
+}
+
+#[test]
+fn other_commands_have_no_waveform_points() {
+    let command = M8Command::KeyPressState { keys: 0 };
+    assert_eq!(command.waveform_points(16).count(), 0);
+}
@@ -0,0 +1,178 @@
+//! Checks the zoom presets and the display view are applied to the camera
+//! and kept within the display.
+
+#![cfg(feature = "render")]
+
+use bevy::{camera::ScalingMode, prelude::*, window::PrimaryWindow};
+use bevy_m8::prelude::*;
+
+fn app(dimensions: M8DisplayDimensions) -> App {
+    app_with(dimensions, M8DisplayRotation::Deg0)
+}
+
+fn app_with(dimensions: M8DisplayDimensions, rotation: M8DisplayRotation) -> App {
+    let mut app = App::new();
+    app.add_plugins((M8ZoomPlugin, M8RotationPlugin { rotation }));
+    app.init_resource::<ButtonInput<KeyCode>>();
+    app.insert_resource(dimensions);
+    app.world_mut()
+        .spawn((M8DisplaySprite, Sprite::default(), Transform::default()));
+    app.world_mut().spawn((
+        M8DisplayCamera,
+        Projection::Orthographic(OrthographicProjection::default_2d()),
+        Transform::default(),
+    ));
+    app
+}
+
+fn camera(app: &mut App) -> (OrthographicProjection, Vec3) {
+    let (projection, transform) = app
+        .world_mut()
+        .query_filtered::<(&Projection, &Transform), With<M8DisplayCamera>>()
+        .single(app.world())
+        .unwrap();
+    let Projection::Orthographic(orthographic) = projection else {
+        panic!("the display camera isn't orthographic");
+    };
+    (orthographic.clone(), transform.translation)
+}
+
+#[test]
+fn view_scales_and_pans_the_camera() {
+    let mut app = app(M8DisplayDimensions {
+        width: 320,
+        height: 240,
+    });
+    app.insert_resource(M8DisplayView {
+        scale: 2.0,
+        offset: Vec2::new(40.0, -30.0),
+        ..default()
+    });
+    app.update();
+
+    let (orthographic, translation) = camera(&mut app);
+    assert_eq!(orthographic.scale, 0.5);
+    assert_eq!(translation.truncate(), Vec2::new(40.0, -30.0));
+    assert!(matches!(
+        orthographic.scaling_mode,
+        ScalingMode::Fixed {
+            width: 320.0,
+            height: 240.0
+        }
+    ));
+}
+
+#[test]
+fn view_offset_clamps_to_the_display_dimensions() {
+    // The Model:02's screen is larger than the constants.
+    let mut app = app(M8DisplayDimensions {
+        width: 480,
+        height: 320,
+    });
+    app.insert_resource(M8DisplayView {
+        scale: 4.0,
+        offset: Vec2::new(1000.0, -1000.0),
+        ..default()
+    });
+    app.update();
+
+    // A quarter of the display is in view, so the centre can move up to
+    // three eighths of it either way.
+    let (_, translation) = camera(&mut app);
+    assert_eq!(translation.truncate(), Vec2::new(180.0, -120.0));
+}
+
+#[test]
+fn view_offset_clamps_to_the_zoom_region() {
+    let mut app = app(M8DisplayDimensions {
+        width: 320,
+        height: 240,
+    });
+    app.insert_resource(M8Zoom {
+        enabled: true,
+        region: URect::new(0, 0, 320, 40),
+    });
+    app.insert_resource(M8DisplayView {
+        scale: 2.0,
+        offset: Vec2::new(-1000.0, 1000.0),
+        ..default()
+    });
+    app.update();
+
+    let (orthographic, translation) = camera(&mut app);
+    assert_eq!(translation.truncate(), Vec2::new(-80.0, 10.0));
+    assert!(matches!(
+        orthographic.scaling_mode,
+        ScalingMode::AutoMin {
+            min_width: 320.0,
+            min_height: 40.0
+        }
+    ));
+}
+
+#[test]
+fn presets_follow_the_display_dimensions() {
+    let model_02 = M8DisplayDimensions {
+        width: 480,
+        height: 320,
+    };
+    let [scope, full, info] = M8Zoom::presets(model_02);
+    assert_eq!(scope, URect::new(0, 0, 480, 32));
+    assert_eq!(full, URect::new(0, 0, 480, 320));
+    assert_eq!(info, URect::new(0, 267, 480, 320));
+}
+
+#[test]
+fn integer_scaling_keeps_display_pixels_whole() {
+    let mut app = app(M8DisplayDimensions {
+        width: 320,
+        height: 240,
+    });
+    // A 1280x720 window fits the display 3 times over, 4.5 times once
+    // scaled, which is rounded down.
+    app.world_mut().spawn((Window::default(), PrimaryWindow));
+    app.insert_resource(M8DisplayView {
+        scale: 1.5,
+        offset: Vec2::new(10.3, 0.0),
+        integer_scaling: true,
+    });
+    app.update();
+
+    let (orthographic, translation) = camera(&mut app);
+    assert!(matches!(orthographic.scaling_mode, ScalingMode::WindowSize));
+    assert_eq!(orthographic.scale, 0.25);
+    // The left edge of the display lands on a whole window pixel.
+    assert_eq!(translation.truncate(), Vec2::new(10.25, 0.0));
+    let left = 640.0 - (160.0 + translation.x) * 4.0;
+    assert_eq!(left, left.round());
+}
+
+#[test]
+fn integer_scaling_fits_the_turned_display() {
+    let mut app = app_with(
+        M8DisplayDimensions {
+            width: 320,
+            height: 240,
+        },
+        M8DisplayRotation::Deg90,
+    );
+    // Turned on its side the display is 320 pixels tall, which only fits
+    // a 720 pixel tall window twice.
+    app.world_mut().spawn((Window::default(), PrimaryWindow));
+    app.insert_resource(M8DisplayView {
+        integer_scaling: true,
+        ..default()
+    });
+    app.update();
+
+    let (orthographic, translation) = camera(&mut app);
+    assert!(matches!(orthographic.scaling_mode, ScalingMode::WindowSize));
+    assert_eq!(orthographic.scale, 0.5);
+    assert_eq!(translation.truncate(), Vec2::ZERO);
+    let sprite = app
+        .world_mut()
+        .query_filtered::<&Transform, With<M8DisplaySprite>>()
+        .single(app.world())
+        .unwrap();
+    assert_eq!(sprite.rotation, M8DisplayRotation::Deg90.quat());
+}